@@ -0,0 +1,82 @@
+//! Minimal MCP server over stdio, used by the `--mcp-server` tests.
+//!
+//! Tools: `echo` (read-only, returns `text` repeated `repeat` times back to back) and
+//! `fail` (always returns an error result).
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+fn main() {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    // Servers are allowed to log to stdout; clients must skip lines that are not JSON-RPC.
+    writeln!(stdout, "mcp stub starting").unwrap();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let Ok(request) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let Some(id) = request.get("id").cloned() else {
+            continue;
+        };
+        let method = request["method"].as_str().unwrap_or_default();
+        let response = match method {
+            "initialize" => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "mcp-stub", "version": "0.1.0" }
+                }
+            }),
+            "tools/list" => json!({ "jsonrpc": "2.0", "id": id, "result": list_tools(&request) }),
+            "tools/call" => json!({ "jsonrpc": "2.0", "id": id, "result": call_tool(&request) }),
+            _ => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": "Method not found" }
+            }),
+        };
+        writeln!(stdout, "{response}").unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+/// Returns one tool per page to exercise `nextCursor` pagination.
+fn list_tools(request: &Value) -> Value {
+    let echo = json!({
+        "name": "echo",
+        "description": "Echo text back.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "text": { "type": "string" },
+                "repeat": { "type": "integer" }
+            },
+            "required": ["text"]
+        },
+        "annotations": { "readOnlyHint": true }
+    });
+    let fail = json!({
+        "name": "fail",
+        "description": "Always fails.",
+        "inputSchema": { "type": "object", "properties": {} }
+    });
+    match request.pointer("/params/cursor").and_then(Value::as_str) {
+        Some("page-2") => json!({ "tools": [fail] }),
+        _ => json!({ "tools": [echo], "nextCursor": "page-2" }),
+    }
+}
+
+fn call_tool(request: &Value) -> Value {
+    let args = &request["params"]["arguments"];
+    match request.pointer("/params/name").and_then(Value::as_str) {
+        Some("echo") => {
+            let text = args["text"].as_str().unwrap_or_default();
+            let repeat = args["repeat"].as_u64().unwrap_or(1) as usize;
+            json!({ "content": [{ "type": "text", "text": text.repeat(repeat) }] })
+        }
+        _ => json!({ "content": [{ "type": "text", "text": "boom" }], "isError": true }),
+    }
+}
@@ -1,8 +1,22 @@
 use crate::constants::DEFAULT_CONTINUE_MESSAGE;
+use crate::context::{
+    chars_to_tokens, estimate_tokens, insert_summary, render_transcript, split_oldest_turns,
+    truncate_old_tool_outputs, Compaction, ContextStrategy, Prune, COMPACTED_TOOL_OUTPUT_CHARS,
+    CONTEXT_COMPACT_THRESHOLD, SUMMARY_SYSTEM_PROMPT,
+};
+use crate::grade::{Grade, Grader};
 use crate::logger::Logger;
+use crate::pricing::Pricing;
 use crate::prompt::build_system_prompt;
-use crate::protocol::{ApiErrorResponse, CompletionResult, TokenUsage, Usage};
-use crate::tools::{execute_tool, parse_patch_changes, tool_error, truncate, ToolExecContext};
+use crate::protocol::{ApiErrorResponse, CompletionResult, Message, TokenUsage, ToolCall, Usage};
+use crate::provider::{
+    names_missing_model, ModelUnavailable, Provider, RequestParams, SamplingParams,
+};
+use crate::resume::{dangling_tool_calls, ResumeState};
+use crate::retry::{parse_retry_after, RetryPolicy};
+use crate::sandbox::Sandbox;
+use crate::stream::StreamInterrupted;
+use crate::tools::{tool_command_string, tool_error, truncate, ToolExecContext, ToolRegistry};
 use anyhow::{anyhow, Context, Result};
 use reqwest::blocking::Client;
 use reqwest::header::HeaderMap;
@@ -10,115 +24,269 @@ use reqwest::header::RETRY_AFTER;
 use reqwest::StatusCode;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use std::collections::HashSet;
 use std::error::Error as StdError;
+use std::io::BufReader;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant};
 
 type ToolLogging = (Option<(String, String)>, Vec<Value>);
 
 pub(crate) struct Agent {
     client: Client,
+    provider: Box<dyn Provider>,
     base_url: String,
+    /// The model requests currently go to.
     model: String,
+    /// The model each step starts with: `--model`, or a fallback once `--fallback-sticky`
+    /// switched to it.
+    step_model: String,
+    fallback_models: Vec<String>,
+    fallback_sticky: bool,
     api_key: String,
     session_id: String,
+    registry: ToolRegistry,
     tools: Vec<Value>,
-    messages: Vec<Value>,
-    temperature: Option<f64>,
+    messages: Vec<Message>,
+    sampling: SamplingParams,
+    stream: bool,
     max_steps: Option<usize>,
     time_limit: Option<Duration>,
+    max_cost: Option<f64>,
+    max_total_tokens: Option<u64>,
+    pricing: Option<Pricing>,
     max_tool_output_chars: usize,
+    patch_max_offset: usize,
+    context_window: Option<u64>,
+    context_strategy: ContextStrategy,
+    parallel_tools: bool,
+    sandbox: Sandbox,
     cwd: PathBuf,
     submit_enabled: bool,
-    web_search_enabled: bool,
     retry_429: bool,
+    retry: Arc<RetryPolicy>,
+    redact_reasoning: bool,
     logger: Logger,
     token_usage_total: TokenUsage,
+    /// None until some request's cost is known.
+    cost_total_usd: Option<f64>,
+    cost_unknown_warned: bool,
     next_item_id: u64,
+    /// `reason` of the last `turn.completed`.
+    end_reason: Option<&'static str>,
 }
 
 impl Agent {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         client: Client,
+        provider: Box<dyn Provider>,
         base_url: String,
         model: String,
+        fallback_models: Vec<String>,
+        fallback_sticky: bool,
         api_key: String,
         session_id: String,
-        tools: Vec<Value>,
-        temperature: Option<f64>,
+        registry: ToolRegistry,
+        sampling: SamplingParams,
+        stream: bool,
         max_steps: Option<usize>,
         time_limit: Option<Duration>,
+        max_cost: Option<f64>,
+        max_total_tokens: Option<u64>,
+        pricing: Option<Pricing>,
         max_tool_output_chars: usize,
+        patch_max_offset: usize,
+        context_window: Option<u64>,
+        context_strategy: ContextStrategy,
+        parallel_tools: bool,
+        sandbox: Sandbox,
         cwd: PathBuf,
         submit_enabled: bool,
-        web_search_enabled: bool,
         retry_429: bool,
+        retry: RetryPolicy,
+        redact_reasoning: bool,
         logger: Logger,
     ) -> Self {
         Self {
             client,
+            provider,
             base_url,
+            step_model: model.clone(),
             model,
+            fallback_models,
+            fallback_sticky,
             api_key,
             session_id,
-            tools,
+            tools: registry.schemas(),
+            registry,
             messages: Vec::new(),
-            temperature,
+            sampling,
+            stream,
             max_steps,
             time_limit,
+            max_cost,
+            max_total_tokens,
+            pricing,
             max_tool_output_chars,
+            patch_max_offset,
+            context_window,
+            context_strategy,
+            parallel_tools,
+            sandbox,
             cwd,
             submit_enabled,
-            web_search_enabled,
             retry_429,
+            retry: Arc::new(retry),
+            redact_reasoning,
             logger,
             token_usage_total: TokenUsage::default(),
+            cost_total_usd: None,
+            cost_unknown_warned: false,
             next_item_id: 0,
+            end_reason: None,
         }
     }
 
     pub(crate) fn run(&mut self, task: String) -> Result<String> {
-        let start = Instant::now();
-        let mut steps = 0usize;
-
         let (system_prompt, agents_text) = build_system_prompt(
             &self.cwd,
             self.max_steps,
             self.time_limit,
             self.submit_enabled,
-            self.web_search_enabled,
+            self.parallel_tools,
+            &self.registry,
+            &self.sandbox,
         )?;
         self.log_thread_started()?;
         self.log_turn_started(&task, &system_prompt, agents_text.as_deref())?;
 
-        self.messages
-            .push(json!({"role": "system", "content": system_prompt}));
-        self.messages.push(json!({"role": "user", "content": task}));
+        self.messages.push(Message::System {
+            content: system_prompt,
+        });
+        self.messages.push(Message::User { content: task });
+
+        self.run_loop(0, Duration::ZERO)
+    }
+
+    /// Continues a run rebuilt from its JSONL log, with the remaining step and time budget.
+    pub(crate) fn resume(&mut self, state: ResumeState) -> Result<String> {
+        self.messages = state.messages;
+        self.token_usage_total = state.usage;
+        self.cost_total_usd = state.cost_usd;
+        self.next_item_id = state.next_item_id;
+        let dangling = dangling_tool_calls(&self.messages);
+        let last_is_final_text = matches!(
+            self.messages.last(),
+            Some(Message::Assistant(assistant)) if assistant.is_final_text()
+        );
+        if self.submit_enabled
+            && dangling
+                .first()
+                .is_some_and(|tc| tc.function.name == "submit")
+        {
+            return Err(anyhow!("cannot resume: the run already called submit"));
+        }
+        if last_is_final_text && !self.submit_enabled {
+            return Err(anyhow!(
+                "cannot resume: the run already ended with a final answer"
+            ));
+        }
+
+        self.log_thread_resumed(state.steps, state.elapsed)?;
+        // Tool calls that were in flight when the run died never produced a result; close them
+        // so the history stays valid for the provider.
+        for tool_call in dangling {
+            let content = tool_error(
+                "Tool execution was interrupted before completion (run resumed from log).".into(),
+            );
+            self.record_message(Message::Tool {
+                tool_call_id: tool_call.id,
+                content,
+            })?;
+        }
+        if last_is_final_text {
+            self.record_message(Message::User {
+                content: DEFAULT_CONTINUE_MESSAGE.to_string(),
+            })?;
+        }
+
+        self.run_loop(state.steps, state.elapsed)
+    }
 
+    /// Grades the run's answer and logs the result as `grade.completed`. Only a submitted or
+    /// final answer is graded; a run stopped by a budget fails without consulting the grader.
+    /// A grader that fails is logged as a failed grade with its `error`, not as a failed run.
+    pub(crate) fn grade(&mut self, grader: &Grader, answer: &str) -> Result<()> {
+        let graded = match self.end_reason {
+            Some("submit" | "final_answer") => grader.grade(answer, &self.cwd, &self.sandbox),
+            reason => Ok(Grade::failed(format!(
+                "not graded: the run ended on {} without an answer",
+                reason.unwrap_or("an unknown condition")
+            ))),
+        };
+        let mut event = match graded {
+            Ok(grade) => grade.to_json(),
+            Err(err) => json!({
+                "score": 0.0,
+                "passed": false,
+                "explanation": format!("grader failed: {err:#}"),
+                "error": format!("{err:#}"),
+            }),
+        };
+        event["type"] = json!("grade.completed");
+        event["grader"] = json!(grader.kind());
+        self.logger.log_event(&event)
+    }
+
+    fn run_loop(&mut self, mut steps: usize, elapsed_before: Duration) -> Result<String> {
+        let start = Instant::now();
         loop {
             if let Some(max_steps) = self.max_steps {
                 if steps >= max_steps {
                     let message = format!("Terminated: max_steps ({}) reached.", max_steps);
                     self.log_warning_item(&message)?;
-                    self.log_turn_completed()?;
+                    self.log_turn_completed("max_steps", steps)?;
                     return Ok(message);
                 }
             }
             if let Some(limit) = self.time_limit {
-                if start.elapsed() >= limit {
+                if elapsed_before + start.elapsed() >= limit {
                     let message = "Terminated: time_limit reached.".to_string();
                     self.log_warning_item(&message)?;
-                    self.log_turn_completed()?;
+                    self.log_turn_completed("time_limit", steps)?;
+                    return Ok(message);
+                }
+            }
+            if let Some(max_cost) = self.max_cost {
+                let spent = self.cost_total_usd.unwrap_or(0.0);
+                if spent >= max_cost {
+                    let message = format!(
+                        "Terminated: max_cost (${}) reached (spent ${:.4}).",
+                        max_cost, spent
+                    );
+                    self.log_warning_item(&message)?;
+                    self.log_turn_completed("max_cost", steps)?;
+                    return Ok(message);
+                }
+            }
+            if let Some(max_total_tokens) = self.max_total_tokens {
+                if self.token_usage_total.total_tokens.max(0) as u64 >= max_total_tokens {
+                    let message = format!(
+                        "Terminated: max_total_tokens ({}) reached.",
+                        max_total_tokens
+                    );
+                    self.log_warning_item(&message)?;
+                    self.log_turn_completed("max_total_tokens", steps)?;
                     return Ok(message);
                 }
             }
 
             steps += 1;
-            let request = self.build_request()?;
-            let completion = match self.send_request(&request) {
+            self.model.clone_from(&self.step_model);
+            self.compact_to_context_window()?;
+            let completion = match self.request_completion() {
                 Ok(result) => result,
                 Err(err) => {
                     let err_msg = err.to_string();
@@ -130,18 +298,23 @@ impl Agent {
 
                     // Context overflow: prune like basicagent (keep system + initial user task +
                     // tool-call/response pairs) and retry. We may need to prune multiple times.
-                    let mut last_len = self.messages.len();
                     let mut recovered: Option<CompletionResult> = None;
                     loop {
-                        self.messages = prune_messages(&self.messages);
-                        let new_len = self.messages.len();
-                        if new_len >= last_len {
+                        let tokens_before = estimate_tokens(&self.messages, &self.tools);
+                        let Some(prune) = self.drop_oldest_turns()? else {
                             break;
-                        }
-                        last_len = new_len;
+                        };
+                        self.log_context_compacted(
+                            "context_error",
+                            &Compaction {
+                                tokens_before,
+                                tokens_after: estimate_tokens(&self.messages, &self.tools),
+                                truncated_tool_outputs: Vec::new(),
+                                prunes: vec![prune],
+                            },
+                        )?;
 
-                        let request = self.build_request()?;
-                        match self.send_request(&request) {
+                        match self.request_completion() {
                             Ok(result) => {
                                 recovered = Some(result);
                                 break;
@@ -163,145 +336,347 @@ impl Agent {
                     } else {
                         let message = "Terminated: context length exceeded.".to_string();
                         self.log_warning_item(&message)?;
-                        self.log_turn_completed()?;
+                        self.log_turn_completed("context_length", steps)?;
                         return Ok(message);
                     }
                 }
             };
 
             let message = completion.message;
-            let content_text = message.content.clone().unwrap_or_default();
-            let mut assistant = json!({
-                "role": "assistant",
-                "content": message.content,
-            });
-            if let Some(tool_calls) = &message.tool_calls {
-                assistant["tool_calls"] = serde_json::to_value(tool_calls)?;
+            let streamed_item_id = completion.streamed_item_id;
+            if let Some(reasoning) = message.reasoning.as_deref() {
+                if !reasoning.trim().is_empty() {
+                    self.log_reasoning(reasoning)?;
+                }
             }
+            let content_text = message.content.clone().unwrap_or_default();
             if !content_text.trim().is_empty() {
-                self.log_agent_message(&content_text)?;
+                let item_id = streamed_item_id.unwrap_or_else(|| self.next_item_id());
+                self.log_agent_message_with_id(item_id, &content_text)?;
             }
-            self.messages.push(assistant);
+            self.record_message(Message::Assistant(message.clone()))?;
 
             if let Some(usage) = completion.usage {
-                self.update_usage(&usage);
+                self.update_usage(&usage, message.reasoning.as_deref())?;
             }
 
-            let tool_calls = message.tool_calls.clone().unwrap_or_default();
+            let tool_calls = message.tool_calls;
             if !tool_calls.is_empty() {
-                let mut first = true;
-                for tool_call in tool_calls {
-                    if first {
-                        first = false;
-                        if tool_call.function.name == "submit" && self.submit_enabled {
-                            let answer = parse_submit_answer(&tool_call.function.arguments)?;
-                            if !answer.trim().is_empty() {
-                                self.log_agent_message(&answer)?;
-                            }
-                            self.log_turn_completed()?;
-                            return Ok(answer);
-                        }
-
-                        let tool_name = tool_call.function.name.as_str();
-                        let (command_item, file_changes) =
-                            self.prepare_tool_logging(tool_name, &tool_call.function.arguments)?;
-                        if let Some((item_id, command)) = &command_item {
-                            self.log_command_execution_started(item_id, command)?;
-                        }
-
-                        let ctx = ToolExecContext {
-                            cwd: &self.cwd,
-                            max_output_chars: self.max_tool_output_chars,
-                        };
-                        let result = execute_tool(&tool_call, &ctx);
-                        let success = result.is_ok();
-                        let content = match result {
-                            Ok(value) => value,
-                            Err(err) => tool_error(format!("{err:#}")),
-                        };
-                        self.messages.push(json!({
-                            "role": "tool",
-                            "tool_call_id": tool_call.id,
-                            "content": content,
-                        }));
-                        self.log_tool_result(
-                            tool_name,
-                            command_item,
-                            file_changes,
-                            &content,
-                            success,
-                        )?;
-                    } else {
-                        let content =
-                            tool_error("Multiple tool calls in one step are not supported.".into());
-                        self.messages.push(json!({
-                            "role": "tool",
-                            "tool_call_id": tool_call.id,
-                            "content": content,
-                        }));
-                        self.log_warning_item(
-                            "Multiple tool calls in one step are not supported.",
-                        )?;
-                    }
+                // Baseline: only the first call runs. With --parallel-tools every call runs.
+                let runnable = if self.parallel_tools {
+                    tool_calls.len()
+                } else {
+                    1
+                };
+                let (to_run, rejected) = tool_calls.split_at(runnable);
+                if let Some(answer) = self.run_tool_calls(to_run)? {
+                    self.log_turn_completed("submit", steps)?;
+                    return Ok(answer);
+                }
+                for tool_call in rejected {
+                    let content =
+                        tool_error("Multiple tool calls in one step are not supported.".into());
+                    self.record_message(Message::Tool {
+                        tool_call_id: tool_call.id.clone(),
+                        content,
+                    })?;
+                    self.log_warning_item("Multiple tool calls in one step are not supported.")?;
                 }
                 continue;
             }
 
             if self.submit_enabled {
-                self.messages.push(json!({
-                    "role": "user",
-                    "content": DEFAULT_CONTINUE_MESSAGE,
-                }));
+                self.record_message(Message::User {
+                    content: DEFAULT_CONTINUE_MESSAGE.to_string(),
+                })?;
                 continue;
             }
 
             let final_text = message.content.unwrap_or_default();
-            self.log_turn_completed()?;
+            self.log_turn_completed("final_answer", steps)?;
             return Ok(final_text);
         }
     }
 
+    /// Runs tool calls in order and records their results. Consecutive read-only calls run
+    /// concurrently when `--parallel-tools` is set. Returns the answer if `submit` was called.
+    fn run_tool_calls(&mut self, calls: &[ToolCall]) -> Result<Option<String>> {
+        let mut i = 0;
+        while i < calls.len() {
+            let call = &calls[i];
+            if call.function.name == "submit" && self.submit_enabled {
+                let answer = parse_submit_answer(&call.function.arguments)?;
+                if !answer.trim().is_empty() {
+                    self.log_agent_message(&answer)?;
+                }
+                return Ok(Some(answer));
+            }
+            let mut end = i + 1;
+            if self.parallel_tools && self.registry.is_read_only(&call.function.name) {
+                while end < calls.len() && self.registry.is_read_only(&calls[end].function.name) {
+                    end += 1;
+                }
+            }
+            self.execute_tool_batch(&calls[i..end])?;
+            i = end;
+        }
+        Ok(None)
+    }
+
+    fn execute_tool_batch(&mut self, calls: &[ToolCall]) -> Result<()> {
+        let mut logging = Vec::with_capacity(calls.len());
+        for call in calls {
+            let (command_item, file_changes) =
+                self.prepare_tool_logging(&call.function.name, &call.function.arguments)?;
+            if let Some((item_id, command)) = &command_item {
+                self.log_command_execution_started(item_id, command)?;
+            }
+            logging.push((command_item, file_changes));
+        }
+
+        let ctx = ToolExecContext {
+            cwd: &self.cwd,
+            max_output_chars: self.max_tool_output_chars,
+            patch_max_offset: self.patch_max_offset,
+            sandbox: &self.sandbox,
+            retry: &self.retry,
+        };
+        let registry = &self.registry;
+        let results: Vec<Result<String>> = if calls.len() == 1 {
+            vec![registry.execute(&calls[0], &ctx)]
+        } else {
+            let ctx = &ctx;
+            thread::scope(|scope| {
+                let handles: Vec<_> = calls
+                    .iter()
+                    .map(|call| scope.spawn(move || registry.execute(call, ctx)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|_| Err(anyhow!("tool execution panicked")))
+                    })
+                    .collect()
+            })
+        };
+
+        for event in self.retry.take_events() {
+            self.logger.log_event(&event)?;
+        }
+        // Results are recorded in call order regardless of completion order.
+        for ((call, result), (command_item, file_changes)) in calls.iter().zip(results).zip(logging)
+        {
+            let success = result.is_ok();
+            let content = match result {
+                Ok(value) => value,
+                Err(err) => tool_error(format!("{err:#}")),
+            };
+            self.record_message(Message::Tool {
+                tool_call_id: call.id.clone(),
+                content: content.clone(),
+            })?;
+            self.log_tool_result(
+                &call.function.name,
+                command_item,
+                file_changes,
+                &content,
+                success,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Compacts the history before a request would exceed the context window, so we rarely
+    /// have to rely on the provider rejecting it.
+    fn compact_to_context_window(&mut self) -> Result<()> {
+        let Some(window) = self.context_window else {
+            return Ok(());
+        };
+        let budget = (window as f64 * CONTEXT_COMPACT_THRESHOLD) as u64;
+        let tokens_before = estimate_tokens(&self.messages, &self.tools);
+        if tokens_before <= budget {
+            return Ok(());
+        }
+        let truncated_tool_outputs =
+            truncate_old_tool_outputs(&mut self.messages, &self.tools, budget);
+        let mut prunes = Vec::new();
+        while estimate_tokens(&self.messages, &self.tools) > budget {
+            let Some(prune) = self.drop_oldest_turns()? else {
+                break;
+            };
+            prunes.push(prune);
+        }
+        if truncated_tool_outputs.is_empty() && prunes.is_empty() {
+            // Nothing left to compact; the provider will tell us if the request is too large.
+            return Ok(());
+        }
+        self.log_context_compacted(
+            "budget",
+            &Compaction {
+                tokens_before,
+                tokens_after: estimate_tokens(&self.messages, &self.tools),
+                truncated_tool_outputs,
+                prunes,
+            },
+        )
+    }
+
+    /// Removes the oldest turns according to `--context-strategy`. Returns `None` when there is
+    /// nothing left to drop.
+    fn drop_oldest_turns(&mut self) -> Result<Option<Prune>> {
+        let (mut kept, dropped) = split_oldest_turns(&self.messages);
+        if dropped.is_empty() {
+            return Ok(None);
+        }
+        let mut kept_summary = None;
+        if self.context_strategy == ContextStrategy::Summarize {
+            match self.summarize_messages(&dropped) {
+                Ok(summary) => {
+                    let mut summarized = kept.clone();
+                    insert_summary(&mut summarized, &summary);
+                    // Only keep the summary if it actually shrinks the history; otherwise a
+                    // summary of a summary could stop compaction from making progress.
+                    if estimate_tokens(&summarized, &self.tools)
+                        < estimate_tokens(&self.messages, &self.tools)
+                    {
+                        kept = summarized;
+                        kept_summary = Some(summary);
+                    }
+                }
+                Err(err) => {
+                    self.log_warning_item(&format!(
+                        "History summarization failed; dropping the oldest messages instead: {err:#}"
+                    ))?;
+                }
+            }
+        }
+        self.messages = kept;
+        Ok(Some(Prune {
+            dropped_messages: dropped.len(),
+            summary: kept_summary,
+        }))
+    }
+
+    /// Side request asking the model to summarize a span of history that is about to be dropped.
+    fn summarize_messages(&mut self, dropped: &[Message]) -> Result<String> {
+        let messages = vec![
+            Message::System {
+                content: SUMMARY_SYSTEM_PROMPT.to_string(),
+            },
+            Message::User {
+                content: render_transcript(dropped),
+            },
+        ];
+        let params = RequestParams {
+            model: &self.model,
+            sampling: &self.sampling,
+            stream: false,
+            parallel_tool_calls: false,
+        };
+        let mut request = self.provider.build_request(&params, &messages, &[])?;
+        self.sampling.apply_extra_body(&mut request);
+        let completion = self.send_request(&request, false)?;
+        if let Some(usage) = &completion.usage {
+            self.update_usage(usage, completion.message.reasoning.as_deref())?;
+        }
+        let summary = completion.message.content.unwrap_or_default();
+        if summary.trim().is_empty() {
+            return Err(anyhow!("the model returned an empty summary"));
+        }
+        self.logger.log_event(&json!({
+            "type": "context.summarized",
+            "dropped_messages": dropped.len(),
+            "summary": summary,
+            "usage": completion.usage.as_ref().map(|usage| {
+                let usage = token_usage_from_usage(usage, None);
+                json!({
+                    "input_tokens": usage.input_tokens,
+                    "cached_input_tokens": usage.cached_input_tokens,
+                    "output_tokens": usage.output_tokens,
+                })
+            }),
+        }))?;
+        Ok(summary)
+    }
+
     fn build_request(&self) -> Result<Value> {
-        let mut body = json!({
-            "model": self.model,
-            "messages": self.messages,
-            "tools": self.tools,
-            "tool_choice": "auto",
-            "parallel_tool_calls": false,
-        });
-        if let Some(temp) = self.temperature {
-            body["temperature"] = json!(temp);
+        let params = RequestParams {
+            model: &self.model,
+            sampling: &self.sampling,
+            stream: self.stream,
+            parallel_tool_calls: self.parallel_tools,
+        };
+        let mut request = self
+            .provider
+            .build_request(&params, &self.messages, &self.tools)?;
+        self.sampling.apply_extra_body(&mut request);
+        Ok(request)
+    }
+
+    /// Sends the history to the active model, moving down the `--fallback-model` chain while
+    /// models are unavailable.
+    fn request_completion(&mut self) -> Result<CompletionResult> {
+        loop {
+            let request = self.build_request()?;
+            let err = match self.send_request(&request, self.stream) {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+            if err.downcast_ref::<ModelUnavailable>().is_none() {
+                return Err(err);
+            }
+            // Fallbacks after the active model; all of them while on `--model`.
+            let next = self
+                .fallback_models
+                .iter()
+                .position(|model| *model == self.model)
+                .map_or(0, |i| i + 1);
+            let Some(fallback) = self.fallback_models.get(next).cloned() else {
+                return Err(err);
+            };
+            self.log_model_switched(&fallback, &err.to_string())?;
+            if self.fallback_sticky {
+                self.step_model.clone_from(&fallback);
+            }
+            self.model = fallback;
         }
-        Ok(body)
     }
 
-    fn send_request(&self, request: &Value) -> Result<CompletionResult> {
-        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
-        // Completions are safe to retry. Small bounded retries make us resilient against transient
+    fn send_request(&mut self, request: &Value, stream: bool) -> Result<CompletionResult> {
+        if let Some(result) = self.provider.respond_offline(request) {
+            return result;
+        }
+        let url = self.provider.endpoint(&self.base_url);
+        let label = self.provider.label();
+        // Completions are safe to retry. Bounded retries make us resilient against transient
         // stalls/timeouts while reading the response body.
-        const MAX_RETRIES: usize = 2;
-        let mut last_http_err: Option<anyhow::Error> = None;
+        let retry_policy = Arc::clone(&self.retry);
+        let mut retry = retry_policy.start("model", &self.model);
 
-        for attempt in 0..=MAX_RETRIES {
+        loop {
+            for event in retry_policy.take_events() {
+                self.logger.log_event(&event)?;
+            }
             let response = match self
-                .client
-                .post(&url)
-                .header("Authorization", format!("Bearer {}", self.api_key))
+                .provider
+                .authorize(self.client.post(&url), &self.api_key)
                 .json(request)
                 .send()
             {
                 Ok(r) => r,
                 Err(err) => {
-                    if attempt < MAX_RETRIES && should_retry_reqwest_error(&err) {
-                        sleep_backoff(attempt, None);
+                    if should_retry_reqwest_error(&err) && retry.wait(None, &err.to_string()) {
                         continue;
                     }
                     return Err(anyhow!(err)).with_context(|| {
                         format!(
-                            "OpenRouter request failed: POST {} (attempt {}/{})",
+                            "{} request failed: POST {} (attempt {}/{})",
+                            label,
                             url,
-                            attempt + 1,
-                            MAX_RETRIES + 1
+                            retry.attempt(),
+                            retry.max_attempts()
                         )
                     });
                 }
@@ -310,21 +685,46 @@ impl Agent {
             let status = response.status();
             let headers = response.headers().clone();
 
+            if stream && status.is_success() {
+                match self.read_stream(response) {
+                    Ok(result) => {
+                        retry.succeeded();
+                        return Ok(result);
+                    }
+                    Err(err) => {
+                        // A stream cut off before [DONE] is treated like a truncated body.
+                        if err.downcast_ref::<StreamInterrupted>().is_some()
+                            && retry.wait(None, &err.to_string())
+                        {
+                            continue;
+                        }
+                        return Err(err).with_context(|| {
+                            format!(
+                                "failed to read {} event stream (attempt {}/{})",
+                                label,
+                                retry.attempt(),
+                                retry.max_attempts()
+                            )
+                        });
+                    }
+                }
+            }
+
             // Read bytes first so we can retry on body-read timeouts, and decode lossily for
             // error messages (JSON should be UTF-8, but we don't want to fail formatting).
             let body_bytes = match response.bytes() {
                 Ok(b) => b,
                 Err(err) => {
-                    if attempt < MAX_RETRIES && should_retry_reqwest_error(&err) {
-                        sleep_backoff(attempt, None);
+                    if should_retry_reqwest_error(&err) && retry.wait(None, &err.to_string()) {
                         continue;
                     }
                     return Err(anyhow!(err)).with_context(|| {
                         format!(
-                            "failed to read OpenRouter response body (HTTP {}) (attempt {}/{})",
+                            "failed to read {} response body (HTTP {}) (attempt {}/{})",
+                            label,
                             status,
-                            attempt + 1,
-                            MAX_RETRIES + 1
+                            retry.attempt(),
+                            retry.max_attempts()
                         )
                     });
                 }
@@ -334,53 +734,138 @@ impl Agent {
             if !status.is_success() {
                 // Align with Codex defaults: do not blindly retry 429s unless the server
                 // provides an explicit Retry-After. This avoids retry-storming under hard limits.
-                let retry_after = headers.get(RETRY_AFTER).and_then(parse_retry_after_secs);
+                let retry_after = headers.get(RETRY_AFTER).and_then(parse_retry_after);
                 let retry_allowed = if status.as_u16() == 429 {
                     self.retry_429 || retry_after.is_some()
                 } else {
                     true
                 };
 
-                if attempt < MAX_RETRIES && retry_allowed && should_retry_status(status) {
-                    sleep_backoff(attempt, retry_after);
-                    last_http_err = Some(anyhow!(format_openrouter_http_error(
-                        &url,
-                        status.as_u16(),
-                        &headers,
-                        &body
-                    )));
+                if retry_allowed
+                    && should_retry_status(status)
+                    && retry.wait(retry_after, &format!("HTTP {}", status.as_u16()))
+                {
                     continue;
                 }
-                return Err(anyhow!(format_openrouter_http_error(
-                    &url,
-                    status.as_u16(),
-                    &headers,
-                    &body
-                )));
+                let message = format_http_error(label, &url, status.as_u16(), &headers, &body);
+                if should_retry_status(status)
+                    || (status == StatusCode::NOT_FOUND && names_missing_model(&body, &self.model))
+                {
+                    return Err(anyhow!(ModelUnavailable(message)));
+                }
+                return Err(anyhow!(message));
             }
 
-            let parsed: crate::protocol::ChatCompletionResponse = serde_json::from_str(&body)
-                .with_context(|| {
-                    let (snippet, _) = truncate(&body, 2000);
-                    format!(
-                        "OpenRouter returned an unexpected response body (HTTP {}):\n{}",
-                        status, snippet
-                    )
-                })?;
-            let choice = parsed
-                .choices
-                .into_iter()
-                .next()
-                .ok_or_else(|| anyhow!("no choices in response"))?;
-            return Ok(CompletionResult {
-                message: choice.message,
-                usage: parsed.usage,
+            retry.succeeded();
+            return self.provider.parse_response(&body).with_context(|| {
+                let (snippet, _) = truncate(&body, 2000);
+                format!(
+                    "{} returned an unexpected response body (HTTP {}):\n{}",
+                    label, status, snippet
+                )
             });
         }
+    }
 
-        // Defensive: we should have returned above. If we didn't, return the most recent HTTP
-        // error (e.g. repeated 503/429), or a generic error otherwise.
-        Err(last_http_err.unwrap_or_else(|| anyhow!("OpenRouter request failed after retries")))
+    fn read_stream(&mut self, response: reqwest::blocking::Response) -> Result<CompletionResult> {
+        let mut logger = self.logger.clone();
+        let mut item_id: Option<String> = None;
+        let mut partial = String::new();
+        let next_item_id = &mut self.next_item_id;
+        let mut reader = BufReader::new(response);
+        let result = self.provider.read_stream(&mut reader, &mut |text| {
+            let id = item_id.get_or_insert_with(|| {
+                let id = format!("item_{}", *next_item_id);
+                *next_item_id += 1;
+                id
+            });
+            partial = text.to_string();
+            logger.log_event(&json!({
+                "type": "item.updated",
+                "item": {
+                    "id": id,
+                    "type": "agent_message",
+                    "text": text,
+                },
+            }))
+        });
+        match result {
+            Ok(mut result) => {
+                result.streamed_item_id = item_id;
+                Ok(result)
+            }
+            Err(err) => {
+                // Close the partial message so it does not stay open forever; a retry streams
+                // into a new item.
+                if let Some(id) = item_id {
+                    self.log_item_completed(json!({
+                        "id": id,
+                        "type": "agent_message",
+                        "text": partial,
+                        "status": "failed",
+                    }))?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Appends a message to the history and records it verbatim so `--resume` can replay it
+    /// (minus reasoning under `--redact-reasoning`).
+    fn record_message(&mut self, message: Message) -> Result<()> {
+        let logged = match &message {
+            Message::Assistant(assistant) if self.redact_reasoning => {
+                json!(Message::Assistant(assistant.without_reasoning()))
+            }
+            _ => json!(message),
+        };
+        let mut event = json!({
+            "type": "message.appended",
+            "message": logged,
+        });
+        if matches!(message, Message::Assistant(_)) {
+            event["model"] = json!(self.model);
+        }
+        self.logger.log_event(&event)?;
+        self.messages.push(message);
+        Ok(())
+    }
+
+    fn log_context_compacted(&mut self, reason: &str, compaction: &Compaction) -> Result<()> {
+        self.logger.log_event(&json!({
+            "type": "context.compacted",
+            "reason": reason,
+            "strategy": self.context_strategy,
+            "context_window": self.context_window,
+            "tokens_before": compaction.tokens_before,
+            "tokens_after": compaction.tokens_after,
+            "truncated_tool_outputs": compaction.truncated_tool_outputs.len(),
+            "truncated_messages": compaction.truncated_tool_outputs,
+            "truncated_to_chars": COMPACTED_TOOL_OUTPUT_CHARS,
+            "dropped_messages": compaction
+                .prunes
+                .iter()
+                .map(|prune| prune.dropped_messages)
+                .sum::<usize>(),
+            "prunes": compaction
+                .prunes
+                .iter()
+                .map(|prune| json!({
+                    "dropped_messages": prune.dropped_messages,
+                    "summary": prune.summary,
+                }))
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    fn log_model_switched(&mut self, to: &str, error: &str) -> Result<()> {
+        self.logger.log_event(&json!({
+            "type": "model.switched",
+            "from": self.model,
+            "to": to,
+            "scope": if self.fallback_sticky { "run" } else { "step" },
+            "error": error,
+        }))
     }
 
     fn log_thread_started(&mut self) -> Result<()> {
@@ -390,6 +875,15 @@ impl Agent {
         }))
     }
 
+    fn log_thread_resumed(&mut self, steps: usize, elapsed: Duration) -> Result<()> {
+        self.logger.log_event(&json!({
+            "type": "thread.resumed",
+            "thread_id": self.session_id.clone(),
+            "steps_completed": steps,
+            "elapsed_ms": elapsed.as_millis() as u64,
+        }))
+    }
+
     fn log_turn_started(
         &mut self,
         prompt: &str,
@@ -400,6 +894,8 @@ impl Agent {
             "type": "turn.started",
             "prompt": prompt,
             "system_prompt": system_prompt,
+            "submit_enabled": self.submit_enabled,
+            "sampling": self.sampling.to_json(),
         });
         if let Some(text) = agents_text {
             event["agents_instructions"] = json!(text);
@@ -407,14 +903,20 @@ impl Agent {
         self.logger.log_event(&event)
     }
 
-    fn log_turn_completed(&mut self) -> Result<()> {
+    /// `reason` is why the run ended: `submit`, `final_answer`, or the budget that ran out.
+    fn log_turn_completed(&mut self, reason: &'static str, steps: usize) -> Result<()> {
+        self.end_reason = Some(reason);
         let usage = json!({
             "input_tokens": self.token_usage_total.input_tokens,
             "cached_input_tokens": self.token_usage_total.cached_input_tokens,
             "output_tokens": self.token_usage_total.output_tokens,
+            "reasoning_output_tokens": self.token_usage_total.reasoning_output_tokens,
+            "cost_usd": self.cost_total_usd,
         });
         self.logger.log_event(&json!({
             "type": "turn.completed",
+            "reason": reason,
+            "steps": steps,
             "usage": usage,
         }))
     }
@@ -443,14 +945,33 @@ impl Agent {
     }
 
     fn log_agent_message(&mut self, text: &str) -> Result<()> {
+        let item_id = self.next_item_id();
+        self.log_agent_message_with_id(item_id, text)
+    }
+
+    fn log_agent_message_with_id(&mut self, item_id: String, text: &str) -> Result<()> {
         let item = json!({
-            "id": self.next_item_id(),
+            "id": item_id,
             "type": "agent_message",
             "text": text,
         });
         self.log_item_completed(item)
     }
 
+    fn log_reasoning(&mut self, text: &str) -> Result<()> {
+        let text = if self.redact_reasoning {
+            "[redacted]"
+        } else {
+            text
+        };
+        let item = json!({
+            "id": self.next_item_id(),
+            "type": "reasoning",
+            "text": text,
+        });
+        self.log_item_completed(item)
+    }
+
     fn log_command_execution_started(&mut self, item_id: &str, command: &str) -> Result<()> {
         self.log_item_started(json!({
             "id": item_id,
@@ -482,14 +1003,14 @@ impl Agent {
     }
 
     fn prepare_tool_logging(&mut self, tool_name: &str, arguments: &str) -> Result<ToolLogging> {
-        if tool_name == "apply_patch" {
-            let patch = serde_json::from_str::<crate::tools::ApplyPatchArgs>(arguments)
-                .map(|args| args.patch)
-                .unwrap_or_default();
-            let changes = parse_patch_changes(&patch);
+        let tool = self.registry.get(tool_name);
+        if let Some(changes) = tool.and_then(|tool| tool.file_changes(arguments, &self.cwd)) {
             Ok((None, changes))
         } else {
-            let command = tool_command_string(tool_name, arguments);
+            let command = match tool {
+                Some(tool) => tool.command_string(arguments),
+                None => tool_command_string(tool_name, arguments),
+            };
             let item_id = self.next_item_id();
             Ok((Some((item_id, command)), Vec::new()))
         }
@@ -503,7 +1024,8 @@ impl Agent {
         content: &str,
         success: bool,
     ) -> Result<()> {
-        if tool_name == "apply_patch" {
+        // Tools without a command item modify files and are logged as `file_change`.
+        let Some((item_id, command)) = command_item else {
             let status = parse_command_output(content)
                 .map(|(code, _)| code == 0)
                 .unwrap_or_else(|| success && !output_is_error_json(content));
@@ -514,21 +1036,26 @@ impl Agent {
                 "changes": file_changes,
                 "status": if status { "completed" } else { "failed" },
             }));
-        }
-
-        let (item_id, command) = command_item
-            .unwrap_or_else(|| (self.next_item_id(), tool_command_string(tool_name, "")));
+        };
 
-        let (exit_code, aggregated_output, status) = if tool_name == "shell_command" {
-            match parse_command_output(content) {
-                Some((code, output)) => (Some(code), output, code == 0),
-                None => (None, content.to_string(), success),
+        let tool = self.registry.get(tool_name);
+        // A command still running has no exit code yet; that is not a failure.
+        let running = tool.is_some_and(|tool| tool.may_keep_running())
+            && serde_json::from_str::<Value>(content)
+                .is_ok_and(|v| v.get("running") == Some(&Value::Bool(true)));
+        // Shell tools and custom command tools report an exit code and output streams.
+        let (exit_code, aggregated_output, status) = match parse_command_output(content) {
+            Some((_, output)) if running => (None, output, true),
+            Some((code, output)) => (Some(code), output, code == 0),
+            None if tool.is_some_and(|tool| tool.reports_exit_code()) => {
+                (None, content.to_string(), success)
+            }
+            None => {
+                let is_error = output_is_error_json(content);
+                let status = success && !is_error;
+                let code = if status { Some(0) } else { Some(1) };
+                (code, content.to_string(), status)
             }
-        } else {
-            let is_error = output_is_error_json(content);
-            let status = success && !is_error;
-            let code = if status { Some(0) } else { Some(1) };
-            (code, content.to_string(), status)
         };
 
         self.log_command_execution_completed(
@@ -554,9 +1081,37 @@ impl Agent {
         }))
     }
 
-    fn update_usage(&mut self, usage: &Usage) {
-        let last_usage = token_usage_from_usage(usage);
+    /// Adds one request's usage to the run totals and logs it with its cost: OpenRouter's
+    /// `usage.cost` when present, otherwise priced from `--pricing`.
+    fn update_usage(&mut self, usage: &Usage, reasoning: Option<&str>) -> Result<()> {
+        let last_usage = token_usage_from_usage(usage, reasoning);
         self.token_usage_total.add_assign(&last_usage);
+        let cost = usage.cost.or_else(|| {
+            self.pricing
+                .as_ref()
+                .and_then(|pricing| pricing.for_model(&self.model))
+                .map(|price| price.cost(&last_usage))
+        });
+        if let Some(cost) = cost {
+            self.cost_total_usd = Some(self.cost_total_usd.unwrap_or(0.0) + cost);
+        }
+        self.logger.log_event(&json!({
+            "type": "usage.updated",
+            "model": self.model,
+            "usage": last_usage,
+            "cost_usd": cost,
+            "total_usage": self.token_usage_total,
+            "total_cost_usd": self.cost_total_usd,
+        }))?;
+        if cost.is_none() && self.max_cost.is_some() && !self.cost_unknown_warned {
+            self.cost_unknown_warned = true;
+            let message = format!(
+                "Cost of {} requests is unknown (no usage.cost and no --pricing entry); --max-cost only counts known costs.",
+                self.model
+            );
+            self.log_warning_item(&message)?;
+        }
+        Ok(())
     }
 
     fn next_item_id(&mut self) -> String {
@@ -566,7 +1121,9 @@ impl Agent {
     }
 }
 
-fn token_usage_from_usage(usage: &Usage) -> TokenUsage {
+/// Providers that bill reasoning as plain output (Anthropic) report no reasoning count, so it
+/// is estimated from the captured reasoning text instead.
+fn token_usage_from_usage(usage: &Usage, reasoning_text: Option<&str>) -> TokenUsage {
     let cached = usage
         .prompt_tokens_details
         .as_ref()
@@ -576,6 +1133,10 @@ fn token_usage_from_usage(usage: &Usage) -> TokenUsage {
         .completion_tokens_details
         .as_ref()
         .and_then(|details| details.reasoning_tokens)
+        .or_else(|| {
+            reasoning_text
+                .map(|text| (chars_to_tokens(text.len()) as i64).min(usage.completion_tokens))
+        })
         .unwrap_or(0);
     let total = if usage.total_tokens > 0 {
         usage.total_tokens
@@ -591,20 +1152,6 @@ fn token_usage_from_usage(usage: &Usage) -> TokenUsage {
     }
 }
 
-fn tool_command_string(tool_name: &str, arguments: &str) -> String {
-    if tool_name == "shell_command" {
-        if let Ok(args) = serde_json::from_str::<crate::tools::ShellArgs>(arguments) {
-            return format!("bash -lc {}", args.command);
-        }
-        return format!("bash -lc {}", arguments);
-    }
-    if arguments.trim().is_empty() {
-        format!("tool:{}", tool_name)
-    } else {
-        format!("tool:{} {}", tool_name, arguments)
-    }
-}
-
 fn parse_command_output(output: &str) -> Option<(i32, String)> {
     #[derive(Deserialize)]
     struct CommandOutput {
@@ -647,7 +1194,13 @@ fn is_context_error(message: &str) -> bool {
     msg.contains("context") && msg.contains("length")
 }
 
-fn format_openrouter_http_error(url: &str, status: u16, headers: &HeaderMap, body: &str) -> String {
+fn format_http_error(
+    label: &str,
+    url: &str,
+    status: u16,
+    headers: &HeaderMap,
+    body: &str,
+) -> String {
     let request_id = headers
         .get("x-request-id")
         .or_else(|| headers.get("x-openrouter-request-id"))
@@ -666,7 +1219,7 @@ fn format_openrouter_http_error(url: &str, status: u16, headers: &HeaderMap, bod
 
     let hint = match status {
         401 | 403 => "Hint: check your API key (set `OPENROUTER_API_KEY` or use `--api-key`) and that it has access to the model.",
-        404 => "Hint: check `--base-url` and the model name (`--model`).",
+        404 => "Hint: check `--base-url`, `--provider`, and the model name (`--model`).",
         408 | 504 => "Hint: the request timed out; try again or use a faster model.",
         429 => "Hint: you may be rate limited; retry later or lower concurrency.",
         500 | 502 | 503 => "Hint: upstream/server error; retry later.",
@@ -676,8 +1229,8 @@ fn format_openrouter_http_error(url: &str, status: u16, headers: &HeaderMap, bod
     let (snippet, _) = truncate(body, 2000);
     let mut msg = String::new();
     msg.push_str(&format!(
-        "OpenRouter API error (HTTP {}) when calling {}",
-        status, url
+        "{} API error (HTTP {}) when calling {}",
+        label, status, url
     ));
     if !request_id.is_empty() {
         msg.push_str(&format!(" (request_id: {})", request_id));
@@ -736,127 +1289,6 @@ fn error_chain_has_retryable_io_dyn(err: &(dyn StdError + 'static)) -> bool {
     false
 }
 
-fn parse_retry_after_secs(v: &reqwest::header::HeaderValue) -> Option<u64> {
-    // Retry-After can be delta-seconds or an HTTP-date; we only parse delta-seconds.
-    let s = v.to_str().ok()?.trim();
-    s.parse::<u64>().ok()
-}
-
-fn sleep_backoff(attempt: usize, retry_after_secs: Option<u64>) {
-    // Exponential backoff with small jitter, capped.
-    // attempt=0 -> ~250ms, attempt=1 -> ~500ms, attempt=2 -> ~1000ms
-    let base_ms = 250u64.saturating_mul(1u64 << attempt.min(10));
-    let capped_ms = base_ms.min(3_000);
-    let jitter_ms = (now_millis() % 50) as u64; // 0..49ms
-    let delay_ms = retry_after_secs
-        .map(|s| s.saturating_mul(1000))
-        .unwrap_or(capped_ms)
-        .saturating_add(jitter_ms);
-    thread::sleep(Duration::from_millis(delay_ms));
-}
-
-fn now_millis() -> u128 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_else(|_| Duration::from_millis(0))
-        .as_millis()
-}
-
-fn prune_messages(messages: &[Value]) -> Vec<Value> {
-    let mut system = Vec::new();
-    let mut non_system = Vec::new();
-    for msg in messages {
-        if msg
-            .get("role")
-            .and_then(Value::as_str)
-            .is_some_and(|r| r == "system")
-        {
-            system.push(msg.clone());
-        } else {
-            non_system.push(msg.clone());
-        }
-    }
-
-    // Spec-aligned pruning:
-    //
-    // - Keep all system messages
-    // - Keep the initial user task message
-    // - Preserve assistant<->tool call/response pairs
-    // - Drop the oldest chunk of the remaining conversation (preferably at a user-boundary)
-    let task_idx = non_system.iter().position(|m| {
-        m.get("role")
-            .and_then(Value::as_str)
-            .is_some_and(|r| r == "user")
-    });
-    let Some(task_idx) = task_idx else {
-        system.extend(non_system);
-        return system;
-    };
-
-    let task_msg = non_system[task_idx].clone();
-    let rest: Vec<Value> = non_system.into_iter().skip(task_idx + 1).collect();
-
-    let drop_target = rest.len() / 3;
-    let mut cut_idx = drop_target.min(rest.len());
-    if let Some(boundary) = rest
-        .iter()
-        .enumerate()
-        .skip(cut_idx)
-        .find(|(_, m)| {
-            m.get("role")
-                .and_then(Value::as_str)
-                .is_some_and(|r| r == "user")
-        })
-        .map(|(i, _)| i)
-    {
-        cut_idx = boundary;
-    }
-
-    let mut preserved = Vec::new();
-    preserved.push(task_msg);
-    preserved.extend(rest.into_iter().skip(cut_idx));
-
-    let mut valid = Vec::new();
-    let mut active_tool_ids: HashSet<String> = HashSet::new();
-
-    for msg in preserved {
-        let role = msg.get("role").and_then(Value::as_str).unwrap_or("");
-        match role {
-            "assistant" => {
-                active_tool_ids = extract_tool_call_ids(&msg);
-                valid.push(msg);
-            }
-            "tool" => {
-                if let Some(call_id) = msg.get("tool_call_id").and_then(Value::as_str) {
-                    if active_tool_ids.contains(call_id) {
-                        valid.push(msg);
-                    }
-                }
-            }
-            "user" => {
-                active_tool_ids.clear();
-                valid.push(msg);
-            }
-            _ => valid.push(msg),
-        }
-    }
-
-    system.extend(valid);
-    system
-}
-
-fn extract_tool_call_ids(msg: &Value) -> HashSet<String> {
-    let mut ids = HashSet::new();
-    if let Some(tool_calls) = msg.get("tool_calls").and_then(Value::as_array) {
-        for tc in tool_calls {
-            if let Some(id) = tc.get("id").and_then(Value::as_str) {
-                ids.insert(id.to_string());
-            }
-        }
-    }
-    ids
-}
-
 #[cfg(test)]
 mod tests {
     use super::error_chain_has_retryable_io_dyn;
@@ -0,0 +1,362 @@
+use crate::cli::{Args, BatchArgs};
+use crate::config::apply_task_overrides;
+use crate::protocol::TokenUsage;
+use crate::resume::load_resume_state;
+use crate::run::run_prompt;
+use crate::tools::resolve_path;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+const SUMMARY_FILE: &str = "summary.jsonl";
+
+/// One line of a batch tasks file. Any other key overrides the config key of that name.
+#[derive(Deserialize)]
+struct Task {
+    id: String,
+    prompt: String,
+    /// Relative to `--cwd`; defaults to it.
+    cwd: Option<PathBuf>,
+    #[serde(flatten)]
+    overrides: Map<String, Value>,
+}
+
+/// Runs every task in `batch.tasks` through `run_prompt`, `--concurrency` at a time. Each task
+/// logs to `OUT_DIR/logs/ID.jsonl`, and its outcome is appended to `OUT_DIR/summary.jsonl` as
+/// soon as it finishes.
+pub(crate) fn run_batch(args: &Args, batch: &BatchArgs, cwd: &Path, api_key: &str) -> Result<()> {
+    if args.prompt.is_some() || args.prompt_file.is_some() || args.resume.is_some() {
+        bail!(
+            "batch reads its prompts from the tasks file; drop PROMPT, --prompt-file and --resume"
+        );
+    }
+    if args.log_path.is_some() || args.json || args.stream_json {
+        bail!("batch logs each task to --out-dir; drop --log-path, --json and --stream-json");
+    }
+    if batch.concurrency == 0 {
+        bail!("--concurrency must be at least 1");
+    }
+
+    let tasks = load_tasks(&resolve_path(cwd, &batch.tasks))?;
+    let out_dir = resolve_path(cwd, &batch.out_dir);
+    let summary_path = out_dir.join(SUMMARY_FILE);
+    let completed = if batch.resume_failed {
+        completed_task_ids(&summary_path)?
+    } else if summary_path.exists() {
+        bail!(
+            "{} already exists; pass --resume-failed to continue that batch, or pick another --out-dir",
+            summary_path.display()
+        );
+    } else {
+        HashSet::new()
+    };
+    fs::create_dir_all(out_dir.join("logs"))
+        .with_context(|| format!("failed to create batch directory {}", out_dir.display()))?;
+    let summary = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&summary_path)
+        .with_context(|| format!("failed to open {}", summary_path.display()))?;
+
+    let pending: Vec<&Task> = tasks
+        .iter()
+        .filter(|task| !completed.contains(&task.id))
+        .collect();
+    let summary = Mutex::new(summary);
+    let next = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+    thread::scope(|scope| -> Result<()> {
+        let workers: Vec<_> = (0..batch.concurrency.min(pending.len()))
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    while let Some(task) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let record =
+                            run_task(args, task, cwd, &out_dir, batch.resume_failed, api_key);
+                        if record["status"] != "completed" {
+                            failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                        writeln!(summary.lock().unwrap(), "{record}").with_context(|| {
+                            format!("failed to write {}", summary_path.display())
+                        })?;
+                        let grade = match record["grade"]["passed"].as_bool() {
+                            Some(true) => ", passed",
+                            Some(false) => ", not passed",
+                            None => "",
+                        };
+                        eprintln!(
+                            "[{}/{}] {}: {} ({}{grade})",
+                            finished.fetch_add(1, Ordering::Relaxed) + 1,
+                            pending.len(),
+                            task.id,
+                            record["status"].as_str().unwrap_or_default(),
+                            record["reason"].as_str().unwrap_or_default(),
+                        );
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker
+                .join()
+                .map_err(|_| anyhow!("batch worker panicked"))??;
+        }
+        Ok(())
+    })?;
+
+    let failed = failed.into_inner();
+    if failed > 0 {
+        bail!(
+            "{failed} of {} tasks failed (summary: {})",
+            pending.len(),
+            summary_path.display()
+        );
+    }
+    println!(
+        "{} tasks completed (summary: {})",
+        pending.len(),
+        summary_path.display()
+    );
+    Ok(())
+}
+
+fn load_tasks(path: &Path) -> Result<Vec<Task>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read tasks file {}", path.display()))?;
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut ids = HashSet::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let task: Task = serde_json::from_str(line)
+            .with_context(|| format!("{}:{}: invalid task", path.display(), i + 1))?;
+        // The id names the task's log file.
+        if task.id.is_empty() || task.id.starts_with('.') || task.id.contains(['/', '\\']) {
+            bail!(
+                "{}:{}: invalid task id {:?}",
+                path.display(),
+                i + 1,
+                task.id
+            );
+        }
+        if !ids.insert(task.id.clone()) {
+            bail!(
+                "{}:{}: duplicate task id {:?}",
+                path.display(),
+                i + 1,
+                task.id
+            );
+        }
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+/// Ids whose latest record in an earlier batch's summary is `completed`.
+fn completed_task_ids(summary_path: &Path) -> Result<HashSet<String>> {
+    let contents = match fs::read_to_string(summary_path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", summary_path.display()))
+        }
+    };
+    let mut latest: HashMap<String, bool> = HashMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = serde_json::from_str(line)
+            .with_context(|| format!("{}:{}: invalid JSON", summary_path.display(), i + 1))?;
+        if let Some(id) = record.get("id").and_then(Value::as_str) {
+            latest.insert(id.to_string(), record["status"] == "completed");
+        }
+    }
+    Ok(latest
+        .into_iter()
+        .filter_map(|(id, completed)| completed.then_some(id))
+        .collect())
+}
+
+/// Runs one task and returns its summary record. Steps, usage and cost are read back from the
+/// task's log, so a failed run still reports what it spent.
+fn run_task(
+    base: &Args,
+    task: &Task,
+    cwd: &Path,
+    out_dir: &Path,
+    resume_failed: bool,
+    api_key: &str,
+) -> Value {
+    let log_path = out_dir.join("logs").join(format!("{}.jsonl", task.id));
+    let started = Instant::now();
+    let result = task_args(base, task, cwd, &log_path, resume_failed)
+        .and_then(|(args, task_cwd)| run_prompt(&args, &task_cwd, api_key));
+    let wall_time_ms = started.elapsed().as_millis() as u64;
+
+    let state = load_resume_state(&log_path).ok();
+    let (answer, error, reason) = match result {
+        Ok(answer) => {
+            let reason = state.as_ref().and_then(|s| s.end_reason.clone());
+            (Some(answer), None, reason)
+        }
+        Err(err) => (None, Some(format!("{err:#}")), Some("error".to_string())),
+    };
+    json!({
+        "id": task.id,
+        "status": if error.is_none() { "completed" } else { "failed" },
+        "reason": reason,
+        "answer": answer,
+        "error": error,
+        "steps": state.as_ref().map_or(0, |s| s.steps),
+        "usage": state.as_ref().map_or_else(TokenUsage::default, |s| s.usage.clone()),
+        "cost_usd": state.as_ref().and_then(|s| s.cost_usd),
+        "grade": state.as_ref().and_then(|s| s.grade.clone()),
+        "wall_time_ms": wall_time_ms,
+        "log_path": log_path.display().to_string(),
+    })
+}
+
+/// The batch options with the task's prompt, working directory and overrides applied. Tasks run
+/// in exec mode (like `--prompt-file`) unless `--no-submit` is set.
+fn task_args(
+    base: &Args,
+    task: &Task,
+    cwd: &Path,
+    log_path: &Path,
+    resume_failed: bool,
+) -> Result<(Args, PathBuf)> {
+    let mut args = base.clone();
+    args.command = None;
+    apply_task_overrides(&mut args, task.overrides.clone())
+        .with_context(|| format!("task {}: invalid overrides", task.id))?;
+    let task_cwd = match &task.cwd {
+        Some(dir) => {
+            let dir = resolve_path(cwd, dir);
+            fs::canonicalize(&dir)
+                .with_context(|| format!("failed to resolve task cwd {}", dir.display()))?
+        }
+        None => cwd.to_path_buf(),
+    };
+    args.cwd = task_cwd.clone();
+    args.prompt = Some(task.prompt.clone());
+    args.exec = !args.no_submit;
+    args.log_dir = None;
+    args.log_path = Some(log_path.to_path_buf());
+    if resume_failed && log_path.exists() {
+        // A log that got as far as the task is continued; one without a conversation (the run
+        // failed while starting up) is replaced.
+        if load_resume_state(log_path).is_ok() {
+            args.resume = Some(log_path.to_path_buf());
+            args.log_path = None;
+        } else {
+            fs::remove_file(log_path)
+                .with_context(|| format!("failed to remove {}", log_path.display()))?;
+        }
+    }
+    Ok((args, task_cwd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_batch;
+    use crate::cli::{Args, Command};
+    use clap::Parser;
+    use serde_json::Value;
+    use std::fs;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    fn batch(dir: &Path, script: &Path, extra: &[&str]) -> anyhow::Result<()> {
+        let mut argv = vec![
+            "ra".to_string(),
+            "--provider".to_string(),
+            "mock".to_string(),
+            "--mock-script".to_string(),
+            script.display().to_string(),
+            "batch".to_string(),
+            "tasks.jsonl".to_string(),
+            "--concurrency".to_string(),
+            "2".to_string(),
+        ];
+        argv.extend(extra.iter().map(|s| s.to_string()));
+        let args = Args::try_parse_from(argv).unwrap();
+        let Some(Command::Batch(batch)) = &args.command else {
+            panic!("expected the batch subcommand");
+        };
+        run_batch(&args, batch, dir, "")
+    }
+
+    #[test]
+    fn runs_tasks_and_resumes_failed_ones() {
+        let dir = fs::canonicalize(std::env::temp_dir())
+            .unwrap()
+            .join(format!("ra-batch-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join("a")).unwrap();
+        let script = dir.join("script.jsonl");
+        fs::write(
+            &script,
+            r#"{"message": {"tool_calls": [{"function": {"name": "submit", "arguments": {"answer": "done"}}}]}, "usage": {"prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("tasks.jsonl"),
+            [
+                r#"{"id": "a", "prompt": "Do a.", "cwd": "a", "grader": "exact:done"}"#,
+                r#"{"id": "b", "prompt": "Do b.", "cwd": "b"}"#,
+                r#"{"id": "c", "prompt": "Do c.", "max_steps": 0}"#,
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        let summary = || -> Vec<Value> {
+            fs::read_to_string(dir.join("ra-batch/summary.jsonl"))
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        };
+
+        // b's working directory does not exist yet.
+        let err = batch(&dir, &script, &[]).unwrap_err().to_string();
+        assert!(err.contains("1 of 3 tasks failed"), "{err}");
+        let records = summary();
+        assert_eq!(records.len(), 3);
+        let record = |id: &str| records.iter().find(|r| r["id"] == id).unwrap().clone();
+        let a = record("a");
+        assert_eq!(a["status"], "completed");
+        assert_eq!(a["reason"], "submit");
+        assert_eq!(a["answer"], "done");
+        assert_eq!(a["steps"], 1);
+        assert_eq!(a["usage"]["total_tokens"], 12);
+        assert_eq!(a["grade"]["passed"], true);
+        assert_eq!(a["grade"]["grader"], "exact");
+        assert!(dir.join("ra-batch/logs/a.jsonl").is_file());
+        assert_eq!(record("b")["status"], "failed");
+        assert_eq!(record("c")["reason"], "max_steps");
+        assert_eq!(record("c")["steps"], 0);
+        assert!(record("c")["grade"].is_null());
+
+        let err = batch(&dir, &script, &[]).unwrap_err().to_string();
+        assert!(err.contains("--resume-failed"), "{err}");
+
+        fs::create_dir_all(dir.join("b")).unwrap();
+        batch(&dir, &script, &["--resume-failed"]).unwrap();
+        let records = summary();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[3]["id"], "b");
+        assert_eq!(records[3]["status"], "completed");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,17 @@
-use clap::{ArgGroup, Parser};
+use crate::context::ContextStrategy;
+use crate::provider::ProviderKind;
+use crate::sandbox::SandboxKind;
+use crate::tools::ToolProfile;
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, ArgGroup, Parser, Subcommand};
 use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
+#[command(subcommand_negates_reqs = true)]
 #[command(group(
     ArgGroup::new("task_input")
         .required(true)
-        .args(["prompt_file", "prompt"])
+        .args(["prompt_file", "prompt", "resume"])
 ))]
 #[command(group(
     ArgGroup::new("exec_mode")
@@ -27,9 +33,45 @@ pub(crate) struct Args {
     )]
     pub(crate) model: String,
 
+    #[arg(
+        long = "fallback-model",
+        value_name = "MODELS",
+        value_delimiter = ',',
+        help = "Models to try in order when the active one stays unavailable (HTTP 429/5xx after retries, or a 404 saying the model was not found), e.g. a,b,c. An empty value clears a list from config files."
+    )]
+    pub(crate) fallback_models: Vec<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
+        help = "Keep using a fallback model for the rest of the run instead of going back to --model at the next step."
+    )]
+    pub(crate) fallback_sticky: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Apply the [profile.NAME] section of ra.toml / ~/.config/ra/config.toml."
+    )]
+    pub(crate) profile: Option<String>,
+
     #[arg(long, value_name = "FILE", help = "Read the prompt from a file.")]
     pub(crate) prompt_file: Option<PathBuf>,
 
+    #[arg(
+        long,
+        value_name = "LOG",
+        conflicts_with_all = ["log_dir", "log_path"],
+        help = "Resume an interrupted run from its JSONL log (appends to the same log)."
+    )]
+    pub(crate) resume: Option<PathBuf>,
+
     #[arg(
         long,
         value_name = "DIR",
@@ -38,7 +80,10 @@ pub(crate) struct Args {
     )]
     pub(crate) cwd: PathBuf,
 
-    #[arg(long, help = "OpenRouter API key (overrides OPENROUTER_API_KEY).")]
+    #[arg(
+        long,
+        help = "API key (overrides OPENROUTER_API_KEY, or ANTHROPIC_API_KEY/OPENAI_API_KEY for those providers)."
+    )]
     pub(crate) api_key: Option<String>,
 
     #[arg(
@@ -48,15 +93,116 @@ pub(crate) struct Args {
     )]
     pub(crate) base_url: String,
 
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ProviderKind::OpenaiChat,
+        help = "Wire format of the model API at --base-url."
+    )]
+    pub(crate) provider: ProviderKind,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "JSONL script of canned assistant responses for --provider mock."
+    )]
+    pub(crate) mock_script: Option<PathBuf>,
+
     #[arg(long, help = "Sampling temperature (omit to use provider default).")]
     pub(crate) temperature: Option<f64>,
 
+    #[arg(
+        long,
+        help = "Nucleus sampling probability mass (omit to use provider default)."
+    )]
+    pub(crate) top_p: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "TOKENS",
+        help = "Maximum output tokens per model response (Anthropic default: 8192)."
+    )]
+    pub(crate) max_tokens: Option<u64>,
+
+    #[arg(long, help = "Sampling seed, for providers that support it.")]
+    pub(crate) seed: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "SEQ",
+        help = "Stop sequence (repeatable; an empty value clears those from config files)."
+    )]
+    pub(crate) stop: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "EFFORT",
+        help = "Reasoning effort for reasoning models (e.g. low, medium, high)."
+    )]
+    pub(crate) reasoning_effort: Option<String>,
+
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Frequency penalty (omit to use provider default)."
+    )]
+    pub(crate) frequency_penalty: Option<f64>,
+
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "Presence penalty (omit to use provider default)."
+    )]
+    pub(crate) presence_penalty: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "JSON",
+        help = "JSON object merged into every request body, e.g. OpenRouter provider routing or transforms."
+    )]
+    pub(crate) extra_body: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
+        help = "Stream completions (SSE) and emit partial agent messages as item.updated events."
+    )]
+    pub(crate) stream: bool,
+
     #[arg(long, help = "Maximum number of tool steps before terminating.")]
     pub(crate) max_steps: Option<usize>,
 
     #[arg(long, help = "Time limit in seconds before terminating.")]
     pub(crate) time_limit_sec: Option<u64>,
 
+    #[arg(
+        long,
+        value_name = "USD",
+        help = "Spending limit in USD before terminating (costs from OpenRouter's usage.cost or --pricing)."
+    )]
+    pub(crate) max_cost: Option<f64>,
+
+    #[arg(
+        long,
+        alias = "max-tokens-total",
+        value_name = "TOKENS",
+        help = "Limit on total tokens (input + output, summed over all requests) before terminating."
+    )]
+    pub(crate) max_total_tokens: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Per-model prices in USD per million tokens ([models.\"NAME\"] tables with input, output, cached_input; TOML or .json), used when the provider reports no cost."
+    )]
+    pub(crate) pricing: Option<PathBuf>,
+
     #[arg(long, help = "Directory to write the JSONL log file.")]
     pub(crate) log_dir: Option<PathBuf>,
 
@@ -81,9 +227,134 @@ pub(crate) struct Args {
     )]
     pub(crate) stream_json: bool,
 
+    #[arg(
+        long,
+        default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
+        help = "Log reasoning items as [redacted] and leave reasoning out of logged messages (a resumed run then starts without it)."
+    )]
+    pub(crate) redact_reasoning: bool,
+
+    #[arg(
+        long,
+        value_name = "SPEC",
+        help = "Grade the answer and log a grade.completed event: exact:TEXT, regex:PATTERN, test:COMMAND (passes if it exits 0), or a grader command printing {\"score\", \"passed\", \"explanation\"}."
+    )]
+    pub(crate) grader: Option<String>,
+
     #[arg(long, help = "Maximum tool output characters to retain.")]
     pub(crate) max_tool_output_chars: Option<usize>,
 
+    #[arg(
+        long,
+        value_name = "LINES",
+        help = "How many lines away from its stated position apply_patch looks for a hunk's context (default: 200)."
+    )]
+    pub(crate) patch_max_offset: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "TOKENS",
+        help = "Model context window in tokens; history is compacted before requests near it (default: known per-model size with --context-strategy, otherwise only when the provider reports an overflow)."
+    )]
+    pub(crate) context_window: Option<u64>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "How old turns are removed when the history no longer fits: drop them, or replace them with a model-written summary (default: prune)."
+    )]
+    pub(crate) context_strategy: Option<ContextStrategy>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
+        help = "Allow several tool calls per step; consecutive read-only tools run concurrently."
+    )]
+    pub(crate) parallel_tools: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
+        help = "Add the shell_session tool: a persistent bash process that keeps cwd, env, and background jobs between steps."
+    )]
+    pub(crate) shell_session: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ToolProfile::Default,
+        help = "Base tool set: baseline (default), write_file + edit_file instead of apply_patch (edit), both (all), or read/list/grep only (read-only)."
+    )]
+    pub(crate) tool_profile: ToolProfile,
+
+    #[arg(
+        long,
+        value_name = "LIST",
+        allow_hyphen_values = true,
+        help = "Comma-separated tools replacing the profile's (e.g. read_file,grep_files), or +name/-name to add/remove tools. submit follows exec mode."
+    )]
+    pub(crate) tools: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "TOML (or .json) file declaring command tools: name, description, parameters schema, and a fixed command."
+    )]
+    pub(crate) custom_tools: Option<PathBuf>,
+
+    #[arg(
+        long = "mcp-server",
+        value_name = "NAME=COMMAND",
+        help = "Launch an MCP server over stdio and offer its tools as NAME__tool (repeatable; an empty value clears those from config files)."
+    )]
+    pub(crate) mcp_servers: Vec<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SandboxKind::None,
+        help = "Where shell tools run: on the host, in a bubblewrap jail (workspace read-write), or via docker exec."
+    )]
+    pub(crate) sandbox: SandboxKind,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
+        help = "Disable networking inside the sandbox (requires --sandbox bwrap)."
+    )]
+    pub(crate) sandbox_no_network: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Running container for --sandbox docker; it must mount the workspace at the same path."
+    )]
+    pub(crate) sandbox_container: Option<String>,
+
     #[arg(
         long,
         default_value_t = false,
@@ -102,19 +373,114 @@ pub(crate) struct Args {
         long,
         env = "RA_RETRY_429",
         default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
         help = "Retry HTTP 429 responses (rate limited). By default, 429s are only retried when Retry-After is present."
     )]
     pub(crate) retry_429: bool,
 
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Attempts per model or web request, including the first (default: 3)."
+    )]
+    pub(crate) retry_max_attempts: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "First retry delay; it doubles per retry up to --retry-max-delay-ms (default: 250)."
+    )]
+    pub(crate) retry_base_delay_ms: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "MS",
+        help = "Longest backoff delay, and the longest Retry-After honored without --retry-budget-sec (default: 4000)."
+    )]
+    pub(crate) retry_max_delay_ms: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        help = "Up to this fraction of each backoff delay is added at random (default: 0.2)."
+    )]
+    pub(crate) retry_jitter: Option<f64>,
+
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "Stop retrying a request once its retries would take longer than this in total (default: no limit)."
+    )]
+    pub(crate) retry_budget_sec: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "After N requests in a row to the same model or host use up their retries, send further ones once, without retries, until one succeeds (default: 0, off)."
+    )]
+    pub(crate) retry_breaker: Option<usize>,
+
     #[arg(
         long = "enable-search",
         alias = "search",
         env = "RA_WEB_SEARCH",
         default_value_t = false,
+        value_name = "BOOL",
+        action = ArgAction::Set,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new(),
         help = "Enable web tools (off by default): web_search (Tavily), web_open, web_find."
     )]
     pub(crate) web_search: bool,
 
     #[arg(value_name = "PROMPT", help = "Prompt text (quote for spaces).")]
     pub(crate) prompt: Option<String>,
+
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum Command {
+    /// Run every task in a JSONL file, using the options given before `batch` as defaults.
+    Batch(BatchArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub(crate) struct BatchArgs {
+    #[arg(
+        value_name = "TASKS",
+        help = "JSONL file with one task per line: {\"id\", \"prompt\", \"cwd\"?, ...config keys to override}."
+    )]
+    pub(crate) tasks: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "ra-batch",
+        help = "Directory for the per-task logs (ID.jsonl) and summary.jsonl, relative to --cwd."
+    )]
+    pub(crate) out_dir: PathBuf,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        help = "Number of tasks to run at once."
+    )]
+    pub(crate) concurrency: usize,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Continue a previous batch in --out-dir: skip completed tasks, resume failed or interrupted ones from their logs."
+    )]
+    pub(crate) resume_failed: bool,
 }
@@ -0,0 +1,367 @@
+use crate::cli::Args;
+use crate::context::ContextStrategy;
+use crate::provider::ProviderKind;
+use crate::sandbox::SandboxKind;
+use crate::tools::ToolProfile;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::{Deserialize, Deserializer};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+const PROJECT_CONFIG_FILE: &str = "ra.toml";
+
+/// Settings that `ra.toml` and `~/.config/ra/config.toml` may provide. Keys are the long flag
+/// names with underscores; `[profile.NAME]` tables hold the same keys.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigValues {
+    model: Option<String>,
+    fallback_models: Option<Vec<String>>,
+    fallback_sticky: Option<bool>,
+    base_url: Option<String>,
+    #[serde(default, deserialize_with = "value_enum")]
+    provider: Option<ProviderKind>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    max_tokens: Option<u64>,
+    seed: Option<u64>,
+    stop: Option<Vec<String>>,
+    reasoning_effort: Option<String>,
+    frequency_penalty: Option<f64>,
+    presence_penalty: Option<f64>,
+    /// A table here, or a JSON string like `--extra-body`.
+    extra_body: Option<serde_json::Value>,
+    stream: Option<bool>,
+    redact_reasoning: Option<bool>,
+    grader: Option<String>,
+    max_steps: Option<usize>,
+    time_limit_sec: Option<u64>,
+    max_cost: Option<f64>,
+    max_total_tokens: Option<u64>,
+    pricing: Option<PathBuf>,
+    log_dir: Option<PathBuf>,
+    max_tool_output_chars: Option<usize>,
+    patch_max_offset: Option<usize>,
+    context_window: Option<u64>,
+    #[serde(default, deserialize_with = "value_enum")]
+    context_strategy: Option<ContextStrategy>,
+    parallel_tools: Option<bool>,
+    shell_session: Option<bool>,
+    custom_tools: Option<PathBuf>,
+    mcp_servers: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "value_enum")]
+    tool_profile: Option<ToolProfile>,
+    tools: Option<String>,
+    #[serde(default, deserialize_with = "value_enum")]
+    sandbox: Option<SandboxKind>,
+    sandbox_no_network: Option<bool>,
+    sandbox_container: Option<String>,
+    retry_429: Option<bool>,
+    retry_max_attempts: Option<usize>,
+    retry_base_delay_ms: Option<u64>,
+    retry_max_delay_ms: Option<u64>,
+    retry_jitter: Option<f64>,
+    retry_budget_sec: Option<u64>,
+    retry_breaker: Option<usize>,
+    web_search: Option<bool>,
+    /// Read by the web tools from `RA_WEB_MAX_BYTES`.
+    web_max_bytes: Option<u64>,
+    /// Read by web_search from `RA_TAVILY_BASE_URL`.
+    tavily_base_url: Option<String>,
+}
+
+/// Config keys holding paths, which are resolved relative to the file that sets them.
+const PATH_KEYS: &[&str] = &["log_dir", "custom_tools", "pricing"];
+
+fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let Some(raw) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    T::from_str(&raw, false)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// `~/.config/ra/config.toml`, if a home directory is known.
+pub(crate) fn user_config_path() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(|home| PathBuf::from(home).join(".config/ra/config.toml"))
+}
+
+/// Fills in every option not given on the command line or through its environment variable
+/// from the config files: `ra.toml` files in `cwd` and its ancestors (nearer files win) over
+/// the user config. `--profile NAME` then applies the `[profile.NAME]` tables on top.
+pub(crate) fn apply_config_files(
+    args: &mut Args,
+    matches: &ArgMatches,
+    cwd: &Path,
+    user_config: Option<&Path>,
+) -> Result<()> {
+    let mut files = Vec::new();
+    if let Some(path) = user_config.filter(|path| path.is_file()) {
+        files.push(path.to_path_buf());
+    }
+    let mut project_files: Vec<PathBuf> = cwd
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .filter(|path| path.is_file())
+        .collect();
+    project_files.reverse();
+    files.extend(project_files);
+
+    let mut base = Table::new();
+    let mut profile = Table::new();
+    let mut profile_found = false;
+    for path in &files {
+        let mut table = load_table(path)?;
+        let profiles = match table.remove("profile") {
+            Some(Value::Table(profiles)) => profiles,
+            Some(_) => bail!("{}: `profile` must be a table of tables", path.display()),
+            None => Table::new(),
+        };
+        base.extend(table);
+        if let Some(name) = &args.profile {
+            match profiles.get(name) {
+                Some(Value::Table(values)) => {
+                    profile_found = true;
+                    let mut values = values.clone();
+                    resolve_paths(&mut values, path);
+                    profile.extend(values);
+                }
+                Some(_) => bail!("{}: [profile.{name}] must be a table", path.display()),
+                None => {}
+            }
+        }
+    }
+    if let Some(name) = &args.profile {
+        if !profile_found {
+            bail!("--profile {name}: no [profile.{name}] section in any config file");
+        }
+    }
+    base.extend(profile);
+
+    let mut values = ConfigValues::deserialize(Value::Table(base)).map_err(|err| {
+        let sources: Vec<String> = files.iter().map(|p| p.display().to_string()).collect();
+        anyhow!("invalid config ({}): {err}", sources.join(", "))
+    })?;
+
+    // These are only read from the environment, so an unset variable is filled in instead.
+    for (var, value) in [
+        (
+            "RA_WEB_MAX_BYTES",
+            values.web_max_bytes.take().map(|v| v.to_string()),
+        ),
+        ("RA_TAVILY_BASE_URL", values.tavily_base_url.take()),
+    ] {
+        if let Some(value) = value {
+            if env::var_os(var).is_none() {
+                env::set_var(var, value);
+            }
+        }
+    }
+
+    apply(values, args, |id| {
+        matches!(
+            matches.value_source(id),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    });
+    // An empty value on the command line (`--stop ''`) keeps a config file's list out and
+    // leaves the list empty.
+    for list in [
+        &mut args.stop,
+        &mut args.fallback_models,
+        &mut args.mcp_servers,
+    ] {
+        list.retain(|value| !value.is_empty());
+    }
+    Ok(())
+}
+
+/// Applies a batch task's overrides (config keys, as JSON) on top of the batch's settings.
+pub(crate) fn apply_task_overrides(
+    args: &mut Args,
+    overrides: serde_json::Map<String, serde_json::Value>,
+) -> Result<()> {
+    let values = ConfigValues::deserialize(serde_json::Value::Object(overrides))?;
+    if values.web_max_bytes.is_some() || values.tavily_base_url.is_some() {
+        bail!("web_max_bytes and tavily_base_url cannot be set per task");
+    }
+    apply(values, args, |_| false);
+    Ok(())
+}
+
+fn load_table(path: &Path) -> Result<Table> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    let mut table: Table = toml::from_str(&text)
+        .with_context(|| format!("failed to parse config file {}", path.display()))?;
+    resolve_paths(&mut table, path);
+    Ok(table)
+}
+
+fn resolve_paths(table: &mut Table, config_path: &Path) {
+    let dir = config_path.parent().unwrap_or(Path::new("."));
+    for key in PATH_KEYS {
+        if let Some(Value::String(raw)) = table.get_mut(*key) {
+            *raw = dir.join(&*raw).display().to_string();
+        }
+    }
+}
+
+/// Sets every option present in `config`, except those `explicit` says were already given.
+fn apply(config: ConfigValues, args: &mut Args, explicit: impl Fn(&str) -> bool) {
+    macro_rules! layer {
+        ($($field:ident),* $(,)?) => {$(
+            if let Some(value) = config.$field {
+                if !explicit(stringify!($field)) {
+                    args.$field = value.into();
+                }
+            }
+        )*};
+    }
+    layer!(
+        model,
+        fallback_models,
+        fallback_sticky,
+        base_url,
+        provider,
+        temperature,
+        top_p,
+        max_tokens,
+        seed,
+        stop,
+        reasoning_effort,
+        frequency_penalty,
+        presence_penalty,
+        stream,
+        redact_reasoning,
+        grader,
+        max_steps,
+        time_limit_sec,
+        max_cost,
+        max_total_tokens,
+        pricing,
+        log_dir,
+        max_tool_output_chars,
+        patch_max_offset,
+        context_window,
+        context_strategy,
+        parallel_tools,
+        shell_session,
+        custom_tools,
+        mcp_servers,
+        tool_profile,
+        tools,
+        sandbox,
+        sandbox_no_network,
+        sandbox_container,
+        retry_429,
+        retry_max_attempts,
+        retry_base_delay_ms,
+        retry_max_delay_ms,
+        retry_jitter,
+        retry_budget_sec,
+        retry_breaker,
+        web_search,
+    );
+
+    if let Some(body) = config.extra_body {
+        if !explicit("extra_body") {
+            args.extra_body = Some(match body {
+                serde_json::Value::String(raw) => raw,
+                table => table.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply_config_files;
+    use crate::cli::Args;
+    use crate::tools::ToolProfile;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn layers_cli_over_project_over_user_config() {
+        let root = std::env::temp_dir().join(format!("ra-config-{}", Uuid::new_v4()));
+        let project = root.join("repo");
+        let nested = project.join("crates/app");
+        fs::create_dir_all(&nested).unwrap();
+        let user = root.join("config.toml");
+        fs::write(
+            &user,
+            "model = \"user/model\"\nmax_steps = 5\ntemperature = 0.9\n[profile.fast]\nmax_steps = 2\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join("ra.toml"),
+            "model = \"project/model\"\nlog_dir = \"logs\"\ntool_profile = \"edit\"\n\n[profile.fast]\nmodel = \"fast/model\"\n",
+        )
+        .unwrap();
+        fs::write(nested.join("ra.toml"), "tool_profile = \"read-only\"\n").unwrap();
+
+        let load = |argv: &[&str]| -> anyhow::Result<Args> {
+            let matches = Args::command().try_get_matches_from(argv)?;
+            let mut args = Args::from_arg_matches(&matches)?;
+            apply_config_files(&mut args, &matches, &nested, Some(&user))?;
+            Ok(args)
+        };
+
+        let args = load(&["ra", "--temperature", "0.1", "hi"]).unwrap();
+        assert_eq!(args.model, "project/model");
+        assert_eq!(args.max_steps, Some(5));
+        assert_eq!(args.temperature, Some(0.1));
+        assert_eq!(args.tool_profile, ToolProfile::ReadOnly);
+        assert_eq!(args.log_dir, Some(project.join("logs")));
+
+        let args = load(&["ra", "--profile", "fast", "--max-steps", "9", "hi"]).unwrap();
+        assert_eq!(args.model, "fast/model");
+        assert_eq!(args.max_steps, Some(9));
+        let args = load(&["ra", "--profile", "fast", "hi"]).unwrap();
+        assert_eq!(args.max_steps, Some(2));
+
+        // Booleans and lists set in a config file can be switched off from the command line.
+        fs::write(
+            nested.join("ra.toml"),
+            "stream = true\nparallel_tools = true\nstop = [\"END\"]\nfallback_models = [\"a\"]\n",
+        )
+        .unwrap();
+        let args = load(&["ra", "hi"]).unwrap();
+        assert!(args.stream && args.parallel_tools);
+        assert_eq!(args.stop, ["END"]);
+        let args = load(&[
+            "ra",
+            "--stream=false",
+            "--parallel-tools=no",
+            "--stop",
+            "",
+            "--fallback-model=",
+            "hi",
+        ])
+        .unwrap();
+        assert!(!args.stream && !args.parallel_tools);
+        assert!(args.stop.is_empty() && args.fallback_models.is_empty());
+        let args = load(&["ra", "--stream", "hi"]).unwrap();
+        assert!(args.stream);
+        assert_eq!(args.prompt.as_deref(), Some("hi"));
+
+        assert!(load(&["ra", "--profile", "missing", "hi"]).is_err());
+        fs::write(nested.join("ra.toml"), "max_stepz = 3\n").unwrap();
+        let err = load(&["ra", "hi"]).unwrap_err().to_string();
+        assert!(err.contains("max_stepz"), "{err}");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
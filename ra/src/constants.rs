@@ -1,5 +1,18 @@
+use std::time::Duration;
+
 pub(crate) const DEFAULT_CONTINUE_MESSAGE: &str = "Please proceed to the next step using your best judgement. If you believe you are finished, double check your work to continue to refine and improve your submission.";
 pub(crate) const DEFAULT_MAX_TOOL_OUTPUT_CHARS: usize = 8000;
 pub(crate) const DEFAULT_READ_LIMIT: usize = 200;
 pub(crate) const DEFAULT_LIST_LIMIT: usize = 200;
 pub(crate) const DEFAULT_GREP_LIMIT: usize = 100;
+/// How far (in lines) apply_patch searches from a hunk's stated position for its context.
+pub(crate) const DEFAULT_PATCH_MAX_OFFSET: usize = 200;
+/// The Anthropic Messages API requires `max_tokens`; this is used when none is configured.
+pub(crate) const DEFAULT_ANTHROPIC_MAX_TOKENS: u64 = 8192;
+pub(crate) const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// How long an MCP server gets to answer `initialize` and `tools/list` at startup.
+pub(crate) const MCP_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long a single MCP `tools/call` may take.
+pub(crate) const MCP_CALL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long a `--grader` command (or `test:` command) may run.
+pub(crate) const GRADER_TIMEOUT: Duration = Duration::from_secs(10 * 60);
@@ -0,0 +1,408 @@
+use crate::protocol::{AssistantMessage, Message};
+use crate::tools::truncate;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Compaction starts once the estimated request size crosses this fraction of the context
+/// window, leaving headroom for the completion and for estimator error.
+pub(crate) const CONTEXT_COMPACT_THRESHOLD: f64 = 0.85;
+/// Old tool outputs are cut down to this many characters during compaction.
+pub(crate) const COMPACTED_TOOL_OUTPUT_CHARS: usize = 1000;
+const TRUNCATION_MARKER_SUFFIX: &str = " truncated to fit the context window]...";
+/// The most recent tool outputs are never truncated; the model is usually still working on them.
+const KEEP_RECENT_TOOL_OUTPUTS: usize = 2;
+/// Rough per-message framing cost (role, separators) in tokens.
+const MESSAGE_OVERHEAD_TOKENS: u64 = 4;
+/// Per-message cap when rendering a dropped span for the summarizer, so the side request stays
+/// well inside the window that just overflowed.
+const TRANSCRIPT_MESSAGE_CHARS: usize = 2000;
+
+pub(crate) const SUMMARY_SYSTEM_PROMPT: &str = "You compress the working history of an autonomous coding agent. The messages you receive are about to be removed from its context window. Write a concise summary the agent can rely on instead: files inspected or modified (with exact paths and the key facts learned), commands run and their outcomes, findings so far, open hypotheses, and what it was about to do next. Keep identifiers, paths and error messages verbatim. Do not address the agent or add commentary.";
+pub(crate) const SUMMARY_MESSAGE_PREFIX: &str =
+    "Summary of earlier work (older messages were removed to fit the context window):";
+
+/// How old turns are removed once the history no longer fits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ContextStrategy {
+    /// Drop the oldest turns.
+    #[default]
+    Prune,
+    /// Ask the model to summarize the oldest turns, and replace them with that summary.
+    Summarize,
+}
+
+/// Known context windows, matched as a prefix of the model name with any `vendor/` stripped.
+/// More specific prefixes must come first.
+const MODEL_CONTEXT_WINDOWS: &[(&str, u64)] = &[
+    ("claude", 200_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-5", 400_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("gemini-2.5", 1_048_576),
+    ("gemini-2.0", 1_048_576),
+    ("deepseek", 128_000),
+    ("qwen3-coder", 262_144),
+    ("kimi-k2", 131_072),
+];
+
+pub(crate) fn context_window_for_model(model: &str) -> Option<u64> {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    MODEL_CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+/// Cheap local token estimate (~4 characters per token of the serialized request).
+pub(crate) fn estimate_tokens(messages: &[Message], tools: &[Value]) -> u64 {
+    let tools_tokens = chars_to_tokens(serde_json::to_string(tools).unwrap_or_default().len());
+    tools_tokens + messages.iter().map(estimate_message_tokens).sum::<u64>()
+}
+
+fn estimate_message_tokens(message: &Message) -> u64 {
+    let chars = serde_json::to_string(message).unwrap_or_default().len();
+    chars_to_tokens(chars) + MESSAGE_OVERHEAD_TOKENS
+}
+
+pub(crate) fn chars_to_tokens(chars: usize) -> u64 {
+    (chars as u64).div_ceil(4)
+}
+
+/// Outcome of one compaction pass, as recorded in `context.compacted` events.
+pub(crate) struct Compaction {
+    pub(crate) tokens_before: u64,
+    pub(crate) tokens_after: u64,
+    /// Indices of the tool results cut down to `COMPACTED_TOOL_OUTPUT_CHARS`.
+    pub(crate) truncated_tool_outputs: Vec<usize>,
+    pub(crate) prunes: Vec<Prune>,
+}
+
+/// One pass of dropping the oldest turns, logged so `--resume` can replay it.
+pub(crate) struct Prune {
+    pub(crate) dropped_messages: usize,
+    /// Summary inserted in place of the dropped turns, if one was kept.
+    pub(crate) summary: Option<String>,
+}
+
+/// Cuts old tool outputs down (oldest first, leaving the most recent ones intact) until the
+/// estimate fits in `budget` tokens or nothing is left to truncate. Returns the indices of the
+/// messages it cut.
+pub(crate) fn truncate_old_tool_outputs(
+    messages: &mut [Message],
+    tools: &[Value],
+    budget: u64,
+) -> Vec<usize> {
+    let mut tokens = estimate_tokens(messages, tools);
+    let tool_indices: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| matches!(m, Message::Tool { .. }))
+        .map(|(i, _)| i)
+        .collect();
+    let truncatable = tool_indices.len().saturating_sub(KEEP_RECENT_TOOL_OUTPUTS);
+    let mut truncated = Vec::new();
+    for &idx in &tool_indices[..truncatable] {
+        if tokens <= budget {
+            break;
+        }
+        let Message::Tool { content, .. } = &messages[idx] else {
+            continue;
+        };
+        let total = content.chars().count();
+        if total <= COMPACTED_TOOL_OUTPUT_CHARS || content.ends_with(TRUNCATION_MARKER_SUFFIX) {
+            continue;
+        }
+        let before = estimate_message_tokens(&messages[idx]);
+        truncate_tool_output(&mut messages[idx], COMPACTED_TOOL_OUTPUT_CHARS);
+        tokens = tokens - before + estimate_message_tokens(&messages[idx]);
+        truncated.push(idx);
+    }
+    truncated
+}
+
+/// Cuts a tool result down to its first `chars` characters plus a marker saying how many were
+/// removed. Other messages, and results already that short, are left alone.
+pub(crate) fn truncate_tool_output(message: &mut Message, chars: usize) {
+    let Message::Tool { content, .. } = message else {
+        return;
+    };
+    let total = content.chars().count();
+    if total <= chars {
+        return;
+    }
+    let head: String = content.chars().take(chars).collect();
+    *content = format!(
+        "{}\n...[{} chars{}",
+        head,
+        total - chars,
+        TRUNCATION_MARKER_SUFFIX
+    );
+}
+
+/// Renders a span of history as plain text for the summarizer.
+pub(crate) fn render_transcript(messages: &[Message]) -> String {
+    let mut out = String::new();
+    for msg in messages {
+        let (label, text) = match msg {
+            Message::System { content } => ("system", content.clone()),
+            Message::User { content } => ("user", content.clone()),
+            Message::Assistant(assistant) => {
+                let mut text = assistant.content.clone().unwrap_or_default();
+                for tc in &assistant.tool_calls {
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(&format!(
+                        "called {}({})",
+                        tc.function.name, tc.function.arguments
+                    ));
+                }
+                ("assistant", text)
+            }
+            Message::Tool { content, .. } => ("tool result", content.clone()),
+        };
+        let (text, _) = truncate(&text, TRANSCRIPT_MESSAGE_CHARS);
+        out.push_str(&format!("[{}]\n{}\n\n", label, text));
+    }
+    out
+}
+
+/// Inserts a summary of dropped turns right after the task message.
+pub(crate) fn insert_summary(messages: &mut Vec<Message>, summary: &str) {
+    let idx = messages
+        .iter()
+        .position(|m| matches!(m, Message::User { .. }))
+        .map(|i| i + 1)
+        .unwrap_or(messages.len());
+    messages.insert(
+        idx,
+        Message::User {
+            content: format!("{}\n\n{}", SUMMARY_MESSAGE_PREFIX, summary.trim()),
+        },
+    );
+}
+
+/// Splits history into what pruning keeps and what it drops (in original order), following the
+/// rules below. `dropped` is empty when there is nothing left to prune.
+pub(crate) fn split_oldest_turns(messages: &[Message]) -> (Vec<Message>, Vec<Message>) {
+    let mut system = Vec::new();
+    let mut non_system = Vec::new();
+    for msg in messages {
+        if matches!(msg, Message::System { .. }) {
+            system.push(msg.clone());
+        } else {
+            non_system.push(msg.clone());
+        }
+    }
+
+    // Spec-aligned pruning:
+    //
+    // - Keep all system messages
+    // - Keep the initial user task message
+    // - Preserve assistant<->tool call/response pairs
+    // - Drop the oldest chunk of the remaining conversation (preferably at a user-boundary)
+    let task_idx = non_system
+        .iter()
+        .position(|m| matches!(m, Message::User { .. }));
+    let Some(task_idx) = task_idx else {
+        system.extend(non_system);
+        return (system, Vec::new());
+    };
+
+    let task_msg = non_system[task_idx].clone();
+    let mut rest: Vec<Message> = non_system.into_iter().skip(task_idx + 1).collect();
+
+    let drop_target = rest.len() / 3;
+    let mut cut_idx = drop_target.min(rest.len());
+    if let Some(boundary) = rest
+        .iter()
+        .enumerate()
+        .skip(cut_idx)
+        .find(|(_, m)| matches!(m, Message::User { .. }))
+        .map(|(i, _)| i)
+    {
+        cut_idx = boundary;
+    }
+
+    let mut dropped: Vec<Message> = rest.drain(..cut_idx).collect();
+    let mut preserved = Vec::new();
+    preserved.push(task_msg);
+    preserved.extend(rest);
+
+    let mut valid = Vec::new();
+    let mut active_tool_ids: HashSet<String> = HashSet::new();
+
+    for msg in preserved {
+        match &msg {
+            Message::Assistant(assistant) => {
+                active_tool_ids = extract_tool_call_ids(assistant);
+                valid.push(msg);
+            }
+            Message::Tool { tool_call_id, .. } => {
+                if active_tool_ids.contains(tool_call_id) {
+                    valid.push(msg);
+                } else {
+                    dropped.push(msg);
+                }
+            }
+            Message::User { .. } => {
+                active_tool_ids.clear();
+                valid.push(msg);
+            }
+            Message::System { .. } => valid.push(msg),
+        }
+    }
+
+    system.extend(valid);
+    (system, dropped)
+}
+
+fn extract_tool_call_ids(msg: &AssistantMessage) -> HashSet<String> {
+    msg.tool_calls.iter().map(|tc| tc.id.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        context_window_for_model, estimate_tokens, insert_summary, render_transcript,
+        split_oldest_turns, truncate_old_tool_outputs,
+    };
+    use crate::protocol::{AssistantMessage, Message, ToolCall, ToolFunction};
+
+    fn call(id: &str) -> Message {
+        Message::Assistant(AssistantMessage {
+            content: None,
+            tool_calls: vec![ToolCall {
+                id: id.to_string(),
+                call_type: "function".to_string(),
+                function: ToolFunction {
+                    name: "read_file".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }],
+            ..AssistantMessage::default()
+        })
+    }
+
+    fn result(id: &str, content: &str) -> Message {
+        Message::Tool {
+            tool_call_id: id.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn labels(messages: &[Message]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| match m {
+                Message::System { .. } => "system".to_string(),
+                Message::User { .. } => "user".to_string(),
+                Message::Assistant(a) => match a.tool_calls.first() {
+                    Some(tc) => format!("assistant:{}", tc.id),
+                    None => "assistant".to_string(),
+                },
+                Message::Tool { tool_call_id, .. } => format!("tool:{}", tool_call_id),
+            })
+            .collect()
+    }
+
+    fn header() -> Vec<Message> {
+        vec![
+            Message::System {
+                content: "sys".to_string(),
+            },
+            Message::User {
+                content: "task".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn pruning_never_leaves_orphaned_tool_results() {
+        let mut messages = header();
+        messages.extend([
+            call("a"),
+            result("a", "x"),
+            Message::Assistant(AssistantMessage {
+                content: Some("noted".to_string()),
+                ..AssistantMessage::default()
+            }),
+            call("b"),
+            result("b", "x"),
+        ]);
+
+        // Five messages after the task: the cut lands on `result("a")`, orphaning it.
+        let (pruned, _) = split_oldest_turns(&messages);
+        assert_eq!(
+            labels(&pruned),
+            vec!["system", "user", "assistant", "assistant:b", "tool:b"]
+        );
+    }
+
+    #[test]
+    fn truncates_only_old_tool_outputs_until_within_budget() {
+        let big = "x".repeat(20_000);
+        let mut messages = header();
+        for id in ["a", "b", "c", "d"] {
+            messages.extend([call(id), result(id, &big)]);
+        }
+        let full = estimate_tokens(&messages, &[]);
+
+        // Room for everything except two of the four large outputs.
+        let budget = full - 9_000;
+        let truncated = truncate_old_tool_outputs(&mut messages, &[], budget);
+        assert_eq!(truncated, [3, 5]);
+        assert!(estimate_tokens(&messages, &[]) <= budget);
+
+        // The two most recent outputs are never touched, even when still over budget.
+        assert!(truncate_old_tool_outputs(&mut messages, &[], 0).is_empty());
+        let Message::Tool { content, .. } = &messages[9] else {
+            panic!("expected a tool result");
+        };
+        assert_eq!(content.len(), 20_000);
+    }
+
+    #[test]
+    fn summary_replaces_the_dropped_span() {
+        let mut messages = header();
+        for id in ["a", "b", "c"] {
+            messages.extend([call(id), result(id, "x")]);
+        }
+        let (mut kept, dropped) = split_oldest_turns(&messages);
+        assert_eq!(labels(&dropped), vec!["assistant:a", "tool:a"]);
+        assert!(render_transcript(&dropped).contains("called read_file({})"));
+
+        insert_summary(&mut kept, "read a");
+        assert_eq!(
+            labels(&kept),
+            vec![
+                "system",
+                "user",
+                "user",
+                "assistant:b",
+                "tool:b",
+                "assistant:c",
+                "tool:c"
+            ]
+        );
+        let Message::User { content } = &kept[2] else {
+            panic!("expected the summary message");
+        };
+        assert!(content.ends_with("read a"));
+    }
+
+    #[test]
+    fn looks_up_known_model_windows() {
+        assert_eq!(
+            context_window_for_model("anthropic/claude-sonnet-4.5"),
+            Some(200_000)
+        );
+        assert_eq!(context_window_for_model("gpt-4o-mini"), Some(128_000));
+        assert_eq!(context_window_for_model("some/unknown-model"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::constants::GRADER_TIMEOUT;
+use crate::sandbox::Sandbox;
+use crate::tools::run_command;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::process::Command;
+
+/// Output of a failed `test:` command kept in the explanation.
+const TEST_OUTPUT_CHARS: usize = 2000;
+/// Output read from a grader command.
+const GRADER_OUTPUT_CHARS: usize = 64_000;
+
+/// How `--grader` scores a run's answer:
+///
+/// - `exact:TEXT`: the answer equals TEXT, ignoring surrounding whitespace.
+/// - `regex:PATTERN`: the answer matches PATTERN.
+/// - `test:COMMAND`: COMMAND exits 0 when run in the workspace (inside the sandbox, like
+///   `shell_command`).
+/// - anything else is a grader command, run on the host with `bash -lc` in the workspace. It
+///   gets the answer in `RA_ANSWER` and the workspace in `RA_WORKSPACE` (and both as JSON on
+///   stdin), and prints `{"score", "passed", "explanation"}`.
+pub(crate) enum Grader {
+    Exact(String),
+    Regex(Regex),
+    Test(String),
+    Command(String),
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Grade {
+    /// Defaults to 1 if passed, otherwise 0.
+    score: Option<f64>,
+    passed: bool,
+    #[serde(default)]
+    explanation: String,
+}
+
+impl Grader {
+    pub(crate) fn parse(spec: &str) -> Result<Self> {
+        if let Some(text) = spec.strip_prefix("exact:") {
+            return Ok(Self::Exact(text.trim().to_string()));
+        }
+        if let Some(pattern) = spec.strip_prefix("regex:") {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("invalid --grader regex {pattern:?}"))?;
+            return Ok(Self::Regex(regex));
+        }
+        if let Some(command) = spec.strip_prefix("test:") {
+            return Ok(Self::Test(command.to_string()));
+        }
+        if spec.trim().is_empty() {
+            bail!("--grader must not be empty");
+        }
+        Ok(Self::Command(spec.to_string()))
+    }
+
+    /// Grades `answer` from a run in `cwd`. An error means the grader itself failed.
+    pub(crate) fn grade(&self, answer: &str, cwd: &Path, sandbox: &Sandbox) -> Result<Grade> {
+        match self {
+            Self::Exact(expected) => Ok(Grade::from_check(
+                answer.trim() == expected,
+                "the answer equals the expected text",
+                "the answer does not equal the expected text",
+            )),
+            Self::Regex(regex) => Ok(Grade::from_check(
+                regex.is_match(answer),
+                "the answer matches the pattern",
+                "the answer does not match the pattern",
+            )),
+            Self::Test(command) => {
+                let output = run_grader_command(
+                    sandbox.command("bash", &["-lc", command], cwd),
+                    cwd,
+                    TEST_OUTPUT_CHARS,
+                    None,
+                )?;
+                let exit_code = output["exit_code"].as_i64().unwrap_or(-1);
+                let mut explanation = if output["timed_out"] == true {
+                    format!("`{command}` timed out")
+                } else {
+                    format!("`{command}` exited with {exit_code}")
+                };
+                if exit_code != 0 {
+                    for stream in ["stdout", "stderr"] {
+                        let text = output[stream].as_str().unwrap_or_default().trim();
+                        if !text.is_empty() {
+                            explanation.push_str(&format!("\n{stream}:\n{text}"));
+                        }
+                    }
+                }
+                Ok(Grade {
+                    score: None,
+                    passed: exit_code == 0,
+                    explanation,
+                })
+            }
+            Self::Command(command) => {
+                let mut process = Command::new("bash");
+                process
+                    .args(["-lc", command])
+                    .current_dir(cwd)
+                    .env("RA_ANSWER", answer)
+                    .env("RA_WORKSPACE", cwd);
+                let input = json!({ "answer": answer, "workspace": cwd }).to_string();
+                let output = run_grader_command(process, cwd, GRADER_OUTPUT_CHARS, Some(input))?;
+                let exit_code = output["exit_code"].as_i64().unwrap_or(-1);
+                if exit_code != 0 {
+                    bail!(
+                        "grader exited with {exit_code}: {}",
+                        output["stderr"].as_str().unwrap_or_default().trim()
+                    );
+                }
+                let stdout = output["stdout"].as_str().unwrap_or_default().trim();
+                serde_json::from_str(stdout)
+                    .with_context(|| format!("grader printed invalid JSON: {stdout}"))
+            }
+        }
+    }
+
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::Exact(_) => "exact",
+            Self::Regex(_) => "regex",
+            Self::Test(_) => "test",
+            Self::Command(_) => "command",
+        }
+    }
+}
+
+impl Grade {
+    pub(crate) fn failed(explanation: String) -> Self {
+        Self {
+            score: None,
+            passed: false,
+            explanation,
+        }
+    }
+
+    fn from_check(passed: bool, pass: &str, fail: &str) -> Self {
+        Self {
+            score: None,
+            passed,
+            explanation: if passed { pass } else { fail }.to_string(),
+        }
+    }
+
+    /// The `score` / `passed` / `explanation` fields of `grade.completed`.
+    pub(crate) fn to_json(&self) -> Value {
+        let score = self.score.unwrap_or(if self.passed { 1.0 } else { 0.0 });
+        json!({
+            "score": score,
+            "passed": self.passed,
+            "explanation": self.explanation,
+        })
+    }
+}
+
+fn run_grader_command(
+    command: Command,
+    cwd: &Path,
+    limit: usize,
+    stdin: Option<String>,
+) -> Result<Value> {
+    let output = run_command(
+        command,
+        cwd,
+        Some(GRADER_TIMEOUT.as_millis() as u64),
+        limit,
+        stdin,
+    )?;
+    serde_json::from_str(&output).map_err(|err| anyhow!("unreadable grader output: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Grader;
+    use crate::sandbox::{Sandbox, SandboxKind};
+    use std::path::PathBuf;
+
+    #[test]
+    fn grades_with_builtin_and_command_graders() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let sandbox = Sandbox::new(SandboxKind::None, cwd.clone(), true, None).unwrap();
+        let grade = |spec: &str, answer: &str| {
+            Grader::parse(spec)
+                .unwrap()
+                .grade(answer, &cwd, &sandbox)
+                .map(|grade| grade.to_json())
+        };
+
+        assert_eq!(grade("exact:42", " 42\n").unwrap()["passed"], true);
+        assert_eq!(grade("exact:42", "420").unwrap()["score"], 0.0);
+        assert_eq!(grade(r"regex:^\d+$", "17").unwrap()["passed"], true);
+        assert!(Grader::parse("regex:(").is_err());
+
+        assert_eq!(
+            grade("test:test -f Cargo.toml", "").unwrap()["passed"],
+            true
+        );
+        let failed = grade("test:echo nope >&2; exit 3", "").unwrap();
+        assert_eq!(failed["passed"], false);
+        let explanation = failed["explanation"].as_str().unwrap();
+        assert!(explanation.contains("exited with 3") && explanation.contains("nope"));
+
+        let graded = grade(
+            r#"if [ "$RA_ANSWER" = yes ]; then echo '{"score": 0.5, "passed": true, "explanation": "half"}'; fi"#,
+            "yes",
+        )
+        .unwrap();
+        assert_eq!(graded["score"], 0.5);
+        assert_eq!(graded["explanation"], "half");
+        let err = grade("echo '{}'", "yes").unwrap_err().to_string();
+        assert!(err.contains("invalid JSON"), "{err}");
+        assert!(grade("exit 2", "yes").is_err());
+    }
+}
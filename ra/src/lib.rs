@@ -1,15 +1,24 @@
 use anyhow::{anyhow, bail, Result};
-use clap::CommandFactory;
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use std::env;
 
 mod agent;
+mod batch;
 mod cli;
+mod config;
 mod constants;
+mod context;
+mod grade;
 mod logger;
+mod pricing;
 mod prompt;
 mod protocol;
+mod provider;
+mod resume;
+mod retry;
 mod run;
+mod sandbox;
+mod stream;
 mod tools;
 
 pub fn run_cli() -> Result<()> {
@@ -21,26 +30,38 @@ pub fn run_cli() -> Result<()> {
         return Ok(());
     }
 
-    let args = crate::cli::Args::parse();
+    let matches = crate::cli::Args::command().get_matches();
+    let mut args = crate::cli::Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
     if args.json && args.stream_json {
         bail!("--json and --stream-json cannot both be set");
     }
     let cwd = crate::run::resolve_and_validate_cwd(&args)
         .map_err(|e| anyhow!("failed to resolve cwd {}: {}", args.cwd.display(), e))?;
+    crate::config::apply_config_files(
+        &mut args,
+        &matches,
+        &cwd,
+        crate::config::user_config_path().as_deref(),
+    )?;
 
-    let api_key = args
-        .api_key
-        .clone()
-        .or_else(|| env::var("OPENROUTER_API_KEY").ok());
+    if args.mock_script.is_some() && args.provider != crate::provider::ProviderKind::Mock {
+        bail!("--mock-script requires --provider mock");
+    }
+
+    let key_envs = args.provider.api_key_envs();
+    let api_key = args.api_key.clone().or_else(|| {
+        key_envs
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|k| !k.is_empty()))
+    });
     let api_key = match api_key {
-        Some(key) => key,
-        None => {
-            bail!("missing API key: set --api-key or OPENROUTER_API_KEY");
-        }
+        Some(key) if !key.is_empty() => key,
+        _ if !args.provider.needs_api_key() => String::new(),
+        _ => bail!("missing API key: set --api-key or {}", key_envs.join(" / ")),
     };
 
-    if api_key.is_empty() {
-        bail!("missing API key: set --api-key or OPENROUTER_API_KEY");
+    if let Some(crate::cli::Command::Batch(batch)) = &args.command {
+        return crate::batch::run_batch(&args, batch, &cwd, &api_key);
     }
 
     let answer = crate::run::run_prompt(&args, &cwd, &api_key)?;
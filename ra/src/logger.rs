@@ -1,16 +1,16 @@
 use anyhow::{Context, Result};
 use serde_json::{json, Value};
-use std::cell::RefCell;
 use std::fs::{self, File};
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use time::format_description::well_known::Rfc3339;
 use time::OffsetDateTime;
 
+/// Shared by the agent and its helpers; `Send` so batch runs can build one per worker thread.
 #[derive(Clone)]
 pub(crate) struct Logger {
-    inner: Rc<RefCell<LoggerInner>>,
+    inner: Arc<Mutex<LoggerInner>>,
 }
 
 struct LoggerInner {
@@ -22,6 +22,7 @@ struct LoggerInner {
 impl Logger {
     pub(crate) fn new(
         log_path: Option<PathBuf>,
+        append: bool,
         stream_to_stdout: bool,
         buffer_for_stdout: bool,
     ) -> Result<Self> {
@@ -31,11 +32,19 @@ impl Logger {
                     format!("failed to create log directory {}", parent.display())
                 })?;
             }
-            let file = std::fs::OpenOptions::new()
-                .write(true)
-                .create_new(true)
-                .open(&log_path)
-                .with_context(|| format!("failed to create log file {}", log_path.display()))?;
+            // Resumed runs continue the original log; fresh runs never clobber an existing file.
+            let file = if append {
+                std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&log_path)
+                    .with_context(|| format!("failed to open log file {}", log_path.display()))?
+            } else {
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&log_path)
+                    .with_context(|| format!("failed to create log file {}", log_path.display()))?
+            };
             Some(BufWriter::new(file))
         } else {
             None
@@ -54,7 +63,7 @@ impl Logger {
         };
 
         Ok(Self {
-            inner: Rc::new(RefCell::new(LoggerInner {
+            inner: Arc::new(Mutex::new(LoggerInner {
                 file_writer,
                 stdout_writer,
                 buffer,
@@ -74,7 +83,7 @@ impl Logger {
                 json!(now.format(&Rfc3339).unwrap_or_else(|_| ts_ms.to_string()))
             });
         }
-        let mut inner = self.inner.borrow_mut();
+        let mut inner = self.inner.lock().unwrap();
 
         if let Some(buf) = inner.buffer.as_mut() {
             buf.push(enriched.clone());
@@ -95,7 +104,7 @@ impl Logger {
     }
 
     pub(crate) fn emit_buffer_to_stdout(&self) -> Result<()> {
-        let inner = self.inner.borrow();
+        let inner = self.inner.lock().unwrap();
         let Some(buf) = inner.buffer.as_ref() else {
             return Ok(());
         };
@@ -0,0 +1,100 @@
+use crate::protocol::TokenUsage;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A `--pricing` file: `[models."NAME"]` tables in TOML, or `{"models": {...}}` in JSON.
+#[derive(Deserialize)]
+struct PricingFile {
+    #[serde(default)]
+    models: HashMap<String, ModelPrice>,
+}
+
+/// Prices in USD per million tokens.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ModelPrice {
+    input: f64,
+    output: f64,
+    /// Cached input tokens; defaults to the input price.
+    cached_input: Option<f64>,
+}
+
+impl ModelPrice {
+    /// Output tokens include reasoning tokens, which are billed at the output price.
+    pub(crate) fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_input_tokens.max(0) as f64;
+        let uncached = (usage.input_tokens - usage.cached_input_tokens).max(0) as f64;
+        let output = usage.output_tokens.max(0) as f64;
+        (uncached * self.input
+            + cached * self.cached_input.unwrap_or(self.input)
+            + output * self.output)
+            / 1_000_000.0
+    }
+}
+
+pub(crate) struct Pricing {
+    models: HashMap<String, ModelPrice>,
+}
+
+impl Pricing {
+    /// Loads a pricing table (`.json`, otherwise TOML).
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read pricing file {}", path.display()))?;
+        let file: PricingFile = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text)
+                .with_context(|| format!("failed to parse pricing file {}", path.display()))?
+        } else {
+            toml::from_str(&text)
+                .with_context(|| format!("failed to parse pricing file {}", path.display()))?
+        };
+        Ok(Self {
+            models: file.models,
+        })
+    }
+
+    /// Looks up `model` as given, then without its `vendor/` prefix.
+    pub(crate) fn for_model(&self, model: &str) -> Option<&ModelPrice> {
+        self.models.get(model).or_else(|| {
+            let name = model.rsplit('/').next().unwrap_or(model);
+            self.models.get(name)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pricing;
+    use crate::protocol::TokenUsage;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn prices_usage_per_million_tokens() {
+        let path = std::env::temp_dir().join(format!("ra-pricing-{}.toml", Uuid::new_v4()));
+        fs::write(
+            &path,
+            "[models.\"openai/gpt-4.1\"]\ninput = 2.0\ncached_input = 0.5\noutput = 8.0\n\n[models.claude-sonnet-4]\ninput = 3.0\noutput = 15.0\n",
+        )
+        .unwrap();
+        let pricing = Pricing::load(&path).unwrap();
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            cached_input_tokens: 400_000,
+            output_tokens: 100_000,
+            ..TokenUsage::default()
+        };
+        let cost = pricing.for_model("openai/gpt-4.1").unwrap().cost(&usage);
+        assert!((cost - (1.2 + 0.2 + 0.8)).abs() < 1e-9, "{cost}");
+        let cost = pricing
+            .for_model("anthropic/claude-sonnet-4")
+            .unwrap()
+            .cost(&usage);
+        assert!((cost - (3.0 + 1.5)).abs() < 1e-9, "{cost}");
+        assert!(pricing.for_model("gpt-4.1").is_none());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::cli::Args;
+use crate::sandbox::Sandbox;
+use crate::tools::ToolRegistry;
 use anyhow::{bail, Context, Result};
 use std::fs::{self, File};
 use std::io::Read;
@@ -25,14 +27,22 @@ pub(crate) fn build_system_prompt(
     max_steps: Option<usize>,
     time_limit: Option<Duration>,
     submit_enabled: bool,
-    web_search_enabled: bool,
+    parallel_tools: bool,
+    tools: &ToolRegistry,
+    sandbox: &Sandbox,
 ) -> Result<(String, Option<String>)> {
     let mut prompt = String::from(
         "You are a CLI agent. Use tools to inspect and modify the workspace to complete the task.\n\
-Rules:\n\
-- Use at most one tool call per step.\n\
-- Prefer tools over guessing. Tool outputs are authoritative.",
+Rules:\n",
     );
+    if parallel_tools {
+        prompt.push_str(
+            "- You may call several tools in one step; they run in order (read-only tools concurrently).\n",
+        );
+    } else {
+        prompt.push_str("- Use at most one tool call per step.\n");
+    }
+    prompt.push_str("- Prefer tools over guessing. Tool outputs are authoritative.");
     if submit_enabled {
         prompt.push_str("\n- If you are done, call submit with a concise final answer.");
     } else {
@@ -46,46 +56,18 @@ Rules:\n\
         .map(|v| v.as_secs().to_string())
         .unwrap_or_else(|| "unset".to_string());
 
+    let (sandbox_str, network_str) = sandbox.describe();
     prompt.push_str(&format!(
-        "\nEnvironment:\n- cwd: {}\n- max_steps: {}\n- time_limit_sec: {}\n- network_access: enabled\n- sandbox: none",
+        "\nEnvironment:\n- cwd: {}\n- max_steps: {}\n- time_limit_sec: {}\n- network_access: {}\n- sandbox: {}",
         cwd.display(),
         max_steps_str,
         time_limit_str,
+        network_str,
+        sandbox_str,
     ));
 
-    prompt.push_str(
-        "\n\nTools:\n\
-- shell_command(command, workdir?, timeout_ms?, max_output_chars?)\n\
-- read_file(file_path, offset?, limit?)\n\
-- list_dir(dir_path, offset?, limit?, depth?)\n\
-- grep_files(pattern, path?, include?, limit?)\n\
-- apply_patch(patch)\n",
-    );
-    if web_search_enabled {
-        prompt.push_str("- web_search(query, max_results?)\n");
-        prompt.push_str("- web_open(url, offset?, limit?)\n");
-        prompt.push_str("- web_find(url, pattern, max_results?, context_lines?)\n");
-    }
-    if submit_enabled {
-        prompt.push_str("- submit(answer)\n");
-    }
-
-    prompt.push_str(
-        "\nTool usage notes:\n\
-- Pagination is 1-indexed: read_file.offset and list_dir.offset start at 1 (not 0). limit/depth must be >= 1.\n\
-- grep_files.pattern is a Rust regex. Escape metacharacters if you want a literal match (e.g. use \"main\\(\" to search for \"main(\").\n\
-- If you need to edit files, prefer apply_patch.\n",
-    );
-    if web_search_enabled {
-        prompt.push_str(
-            "- web_search requires RA_TAVILY_API_KEY (or TAVILY_API_KEY).\n\
-- web_open/web_find fetch live URLs and return extracted text with line numbers for citations.\n",
-        );
-        prompt.push_str(
-            "- Tip: Prefer web_find(url, pattern, ...) over repeatedly calling web_open with different offsets.\n\
-- Tip: Prefer official sources when available (e.g. blog.rust-lang.org for Rust release posts).\n",
-        );
-    }
+    prompt.push_str("\n\n");
+    prompt.push_str(&tools.prompt_section());
 
     let agents_text = load_agents_instructions(cwd)?;
     if let Some(agent_notes) = agents_text.as_ref() {
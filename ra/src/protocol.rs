@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
 #[derive(Deserialize)]
 pub(crate) struct ChatCompletionResponse {
@@ -9,15 +10,126 @@ pub(crate) struct ChatCompletionResponse {
 
 #[derive(Deserialize)]
 pub(crate) struct Choice {
-    pub(crate) message: ChatMessage,
+    pub(crate) message: AssistantMessage,
 }
 
+/// One entry of the conversation history.
+///
+/// Serializes to the Chat Completions message shape, which is also what `message.appended` log
+/// events record. Other providers translate from this type in their `build_request`.
 #[derive(Clone, Serialize, Deserialize)]
-pub(crate) struct ChatMessage {
-    pub(crate) role: String,
+#[serde(tag = "role", rename_all = "lowercase")]
+pub(crate) enum Message {
+    System {
+        content: String,
+    },
+    User {
+        content: String,
+    },
+    Assistant(AssistantMessage),
+    Tool {
+        tool_call_id: String,
+        content: String,
+    },
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct AssistantMessage {
+    #[serde(default)]
+    pub(crate) content: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "null_as_empty",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub(crate) tool_calls: Vec<ToolCall>,
+    /// Reasoning text returned with the turn (OpenRouter `reasoning`, `reasoning_content`,
+    /// Anthropic thinking blocks, Responses reasoning summaries).
+    #[serde(
+        default,
+        alias = "reasoning_content",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) reasoning: Option<String>,
+    /// Provider reasoning items (OpenRouter `reasoning_details`, Anthropic thinking blocks,
+    /// Responses `reasoning` items), replayed as-is to the provider that produced them.
+    #[serde(
+        default,
+        deserialize_with = "null_as_empty",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub(crate) reasoning_details: Vec<Value>,
+}
+
+impl AssistantMessage {
+    /// True for a plain text reply (the model did not ask for a tool).
+    pub(crate) fn is_final_text(&self) -> bool {
+        self.tool_calls.is_empty()
+    }
+
+    /// The same turn without any reasoning, as logged under `--redact-reasoning`.
+    pub(crate) fn without_reasoning(&self) -> AssistantMessage {
+        AssistantMessage {
+            content: self.content.clone(),
+            tool_calls: self.tool_calls.clone(),
+            reasoning: None,
+            reasoning_details: Vec::new(),
+        }
+    }
+}
+
+fn null_as_empty<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChatCompletionChunk {
+    #[serde(default)]
+    pub(crate) choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    pub(crate) usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChunkChoice {
+    #[serde(default)]
+    pub(crate) delta: Option<ChunkDelta>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChunkDelta {
+    #[serde(default)]
     pub(crate) content: Option<String>,
     #[serde(default)]
-    pub(crate) tool_calls: Option<Vec<ToolCall>>,
+    pub(crate) tool_calls: Option<Vec<ToolCallDelta>>,
+    #[serde(default, alias = "reasoning_content")]
+    pub(crate) reasoning: Option<String>,
+    #[serde(default)]
+    pub(crate) reasoning_details: Option<Vec<Value>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ToolCallDelta {
+    #[serde(default)]
+    pub(crate) index: usize,
+    #[serde(default)]
+    pub(crate) id: Option<String>,
+    #[serde(default, rename = "type")]
+    pub(crate) call_type: Option<String>,
+    #[serde(default)]
+    pub(crate) function: Option<ToolFunctionDelta>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ToolFunctionDelta {
+    #[serde(default)]
+    pub(crate) name: Option<String>,
+    #[serde(default)]
+    pub(crate) arguments: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -34,7 +146,7 @@ pub(crate) struct ToolFunction {
     pub(crate) arguments: String,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub(crate) struct Usage {
     #[serde(default)]
     pub(crate) prompt_tokens: i64,
@@ -46,15 +158,18 @@ pub(crate) struct Usage {
     pub(crate) prompt_tokens_details: Option<PromptTokensDetails>,
     #[serde(default)]
     pub(crate) completion_tokens_details: Option<CompletionTokensDetails>,
+    /// USD charged for the request, as reported by OpenRouter.
+    #[serde(default)]
+    pub(crate) cost: Option<f64>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub(crate) struct PromptTokensDetails {
     #[serde(default)]
     pub(crate) cached_tokens: Option<i64>,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub(crate) struct CompletionTokensDetails {
     #[serde(default)]
     pub(crate) reasoning_tokens: Option<i64>,
@@ -70,7 +185,7 @@ pub(crate) struct ApiErrorDetail {
     pub(crate) message: String,
 }
 
-#[derive(Clone, Default, Serialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub(crate) struct TokenUsage {
     pub(crate) input_tokens: i64,
     pub(crate) cached_input_tokens: i64,
@@ -90,6 +205,9 @@ impl TokenUsage {
 }
 
 pub(crate) struct CompletionResult {
-    pub(crate) message: ChatMessage,
+    pub(crate) message: AssistantMessage,
     pub(crate) usage: Option<Usage>,
+    /// Item id used for `item.updated` events while streaming, so the final agent message
+    /// reuses it.
+    pub(crate) streamed_item_id: Option<String>,
 }
@@ -0,0 +1,429 @@
+use super::{error_message, Provider, RequestParams};
+use crate::constants::{ANTHROPIC_VERSION, DEFAULT_ANTHROPIC_MAX_TOKENS};
+use crate::protocol::{
+    AssistantMessage, CompletionResult, Message, PromptTokensDetails, ToolCall, ToolFunction, Usage,
+};
+use crate::stream::{SseReader, StreamInterrupted};
+use crate::tools::truncate;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::{json, Value};
+use std::io::BufRead;
+
+/// Anthropic Messages API.
+pub(crate) struct Anthropic;
+
+impl Provider for Anthropic {
+    fn label(&self) -> &'static str {
+        "Anthropic"
+    }
+
+    fn endpoint(&self, base_url: &str) -> String {
+        format!("{}/messages", base_url.trim_end_matches('/'))
+    }
+
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+    }
+
+    fn build_request(
+        &self,
+        params: &RequestParams<'_>,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<Value> {
+        let mut system: Vec<&str> = Vec::new();
+        let mut out: Vec<Value> = Vec::new();
+        for msg in messages {
+            let (role, blocks) = match msg {
+                Message::System { content } => {
+                    system.push(content);
+                    continue;
+                }
+                Message::User { content } => {
+                    ("user", vec![json!({"type": "text", "text": content})])
+                }
+                Message::Assistant(assistant) => {
+                    // Thinking blocks must come back unchanged (signature included) ahead of the
+                    // tool calls they led to.
+                    let mut blocks: Vec<Value> = assistant
+                        .reasoning_details
+                        .iter()
+                        .filter(|d| is_thinking_block(d))
+                        .cloned()
+                        .collect();
+                    if let Some(content) = assistant.content.as_deref() {
+                        if !content.trim().is_empty() {
+                            blocks.push(json!({"type": "text", "text": content}));
+                        }
+                    }
+                    for tc in &assistant.tool_calls {
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": tc.id,
+                            "name": tc.function.name,
+                            "input": serde_json::from_str::<Value>(&tc.function.arguments)
+                                .unwrap_or_else(|_| json!({})),
+                        }));
+                    }
+                    ("assistant", blocks)
+                }
+                Message::Tool {
+                    tool_call_id,
+                    content,
+                } => (
+                    "user",
+                    vec![json!({
+                        "type": "tool_result",
+                        "tool_use_id": tool_call_id,
+                        "content": content,
+                    })],
+                ),
+            };
+            if blocks.is_empty() {
+                continue;
+            }
+            // Messages must alternate roles; merge consecutive turns (e.g. several tool results
+            // followed by a "continue" message) into one.
+            match out.last_mut() {
+                Some(last) if last["role"] == role => {
+                    if let Some(existing) = last["content"].as_array_mut() {
+                        existing.extend(blocks);
+                    }
+                }
+                _ => out.push(json!({"role": role, "content": blocks})),
+            }
+        }
+
+        let tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|t| t.get("function"))
+            .map(|f| {
+                json!({
+                    "name": f.get("name").cloned().unwrap_or(Value::Null),
+                    "description": f.get("description").cloned().unwrap_or(Value::Null),
+                    "input_schema": f.get("parameters").cloned().unwrap_or_else(|| json!({"type": "object"})),
+                })
+            })
+            .collect();
+
+        let mut body = json!({
+            "model": params.model,
+            "max_tokens": DEFAULT_ANTHROPIC_MAX_TOKENS,
+            "messages": out,
+        });
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+            body["tool_choice"] = json!({
+                "type": "auto",
+                "disable_parallel_tool_use": !params.parallel_tool_calls,
+            });
+        }
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        let sampling = params.sampling;
+        sampling.reject_unsupported(
+            self.label(),
+            &[
+                "seed",
+                "reasoning_effort",
+                "frequency_penalty",
+                "presence_penalty",
+            ],
+        )?;
+        if let Some(temp) = sampling.temperature {
+            body["temperature"] = json!(temp);
+        }
+        if let Some(top_p) = sampling.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = sampling.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if !sampling.stop.is_empty() {
+            body["stop_sequences"] = json!(sampling.stop);
+        }
+        if params.stream {
+            body["stream"] = json!(true);
+        }
+        Ok(body)
+    }
+
+    fn parse_response(&self, body: &str) -> Result<CompletionResult> {
+        let parsed: Value = serde_json::from_str(body)?;
+        let blocks = parsed
+            .get("content")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("no content in response"))?;
+        Ok(CompletionResult {
+            message: message_from_blocks(blocks),
+            usage: parsed.get("usage").map(usage_from_value),
+            streamed_item_id: None,
+        })
+    }
+
+    fn read_stream(
+        &self,
+        reader: &mut dyn BufRead,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<CompletionResult> {
+        let mut sse = SseReader::new(reader);
+        let mut blocks: Vec<Value> = Vec::new();
+        let mut partial_json: Vec<String> = Vec::new();
+        let mut usage = json!({});
+        loop {
+            let event = match sse.next_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => {
+                    return Err(anyhow!(StreamInterrupted(
+                        "stream ended before message_stop".to_string()
+                    )));
+                }
+                Err(err) => return Err(anyhow!(StreamInterrupted(err.to_string()))),
+            };
+            if event.data.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(&event.data).with_context(|| {
+                let (snippet, _) = truncate(&event.data, 2000);
+                format!(
+                    "Anthropic returned an unexpected stream event:\n{}",
+                    snippet
+                )
+            })?;
+            let index = value.get("index").and_then(Value::as_u64).unwrap_or(0) as usize;
+            match value.get("type").and_then(Value::as_str).unwrap_or("") {
+                "message_start" => {
+                    if let Some(u) = value.pointer("/message/usage").and_then(Value::as_object) {
+                        for (k, v) in u {
+                            usage[k] = v.clone();
+                        }
+                    }
+                }
+                "content_block_start" => {
+                    while blocks.len() <= index {
+                        blocks.push(json!({"type": "text", "text": ""}));
+                        partial_json.push(String::new());
+                    }
+                    if let Some(block) = value.get("content_block") {
+                        blocks[index] = block.clone();
+                    }
+                }
+                "content_block_delta" => {
+                    let Some(delta) = value.get("delta") else {
+                        continue;
+                    };
+                    if index >= blocks.len() {
+                        continue;
+                    }
+                    match delta.get("type").and_then(Value::as_str).unwrap_or("") {
+                        "text_delta" => {
+                            let piece = delta.get("text").and_then(Value::as_str).unwrap_or("");
+                            let text = blocks[index]["text"].as_str().unwrap_or("").to_string();
+                            blocks[index]["text"] = json!(text + piece);
+                            on_text(&joined_text(&blocks))?;
+                        }
+                        "thinking_delta" | "signature_delta" => {
+                            let key = if delta["type"] == "thinking_delta" {
+                                "thinking"
+                            } else {
+                                "signature"
+                            };
+                            let piece = delta.get(key).and_then(Value::as_str).unwrap_or("");
+                            let text = blocks[index][key].as_str().unwrap_or("").to_string();
+                            blocks[index][key] = json!(text + piece);
+                        }
+                        "input_json_delta" => {
+                            let piece = delta
+                                .get("partial_json")
+                                .and_then(Value::as_str)
+                                .unwrap_or("");
+                            partial_json[index].push_str(piece);
+                        }
+                        _ => {}
+                    }
+                }
+                "message_delta" => {
+                    if let Some(u) = value.get("usage").and_then(Value::as_object) {
+                        for (k, v) in u {
+                            usage[k] = v.clone();
+                        }
+                    }
+                }
+                "message_stop" => {
+                    for (block, raw) in blocks.iter_mut().zip(&partial_json) {
+                        if block["type"] == "tool_use" && !raw.trim().is_empty() {
+                            block["input"] =
+                                serde_json::from_str(raw).unwrap_or_else(|_| json!({}));
+                        }
+                    }
+                    return Ok(CompletionResult {
+                        message: message_from_blocks(&blocks),
+                        usage: Some(usage_from_value(&usage)),
+                        streamed_item_id: None,
+                    });
+                }
+                "error" => {
+                    let err = value.get("error").unwrap_or(&value);
+                    return Err(anyhow!(
+                        "Anthropic stream error\nMessage: {}",
+                        error_message(err)
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn joined_text(blocks: &[Value]) -> String {
+    blocks
+        .iter()
+        .filter(|b| b["type"] == "text")
+        .filter_map(|b| b.get("text").and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+fn is_thinking_block(block: &Value) -> bool {
+    block["type"] == "thinking" || block["type"] == "redacted_thinking"
+}
+
+fn message_from_blocks(blocks: &[Value]) -> AssistantMessage {
+    let text = joined_text(blocks);
+    let reasoning_details: Vec<Value> = blocks
+        .iter()
+        .filter(|b| is_thinking_block(b))
+        .cloned()
+        .collect();
+    let reasoning = reasoning_details
+        .iter()
+        .filter_map(|b| b.get("thinking").and_then(Value::as_str))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let tool_calls: Vec<ToolCall> = blocks
+        .iter()
+        .filter(|b| b["type"] == "tool_use")
+        .map(|b| ToolCall {
+            id: b
+                .get("id")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_string(),
+            call_type: "function".to_string(),
+            function: ToolFunction {
+                name: b
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string(),
+                arguments: b
+                    .get("input")
+                    .cloned()
+                    .unwrap_or_else(|| json!({}))
+                    .to_string(),
+            },
+        })
+        .collect();
+    AssistantMessage {
+        content: if text.is_empty() { None } else { Some(text) },
+        tool_calls,
+        reasoning: if reasoning.is_empty() {
+            None
+        } else {
+            Some(reasoning)
+        },
+        reasoning_details,
+    }
+}
+
+fn usage_from_value(usage: &Value) -> Usage {
+    let get = |key: &str| usage.get(key).and_then(Value::as_i64).unwrap_or(0);
+    let cache_read = get("cache_read_input_tokens");
+    // Anthropic reports cached and uncached input separately; OpenAI-style prompt_tokens
+    // includes both.
+    let input = get("input_tokens") + cache_read + get("cache_creation_input_tokens");
+    let output = get("output_tokens");
+    Usage {
+        prompt_tokens: input,
+        completion_tokens: output,
+        total_tokens: input + output,
+        prompt_tokens_details: Some(PromptTokensDetails {
+            cached_tokens: Some(cache_read),
+        }),
+        completion_tokens_details: None,
+        cost: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Anthropic;
+    use crate::protocol::{AssistantMessage, Message, ToolCall, ToolFunction};
+    use crate::provider::{Provider, RequestParams, SamplingParams};
+    use serde_json::json;
+
+    #[test]
+    fn translates_history_to_alternating_messages() {
+        let messages = vec![
+            Message::System {
+                content: "sys".into(),
+            },
+            Message::User {
+                content: "task".into(),
+            },
+            Message::Assistant(AssistantMessage {
+                content: None,
+                tool_calls: vec![ToolCall {
+                    id: "c1".into(),
+                    call_type: "function".into(),
+                    function: ToolFunction {
+                        name: "read_file".into(),
+                        arguments: "{\"file_path\":\"a\"}".into(),
+                    },
+                }],
+                reasoning: Some("check a".into()),
+                reasoning_details: vec![
+                    json!({"type": "thinking", "thinking": "check a", "signature": "sig"}),
+                    json!({"type": "reasoning.text", "text": "from another provider"}),
+                ],
+            }),
+            Message::Tool {
+                tool_call_id: "c1".into(),
+                content: "data".into(),
+            },
+            Message::User {
+                content: "continue".into(),
+            },
+        ];
+        let params = RequestParams {
+            model: "claude",
+            sampling: &SamplingParams::default(),
+            stream: false,
+            parallel_tool_calls: false,
+        };
+        let tools = vec![json!({"type": "function", "function": {
+            "name": "read_file",
+            "description": "Read a file.",
+            "parameters": {"type": "object"}
+        }})];
+        let body = Anthropic
+            .build_request(&params, &messages, &tools)
+            .expect("request");
+        assert_eq!(body["system"], "sys");
+        let out = body["messages"].as_array().expect("messages");
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1]["content"].as_array().expect("blocks").len(), 2);
+        assert_eq!(out[1]["content"][0]["signature"], "sig");
+        assert_eq!(out[1]["content"][1]["type"], "tool_use");
+        assert_eq!(out[1]["content"][1]["input"]["file_path"], "a");
+        assert_eq!(out[2]["role"], "user");
+        assert_eq!(out[2]["content"][0]["type"], "tool_result");
+        assert_eq!(out[2]["content"][1]["text"], "continue");
+        assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(body["tool_choice"]["disable_parallel_tool_use"], true);
+    }
+}
@@ -0,0 +1,323 @@
+use super::{names_missing_model, ModelUnavailable, OpenAiChat, Provider, RequestParams};
+use crate::protocol::{AssistantMessage, CompletionResult, Message, ToolCall, ToolFunction, Usage};
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::Value;
+use std::cell::Cell;
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Replays canned assistant turns from a JSONL script instead of calling a model.
+///
+/// Each non-empty line is one model response, consumed in order:
+///
+/// ```text
+/// {"message": {"content": "...", "reasoning": "...", "tool_calls": [{"function": {"name": "read_file", "arguments": {...}}}]},
+///  "usage": {...}, "expect": {...}, "expect_last_message": {...}}
+/// {"error": "maximum context length exceeded"}
+/// {"error": "model overloaded", "status": 503}
+/// ```
+///
+/// Tool call `id`s default to `mock_call_<n>` and `arguments` may be an object or a string.
+/// `expect` / `expect_last_message` are matched as subsets of the Chat Completions request body
+/// (or its final message) the agent would have sent; a mismatch fails the run. An `error` step
+/// fails the request with that message, so context-overflow pruning can be exercised too; with
+/// a `status` of 429 or 5xx it fails as if that status outlasted the retries, which moves on to
+/// the next `--fallback-model`; so does a 404 whose message names a missing model.
+/// Side requests (e.g. `--context-strategy summarize`) consume steps like any other request.
+pub(crate) struct Mock {
+    steps: Vec<MockStep>,
+    cursor: Cell<usize>,
+}
+
+struct MockError {
+    message: String,
+    status: Option<u16>,
+}
+
+struct MockStep {
+    line: usize,
+    response: Result<CompletionResult, MockError>,
+    expect: Option<Value>,
+    expect_last_message: Option<Value>,
+}
+
+impl Mock {
+    pub(crate) fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read mock script {}", path.display()))?;
+        let mut steps = Vec::new();
+        let mut call_counter = 0usize;
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let step = parse_step(line, i + 1, &mut call_counter)
+                .with_context(|| format!("{}:{}: invalid mock step", path.display(), i + 1))?;
+            steps.push(step);
+        }
+        if steps.is_empty() {
+            bail!("mock script {} has no steps", path.display());
+        }
+        Ok(Self {
+            steps,
+            cursor: Cell::new(0),
+        })
+    }
+}
+
+impl Provider for Mock {
+    fn label(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn endpoint(&self, _base_url: &str) -> String {
+        "mock://chat/completions".to_string()
+    }
+
+    fn authorize(&self, request: RequestBuilder, _api_key: &str) -> RequestBuilder {
+        request
+    }
+
+    fn build_request(
+        &self,
+        params: &RequestParams<'_>,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<Value> {
+        OpenAiChat.build_request(params, messages, tools)
+    }
+
+    fn parse_response(&self, _body: &str) -> Result<CompletionResult> {
+        bail!("mock provider does not make HTTP requests")
+    }
+
+    fn read_stream(
+        &self,
+        _reader: &mut dyn BufRead,
+        _on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<CompletionResult> {
+        bail!("mock provider does not make HTTP requests")
+    }
+
+    fn respond_offline(&self, request: &Value) -> Option<Result<CompletionResult>> {
+        let index = self.cursor.get();
+        self.cursor.set(index + 1);
+        let Some(step) = self.steps.get(index) else {
+            return Some(Err(anyhow!(
+                "mock script exhausted: no step for request {} (script has {} steps)",
+                index + 1,
+                self.steps.len()
+            )));
+        };
+        Some(step.respond(request))
+    }
+}
+
+impl MockStep {
+    fn respond(&self, request: &Value) -> Result<CompletionResult> {
+        if let Some(expected) = &self.expect {
+            check_subset(expected, request, "")
+                .map_err(|e| anyhow!("mock script line {}: request mismatch {}", self.line, e))?;
+        }
+        if let Some(expected) = &self.expect_last_message {
+            let last = request
+                .get("messages")
+                .and_then(Value::as_array)
+                .and_then(|m| m.last())
+                .unwrap_or(&Value::Null);
+            check_subset(expected, last, "").map_err(|e| {
+                anyhow!(
+                    "mock script line {}: last message mismatch {}",
+                    self.line,
+                    e
+                )
+            })?;
+        }
+        match &self.response {
+            Ok(result) => Ok(CompletionResult {
+                message: result.message.clone(),
+                usage: result.usage.clone(),
+                streamed_item_id: None,
+            }),
+            Err(MockError {
+                message,
+                status: Some(429 | 500..=599),
+            }) => Err(anyhow!(ModelUnavailable(message.clone()))),
+            Err(MockError {
+                message,
+                status: Some(404),
+            }) if names_missing_model(
+                message,
+                request.get("model").and_then(Value::as_str).unwrap_or(""),
+            ) =>
+            {
+                Err(anyhow!(ModelUnavailable(message.clone())))
+            }
+            Err(MockError { message, .. }) => Err(anyhow!("{}", message)),
+        }
+    }
+}
+
+fn parse_step(line: &str, line_no: usize, call_counter: &mut usize) -> Result<MockStep> {
+    let step: Value = serde_json::from_str(line)?;
+    let expect = step.get("expect").cloned();
+    let expect_last_message = step.get("expect_last_message").cloned();
+
+    if let Some(err) = step.get("error") {
+        let message = err
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| err.to_string());
+        let status = step
+            .get("status")
+            .and_then(Value::as_u64)
+            .map(|status| status as u16);
+        return Ok(MockStep {
+            line: line_no,
+            response: Err(MockError { message, status }),
+            expect,
+            expect_last_message,
+        });
+    }
+
+    let message = step
+        .get("message")
+        .ok_or_else(|| anyhow!("step needs either `message` or `error`"))?;
+    let content = message
+        .get("content")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let reasoning = message
+        .get("reasoning")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let reasoning_details = message
+        .get("reasoning_details")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let mut tool_calls = Vec::new();
+    let calls = message.get("tool_calls").and_then(Value::as_array);
+    for tc in calls.into_iter().flatten() {
+        *call_counter += 1;
+        let name = tc
+            .pointer("/function/name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("tool call is missing function.name"))?;
+        let arguments = match tc.pointer("/function/arguments") {
+            Some(Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => "{}".to_string(),
+        };
+        tool_calls.push(ToolCall {
+            id: tc
+                .get("id")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("mock_call_{}", call_counter)),
+            call_type: "function".to_string(),
+            function: ToolFunction {
+                name: name.to_string(),
+                arguments,
+            },
+        });
+    }
+    let usage: Option<Usage> = step
+        .get("usage")
+        .map(|u| serde_json::from_value(u.clone()))
+        .transpose()
+        .context("invalid usage")?;
+
+    Ok(MockStep {
+        line: line_no,
+        response: Ok(CompletionResult {
+            message: AssistantMessage {
+                content,
+                tool_calls,
+                reasoning,
+                reasoning_details,
+            },
+            usage,
+            streamed_item_id: None,
+        }),
+        expect,
+        expect_last_message,
+    })
+}
+
+/// Checks that `expected` is contained in `actual`: object keys are matched recursively, arrays
+/// element-wise as a prefix, and everything else by equality. Errors name the first mismatch.
+fn check_subset(expected: &Value, actual: &Value, path: &str) -> std::result::Result<(), String> {
+    match (expected, actual) {
+        (Value::Object(exp), Value::Object(act)) => {
+            for (key, exp_value) in exp {
+                let child = format!("{}/{}", path, key);
+                match act.get(key) {
+                    Some(act_value) => check_subset(exp_value, act_value, &child)?,
+                    None => return Err(format!("at {}: key is missing", child)),
+                }
+            }
+            Ok(())
+        }
+        (Value::Array(exp), Value::Array(act)) => {
+            if exp.len() > act.len() {
+                return Err(format!(
+                    "at {}: expected at least {} elements, got {}",
+                    display_path(path),
+                    exp.len(),
+                    act.len()
+                ));
+            }
+            for (i, (e, a)) in exp.iter().zip(act).enumerate() {
+                check_subset(e, a, &format!("{}/{}", path, i))?;
+            }
+            Ok(())
+        }
+        _ if expected == actual => Ok(()),
+        _ => Err(format!(
+            "at {}: expected {}, got {}",
+            display_path(path),
+            expected,
+            actual
+        )),
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_subset;
+    use serde_json::json;
+
+    #[test]
+    fn subset_matching_reports_first_mismatch() {
+        let actual = json!({
+            "model": "m",
+            "messages": [
+                {"role": "system", "content": "sys"},
+                {"role": "user", "content": "task"}
+            ]
+        });
+        assert!(check_subset(&json!({"messages": [{"role": "system"}]}), &actual, "").is_ok());
+        assert_eq!(
+            check_subset(
+                &json!({"messages": [{}, {"content": "other"}]}),
+                &actual,
+                ""
+            ),
+            Err("at /messages/1/content: expected \"other\", got \"task\"".to_string())
+        );
+        assert!(check_subset(&json!({"tools": []}), &actual, "")
+            .unwrap_err()
+            .contains("missing"));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::cli::Args;
+use crate::protocol::{CompletionResult, Message};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use reqwest::blocking::RequestBuilder;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::BufRead;
+
+mod anthropic;
+mod mock;
+mod openai_chat;
+mod responses;
+
+pub(crate) use anthropic::Anthropic;
+pub(crate) use mock::Mock;
+pub(crate) use openai_chat::OpenAiChat;
+pub(crate) use responses::Responses;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ProviderKind {
+    /// OpenAI-style Chat Completions (`/chat/completions`), e.g. OpenRouter.
+    OpenaiChat,
+    /// Anthropic Messages API (`/messages`).
+    Anthropic,
+    /// OpenAI Responses API (`/responses`).
+    Responses,
+    /// Replays a scripted conversation from `--mock-script` (no network, no API key).
+    Mock,
+}
+
+impl ProviderKind {
+    /// Environment variables checked (in order) when `--api-key` is not set.
+    pub(crate) fn api_key_envs(self) -> &'static [&'static str] {
+        match self {
+            ProviderKind::OpenaiChat => &["OPENROUTER_API_KEY"],
+            ProviderKind::Anthropic => &["ANTHROPIC_API_KEY", "OPENROUTER_API_KEY"],
+            ProviderKind::Responses => &["OPENAI_API_KEY", "OPENROUTER_API_KEY"],
+            ProviderKind::Mock => &[],
+        }
+    }
+
+    pub(crate) fn needs_api_key(self) -> bool {
+        self != ProviderKind::Mock
+    }
+
+    pub(crate) fn build(self, args: &Args) -> Result<Box<dyn Provider>> {
+        Ok(match self {
+            ProviderKind::OpenaiChat => Box::new(OpenAiChat),
+            ProviderKind::Anthropic => Box::new(Anthropic),
+            ProviderKind::Responses => Box::new(Responses),
+            ProviderKind::Mock => {
+                let path = args
+                    .mock_script
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("--provider mock requires --mock-script"))?;
+                Box::new(Mock::from_file(path)?)
+            }
+        })
+    }
+}
+
+/// Sampling settings from `--temperature`, `--top-p`, and the other sampling flags. Each is
+/// unset unless explicitly configured, and providers send only what is set.
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct SamplingParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) seed: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reasoning_effort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) presence_penalty: Option<f64>,
+    /// `--extra-body`: top-level keys merged into every request, replacing any built ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) extra_body: Option<Map<String, Value>>,
+}
+
+impl SamplingParams {
+    pub(crate) fn from_args(args: &Args) -> Result<Self> {
+        let extra_body = match args.extra_body.as_deref() {
+            Some(raw) => {
+                match serde_json::from_str(raw).context("--extra-body is not valid JSON")? {
+                    Value::Object(map) => Some(map),
+                    _ => bail!("--extra-body must be a JSON object"),
+                }
+            }
+            None => None,
+        };
+        Ok(Self {
+            temperature: args.temperature,
+            top_p: args.top_p,
+            max_tokens: args.max_tokens,
+            seed: args.seed,
+            stop: args.stop.clone(),
+            reasoning_effort: args.reasoning_effort.clone(),
+            frequency_penalty: args.frequency_penalty,
+            presence_penalty: args.presence_penalty,
+            extra_body,
+        })
+    }
+
+    /// The configured settings as recorded in `turn.started`.
+    pub(crate) fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Errors if a setting the provider's API has no field for is configured, rather than
+    /// silently dropping it.
+    pub(crate) fn reject_unsupported(&self, label: &str, unsupported: &[&str]) -> Result<()> {
+        let configured = self.to_json();
+        for key in unsupported {
+            if configured.get(key).is_some() {
+                bail!(
+                    "--{} is not supported by the {label} provider",
+                    key.replace('_', "-")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges `--extra-body` into a built request body.
+    pub(crate) fn apply_extra_body(&self, body: &mut Value) {
+        if let (Some(extra), Value::Object(body)) = (&self.extra_body, body) {
+            for (key, value) in extra {
+                body.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Provider-independent request settings. Only explicitly configured values are set.
+pub(crate) struct RequestParams<'a> {
+    pub(crate) model: &'a str,
+    pub(crate) sampling: &'a SamplingParams,
+    pub(crate) stream: bool,
+    /// Let the model emit several tool calls per step (`--parallel-tools`).
+    pub(crate) parallel_tool_calls: bool,
+}
+
+/// A model backend's wire format.
+///
+/// The agent keeps its history as typed `Message`s and its tool schemas in Chat Completions form
+/// (as produced by `build_tools`); each provider translates those to and from its own
+/// request/response shape.
+/// HTTP transport, retries and logging stay in `Agent`.
+pub(crate) trait Provider {
+    /// Human-readable name used in error messages.
+    fn label(&self) -> &'static str;
+
+    fn endpoint(&self, base_url: &str) -> String;
+
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder;
+
+    fn build_request(
+        &self,
+        params: &RequestParams<'_>,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<Value>;
+
+    fn parse_response(&self, body: &str) -> Result<CompletionResult>;
+
+    /// Reads a `text/event-stream` response to completion. `on_text` receives the accumulated
+    /// assistant text whenever it grows. Returns `StreamInterrupted` if the stream ends early.
+    fn read_stream(
+        &self,
+        reader: &mut dyn BufRead,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<CompletionResult>;
+
+    /// Answers `request` without any HTTP round trip. Only offline providers (the mock) return
+    /// `Some`; the agent then skips transport and retries entirely.
+    fn respond_offline(&self, _request: &Value) -> Option<Result<CompletionResult>> {
+        None
+    }
+}
+
+/// Returned when the model is still unavailable after retries (HTTP 429/5xx) or is not found
+/// (an HTTP 404 that `names_missing_model`), so the agent can move on to the next
+/// `--fallback-model`.
+#[derive(Debug)]
+pub(crate) struct ModelUnavailable(pub(crate) String);
+
+impl fmt::Display for ModelUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ModelUnavailable {}
+
+/// Whether a 404 error body is about the model rather than the URL: OpenAI's
+/// `model_not_found`, OpenRouter's "No endpoints found", or any body naming `model` (Anthropic
+/// reports `not_found_error` with the model id). A wrong `--base-url` or path matches none.
+pub(crate) fn names_missing_model(body: &str, model: &str) -> bool {
+    let lower = body.to_ascii_lowercase();
+    lower.contains("model_not_found")
+        || lower.contains("no endpoints found")
+        || (!model.is_empty() && body.contains(model))
+}
+
+/// Extracts a human-readable message from a provider error payload.
+pub(crate) fn error_message(err: &Value) -> String {
+    err.get("message")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| err.to_string())
+}
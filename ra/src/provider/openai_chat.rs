@@ -0,0 +1,374 @@
+use super::{error_message, Provider, RequestParams};
+use crate::protocol::{
+    AssistantMessage, ChatCompletionChunk, ChatCompletionResponse, CompletionResult, Message,
+    ToolCall, ToolFunction, Usage,
+};
+use crate::stream::{SseReader, StreamInterrupted};
+use crate::tools::truncate;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::{json, Value};
+use std::io::BufRead;
+
+/// OpenAI-style Chat Completions (the default; what OpenRouter speaks).
+pub(crate) struct OpenAiChat;
+
+impl Provider for OpenAiChat {
+    fn label(&self) -> &'static str {
+        "OpenRouter"
+    }
+
+    fn endpoint(&self, base_url: &str) -> String {
+        format!("{}/chat/completions", base_url.trim_end_matches('/'))
+    }
+
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request.header("Authorization", format!("Bearer {}", api_key))
+    }
+
+    fn build_request(
+        &self,
+        params: &RequestParams<'_>,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<Value> {
+        let mut body = json!({
+            "model": params.model,
+            "messages": messages,
+        });
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+            body["tool_choice"] = json!("auto");
+            body["parallel_tool_calls"] = json!(params.parallel_tool_calls);
+        }
+        let sampling = params.sampling;
+        if let Some(temp) = sampling.temperature {
+            body["temperature"] = json!(temp);
+        }
+        if let Some(top_p) = sampling.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = sampling.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if let Some(seed) = sampling.seed {
+            body["seed"] = json!(seed);
+        }
+        if !sampling.stop.is_empty() {
+            body["stop"] = json!(sampling.stop);
+        }
+        if let Some(effort) = &sampling.reasoning_effort {
+            body["reasoning_effort"] = json!(effort);
+        }
+        if let Some(penalty) = sampling.frequency_penalty {
+            body["frequency_penalty"] = json!(penalty);
+        }
+        if let Some(penalty) = sampling.presence_penalty {
+            body["presence_penalty"] = json!(penalty);
+        }
+        if params.stream {
+            body["stream"] = json!(true);
+            // Usage arrives in a final chunk only when requested.
+            body["stream_options"] = json!({"include_usage": true});
+        }
+        Ok(body)
+    }
+
+    fn parse_response(&self, body: &str) -> Result<CompletionResult> {
+        let parsed: ChatCompletionResponse = serde_json::from_str(body)?;
+        let choice = parsed
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no choices in response"))?;
+        Ok(CompletionResult {
+            message: choice.message,
+            usage: parsed.usage,
+            streamed_item_id: None,
+        })
+    }
+
+    fn read_stream(
+        &self,
+        reader: &mut dyn BufRead,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<CompletionResult> {
+        let mut sse = SseReader::new(reader);
+        let mut acc = ChatStreamAccumulator::default();
+        loop {
+            let event = match sse.next_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => {
+                    return Err(anyhow!(StreamInterrupted(
+                        "stream ended before [DONE]".to_string()
+                    )));
+                }
+                Err(err) => return Err(anyhow!(StreamInterrupted(err.to_string()))),
+            };
+            let data = event.data.trim();
+            if data.is_empty() {
+                continue;
+            }
+            if data == "[DONE]" {
+                return Ok(acc.finish());
+            }
+            let value: Value = serde_json::from_str(data).with_context(|| {
+                let (snippet, _) = truncate(data, 2000);
+                format!(
+                    "OpenRouter returned an unexpected stream chunk:\n{}",
+                    snippet
+                )
+            })?;
+            if let Some(err) = value.get("error") {
+                // Providers may report failures mid-stream (e.g. context overflow after routing).
+                return Err(anyhow!(
+                    "OpenRouter stream error\nMessage: {}",
+                    error_message(err)
+                ));
+            }
+            let chunk: ChatCompletionChunk =
+                serde_json::from_value(value).context("failed to decode stream chunk")?;
+            if acc.apply(chunk) {
+                on_text(&acc.content)?;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct ChatStreamAccumulator {
+    content: String,
+    saw_content: bool,
+    tool_calls: Vec<ToolCall>,
+    reasoning: String,
+    reasoning_details: Vec<Value>,
+    usage: Option<Usage>,
+}
+
+impl ChatStreamAccumulator {
+    /// Applies one chunk. Returns true if assistant text changed.
+    fn apply(&mut self, chunk: ChatCompletionChunk) -> bool {
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+        let mut text_changed = false;
+        for choice in chunk.choices {
+            let Some(delta) = choice.delta else {
+                continue;
+            };
+            if let Some(text) = delta.content {
+                if !text.is_empty() {
+                    self.content.push_str(&text);
+                    self.saw_content = true;
+                    text_changed = true;
+                }
+            }
+            if let Some(text) = delta.reasoning {
+                self.reasoning.push_str(&text);
+            }
+            for detail in delta.reasoning_details.unwrap_or_default() {
+                merge_reasoning_detail(&mut self.reasoning_details, detail);
+            }
+            for tc in delta.tool_calls.unwrap_or_default() {
+                while self.tool_calls.len() <= tc.index {
+                    self.tool_calls.push(ToolCall {
+                        id: String::new(),
+                        call_type: "function".to_string(),
+                        function: ToolFunction {
+                            name: String::new(),
+                            arguments: String::new(),
+                        },
+                    });
+                }
+                let slot = &mut self.tool_calls[tc.index];
+                if let Some(id) = tc.id.filter(|id| !id.is_empty()) {
+                    slot.id = id;
+                }
+                if let Some(call_type) = tc.call_type.filter(|t| !t.is_empty()) {
+                    slot.call_type = call_type;
+                }
+                if let Some(function) = tc.function {
+                    if let Some(name) = function.name {
+                        slot.function.name.push_str(&name);
+                    }
+                    if let Some(arguments) = function.arguments {
+                        slot.function.arguments.push_str(&arguments);
+                    }
+                }
+            }
+        }
+        text_changed
+    }
+
+    fn finish(self) -> CompletionResult {
+        let tool_calls: Vec<ToolCall> = self
+            .tool_calls
+            .into_iter()
+            .filter(|tc| !tc.function.name.is_empty())
+            .collect();
+        CompletionResult {
+            message: AssistantMessage {
+                content: if self.saw_content {
+                    Some(self.content)
+                } else {
+                    None
+                },
+                tool_calls,
+                reasoning: if self.reasoning.is_empty() {
+                    None
+                } else {
+                    Some(self.reasoning)
+                },
+                reasoning_details: self.reasoning_details,
+            },
+            usage: self.usage,
+            streamed_item_id: None,
+        }
+    }
+}
+
+/// OpenRouter streams `reasoning_details` as fragments tagged with an `index`; fragments of the
+/// same entry have their text fields concatenated.
+fn merge_reasoning_detail(details: &mut Vec<Value>, detail: Value) {
+    let index = detail.get("index").and_then(Value::as_u64);
+    let existing = details
+        .iter_mut()
+        .find(|d| index.is_some() && d.get("index").and_then(Value::as_u64) == index);
+    let (Some(existing), Value::Object(fields)) = (existing, &detail) else {
+        details.push(detail);
+        return;
+    };
+    for (key, value) in fields {
+        match (existing.get(key).and_then(Value::as_str), value.as_str()) {
+            (Some(prev), Some(piece)) if matches!(key.as_str(), "text" | "summary" | "data") => {
+                existing[key] = json!(format!("{prev}{piece}"));
+            }
+            _ if value.is_null() => {}
+            _ => existing[key] = value.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpenAiChat;
+    use crate::protocol::Message;
+    use crate::provider::{Provider, RequestParams, Responses, SamplingParams};
+    use crate::stream::StreamInterrupted;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn sends_only_configured_sampling_params() {
+        let messages = vec![Message::User {
+            content: "hi".into(),
+        }];
+        let unset = SamplingParams::default();
+        let mut params = RequestParams {
+            model: "m",
+            sampling: &unset,
+            stream: false,
+            parallel_tool_calls: false,
+        };
+        let body = OpenAiChat.build_request(&params, &messages, &[]).unwrap();
+        let keys: Vec<&String> = body.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["messages", "model"]);
+
+        let extra = json!({"provider": {"order": ["openai"]}, "top_p": 0.9});
+        let sampling = SamplingParams {
+            top_p: Some(0.5),
+            seed: Some(7),
+            stop: vec!["END".to_string()],
+            reasoning_effort: Some("low".to_string()),
+            extra_body: extra.as_object().cloned(),
+            ..SamplingParams::default()
+        };
+        params.sampling = &sampling;
+        let mut body = OpenAiChat.build_request(&params, &messages, &[]).unwrap();
+        assert_eq!(body["seed"], 7);
+        assert_eq!(body["stop"], json!(["END"]));
+        assert_eq!(body["reasoning_effort"], "low");
+        assert!(body.get("temperature").is_none());
+        sampling.apply_extra_body(&mut body);
+        assert_eq!(body["top_p"], 0.9);
+        assert_eq!(body["provider"]["order"][0], "openai");
+
+        let err = Responses
+            .build_request(&params, &messages, &[])
+            .unwrap_err();
+        assert!(err.to_string().contains("--seed"), "{err}");
+    }
+
+    #[test]
+    fn assembles_content_and_tool_call_deltas() {
+        let body = concat!(
+            ": OPENROUTER PROCESSING\n\n",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call-1\",\"type\":\"function\",\"function\":{\"name\":\"read_file\",\"arguments\":\"{\\\"file_\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"path\\\":\\\"a\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":2,\"total_tokens\":5}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let mut updates = Vec::new();
+        let result = OpenAiChat
+            .read_stream(&mut Cursor::new(body), &mut |text| {
+                updates.push(text.to_string());
+                Ok(())
+            })
+            .expect("stream");
+
+        assert_eq!(updates, vec!["Hel", "Hello"]);
+        assert_eq!(result.message.content.as_deref(), Some("Hello"));
+        let calls = result.message.tool_calls;
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "call-1");
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(calls[0].function.arguments, "{\"file_path\":\"a\"}");
+        assert_eq!(result.usage.expect("usage").total_tokens, 5);
+    }
+
+    #[test]
+    fn accumulates_reasoning_deltas() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"reasoning\":\"Think\",\"reasoning_details\":[{\"type\":\"reasoning.text\",\"text\":\"Think\",\"index\":0}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"reasoning\":\"ing.\",\"reasoning_details\":[{\"type\":\"reasoning.text\",\"text\":\"ing.\",\"signature\":\"sig\",\"index\":0}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Done\",\"reasoning_details\":[{\"type\":\"reasoning.encrypted\",\"data\":\"xyz\",\"index\":1}]}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let result = OpenAiChat
+            .read_stream(&mut Cursor::new(body), &mut |_| Ok(()))
+            .expect("stream");
+        let message = result.message;
+        assert_eq!(message.reasoning.as_deref(), Some("Thinking."));
+        assert_eq!(
+            message.reasoning_details,
+            vec![
+                json!({"type": "reasoning.text", "text": "Thinking.", "signature": "sig", "index": 0}),
+                json!({"type": "reasoning.encrypted", "data": "xyz", "index": 1}),
+            ]
+        );
+
+        // Both fields go back to OpenRouter with the assistant turn.
+        let params = RequestParams {
+            model: "m",
+            sampling: &SamplingParams::default(),
+            stream: false,
+            parallel_tool_calls: false,
+        };
+        let body = OpenAiChat
+            .build_request(&params, &[Message::Assistant(message)], &[])
+            .unwrap();
+        assert_eq!(body["messages"][0]["reasoning"], "Thinking.");
+        assert_eq!(body["messages"][0]["reasoning_details"][1]["data"], "xyz");
+    }
+
+    #[test]
+    fn missing_done_is_interrupted() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"partial\"}}]}\n\n";
+        let Err(err) = OpenAiChat.read_stream(&mut Cursor::new(body), &mut |_| Ok(())) else {
+            panic!("expected an interrupted stream");
+        };
+        assert!(err.downcast_ref::<StreamInterrupted>().is_some());
+    }
+}
@@ -0,0 +1,275 @@
+use super::{error_message, Provider, RequestParams};
+use crate::protocol::{
+    AssistantMessage, CompletionResult, CompletionTokensDetails, Message, PromptTokensDetails,
+    ToolCall, ToolFunction, Usage,
+};
+use crate::stream::{SseReader, StreamInterrupted};
+use crate::tools::truncate;
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::RequestBuilder;
+use serde_json::{json, Value};
+use std::io::BufRead;
+
+/// OpenAI Responses API.
+pub(crate) struct Responses;
+
+impl Provider for Responses {
+    fn label(&self) -> &'static str {
+        "Responses API"
+    }
+
+    fn endpoint(&self, base_url: &str) -> String {
+        format!("{}/responses", base_url.trim_end_matches('/'))
+    }
+
+    fn authorize(&self, request: RequestBuilder, api_key: &str) -> RequestBuilder {
+        request.header("Authorization", format!("Bearer {}", api_key))
+    }
+
+    fn build_request(
+        &self,
+        params: &RequestParams<'_>,
+        messages: &[Message],
+        tools: &[Value],
+    ) -> Result<Value> {
+        let mut instructions: Vec<&str> = Vec::new();
+        let mut input: Vec<Value> = Vec::new();
+        for msg in messages {
+            match msg {
+                Message::System { content } => instructions.push(content),
+                Message::User { content } => {
+                    input.push(json!({"role": "user", "content": content}))
+                }
+                Message::Assistant(assistant) => {
+                    // Reasoning items precede the output they produced.
+                    input.extend(
+                        assistant
+                            .reasoning_details
+                            .iter()
+                            .filter(|d| d["type"] == "reasoning")
+                            .cloned(),
+                    );
+                    if let Some(content) = assistant.content.as_deref() {
+                        if !content.trim().is_empty() {
+                            input.push(json!({"role": "assistant", "content": content}));
+                        }
+                    }
+                    for tc in &assistant.tool_calls {
+                        input.push(json!({
+                            "type": "function_call",
+                            "call_id": tc.id,
+                            "name": tc.function.name,
+                            "arguments": tc.function.arguments,
+                        }));
+                    }
+                }
+                Message::Tool {
+                    tool_call_id,
+                    content,
+                } => input.push(json!({
+                    "type": "function_call_output",
+                    "call_id": tool_call_id,
+                    "output": content,
+                })),
+            }
+        }
+
+        let tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|t| t.get("function"))
+            .map(|f| {
+                json!({
+                    "type": "function",
+                    "name": f.get("name").cloned().unwrap_or(Value::Null),
+                    "description": f.get("description").cloned().unwrap_or(Value::Null),
+                    "parameters": f.get("parameters").cloned().unwrap_or_else(|| json!({"type": "object"})),
+                })
+            })
+            .collect();
+
+        let mut body = json!({
+            "model": params.model,
+            "input": input,
+        });
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+            body["tool_choice"] = json!("auto");
+            body["parallel_tool_calls"] = json!(params.parallel_tool_calls);
+        }
+        if !instructions.is_empty() {
+            body["instructions"] = json!(instructions.join("\n\n"));
+        }
+        let sampling = params.sampling;
+        sampling.reject_unsupported(
+            self.label(),
+            &["seed", "stop", "frequency_penalty", "presence_penalty"],
+        )?;
+        if let Some(temp) = sampling.temperature {
+            body["temperature"] = json!(temp);
+        }
+        if let Some(top_p) = sampling.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = sampling.max_tokens {
+            body["max_output_tokens"] = json!(max_tokens);
+        }
+        if let Some(effort) = &sampling.reasoning_effort {
+            body["reasoning"] = json!({ "effort": effort });
+        }
+        if params.stream {
+            body["stream"] = json!(true);
+        }
+        Ok(body)
+    }
+
+    fn parse_response(&self, body: &str) -> Result<CompletionResult> {
+        let parsed: Value = serde_json::from_str(body)?;
+        completion_from_response(&parsed)
+    }
+
+    fn read_stream(
+        &self,
+        reader: &mut dyn BufRead,
+        on_text: &mut dyn FnMut(&str) -> Result<()>,
+    ) -> Result<CompletionResult> {
+        let mut sse = SseReader::new(reader);
+        let mut text = String::new();
+        loop {
+            let event = match sse.next_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => {
+                    return Err(anyhow!(StreamInterrupted(
+                        "stream ended before response.completed".to_string()
+                    )));
+                }
+                Err(err) => return Err(anyhow!(StreamInterrupted(err.to_string()))),
+            };
+            if event.data.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(&event.data).with_context(|| {
+                let (snippet, _) = truncate(&event.data, 2000);
+                format!(
+                    "Responses API returned an unexpected stream event:\n{}",
+                    snippet
+                )
+            })?;
+            match value.get("type").and_then(Value::as_str).unwrap_or("") {
+                "response.output_text.delta" => {
+                    text.push_str(value.get("delta").and_then(Value::as_str).unwrap_or(""));
+                    on_text(&text)?;
+                }
+                // The terminal event carries the full response, including tool calls and usage.
+                "response.completed" | "response.incomplete" => {
+                    let response = value
+                        .get("response")
+                        .ok_or_else(|| anyhow!("stream event is missing `response`"))?;
+                    return completion_from_response(response);
+                }
+                "response.failed" => {
+                    let err = value.pointer("/response/error").unwrap_or(&value);
+                    return Err(anyhow!(
+                        "Responses API stream error\nMessage: {}",
+                        error_message(err)
+                    ));
+                }
+                "error" => {
+                    return Err(anyhow!(
+                        "Responses API stream error\nMessage: {}",
+                        error_message(&value)
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn completion_from_response(response: &Value) -> Result<CompletionResult> {
+    let output = response
+        .get("output")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("no output in response"))?;
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    let mut reasoning: Vec<&str> = Vec::new();
+    let mut reasoning_details = Vec::new();
+    for item in output {
+        match item.get("type").and_then(Value::as_str).unwrap_or("") {
+            "reasoning" => {
+                // Summaries by default; raw `reasoning_text` content where the model exposes it.
+                for key in ["summary", "content"] {
+                    let parts = item.get(key).and_then(Value::as_array);
+                    reasoning.extend(
+                        parts
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|part| part.get("text").and_then(Value::as_str)),
+                    );
+                }
+                reasoning_details.push(item.clone());
+            }
+            "message" => {
+                let parts = item.get("content").and_then(Value::as_array);
+                for part in parts.into_iter().flatten() {
+                    if part["type"] == "output_text" {
+                        text.push_str(part.get("text").and_then(Value::as_str).unwrap_or(""));
+                    }
+                }
+            }
+            "function_call" => tool_calls.push(ToolCall {
+                id: item
+                    .get("call_id")
+                    .and_then(Value::as_str)
+                    .unwrap_or("")
+                    .to_string(),
+                call_type: "function".to_string(),
+                function: ToolFunction {
+                    name: item
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or("")
+                        .to_string(),
+                    arguments: item
+                        .get("arguments")
+                        .and_then(Value::as_str)
+                        .unwrap_or("{}")
+                        .to_string(),
+                },
+            }),
+            _ => {}
+        }
+    }
+    Ok(CompletionResult {
+        message: AssistantMessage {
+            content: if text.is_empty() { None } else { Some(text) },
+            tool_calls,
+            reasoning: if reasoning.is_empty() {
+                None
+            } else {
+                Some(reasoning.join("\n\n"))
+            },
+            reasoning_details,
+        },
+        usage: response.get("usage").map(usage_from_value),
+        streamed_item_id: None,
+    })
+}
+
+fn usage_from_value(usage: &Value) -> Usage {
+    let get = |ptr: &str| usage.pointer(ptr).and_then(Value::as_i64);
+    let input = get("/input_tokens").unwrap_or(0);
+    let output = get("/output_tokens").unwrap_or(0);
+    Usage {
+        prompt_tokens: input,
+        completion_tokens: output,
+        total_tokens: get("/total_tokens").unwrap_or(input + output),
+        prompt_tokens_details: Some(PromptTokensDetails {
+            cached_tokens: get("/input_tokens_details/cached_tokens"),
+        }),
+        completion_tokens_details: Some(CompletionTokensDetails {
+            reasoning_tokens: get("/output_tokens_details/reasoning_tokens"),
+        }),
+        cost: None,
+    }
+}
@@ -0,0 +1,283 @@
+use crate::cli::Args;
+use anyhow::{bail, Result};
+use reqwest::header::HeaderValue;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+/// How model requests and the web tools retry transient failures: capped exponential backoff
+/// with jitter, an optional budget for the total time spent retrying one request, and an
+/// opt-in circuit breaker per target.
+///
+/// Each retry is queued as a `request.retry` event; the agent logs them with `take_events`
+/// (tools may retry from worker threads, where the logger is not available).
+pub(crate) struct RetryPolicy {
+    /// Attempts per request, including the first.
+    pub(crate) max_attempts: usize,
+    pub(crate) base_delay: Duration,
+    pub(crate) max_delay: Duration,
+    /// Up to this fraction of each delay is added at random.
+    pub(crate) jitter: f64,
+    /// No retry starts once retrying a request would take longer than this in total.
+    pub(crate) budget: Option<Duration>,
+    /// After this many consecutive requests to one target use up their retries, further
+    /// requests to it get a single attempt until one succeeds (0, the default, disables).
+    pub(crate) breaker_threshold: usize,
+    failures: Mutex<HashMap<String, usize>>,
+    events: Mutex<Vec<Value>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(4),
+            jitter: 0.2,
+            budget: None,
+            breaker_threshold: 0,
+            failures: Mutex::new(HashMap::new()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The defaults, with any `--retry-*` flags applied.
+    pub(crate) fn from_args(args: &Args) -> Result<Self> {
+        let mut policy = Self::default();
+        if let Some(attempts) = args.retry_max_attempts {
+            if attempts == 0 {
+                bail!("--retry-max-attempts must be at least 1");
+            }
+            policy.max_attempts = attempts;
+        }
+        if let Some(ms) = args.retry_base_delay_ms {
+            policy.base_delay = Duration::from_millis(ms);
+        }
+        if let Some(ms) = args.retry_max_delay_ms {
+            policy.max_delay = Duration::from_millis(ms);
+        }
+        if let Some(jitter) = args.retry_jitter {
+            if !(0.0..=1.0).contains(&jitter) {
+                bail!("--retry-jitter must be between 0 and 1");
+            }
+            policy.jitter = jitter;
+        }
+        policy.budget = args.retry_budget_sec.map(Duration::from_secs);
+        if let Some(threshold) = args.retry_breaker {
+            policy.breaker_threshold = threshold;
+        }
+        Ok(policy)
+    }
+
+    /// Starts retrying one request to `target` (`model`, `web_search`, `web_fetch`) at `key`
+    /// (the model name or host).
+    pub(crate) fn start<'a>(&'a self, target: &'a str, key: &str) -> Retry<'a> {
+        let open = self.breaker_threshold > 0
+            && self.failures.lock().unwrap().get(key).copied().unwrap_or(0)
+                >= self.breaker_threshold;
+        Retry {
+            policy: self,
+            target,
+            key: key.to_string(),
+            attempt: 1,
+            max_attempts: if open { 1 } else { self.max_attempts },
+            started: Instant::now(),
+        }
+    }
+
+    /// Retry events queued since the last call, oldest first.
+    pub(crate) fn take_events(&self) -> Vec<Value> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    fn backoff(&self, attempt: usize) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << (attempt - 1).min(16))
+            .min(self.max_delay);
+        exp + exp.mul_f64(self.jitter * random_fraction())
+    }
+}
+
+/// Retry state for one request.
+pub(crate) struct Retry<'a> {
+    policy: &'a RetryPolicy,
+    target: &'a str,
+    key: String,
+    attempt: usize,
+    max_attempts: usize,
+    started: Instant,
+}
+
+impl Retry<'_> {
+    /// The attempt in progress, starting at 1.
+    pub(crate) fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    pub(crate) fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Called after a retryable failure: sleeps before the next attempt and returns true, or
+    /// returns false once the attempts or the time budget are used up. The server's
+    /// `retry_after` replaces the backoff; with a budget it is waited in full (the budget
+    /// decides whether to retry at all), without one it is capped at `max_delay`.
+    pub(crate) fn wait(&mut self, retry_after: Option<Duration>, reason: &str) -> bool {
+        let delay = match retry_after {
+            Some(wait) if self.policy.budget.is_some() => wait,
+            Some(wait) => wait.min(self.policy.max_delay),
+            None => self.policy.backoff(self.attempt),
+        };
+        let over_budget = self
+            .policy
+            .budget
+            .is_some_and(|budget| self.started.elapsed() + delay > budget);
+        if self.attempt >= self.max_attempts || over_budget {
+            *self
+                .policy
+                .failures
+                .lock()
+                .unwrap()
+                .entry(self.key.clone())
+                .or_default() += 1;
+            return false;
+        }
+        self.policy.events.lock().unwrap().push(json!({
+            "type": "request.retry",
+            "target": self.target,
+            "key": self.key,
+            "attempt": self.attempt,
+            "max_attempts": self.max_attempts,
+            "delay_ms": delay.as_millis() as u64,
+            "reason": reason,
+        }));
+        thread::sleep(delay);
+        self.attempt += 1;
+        true
+    }
+
+    /// Closes the target's circuit again.
+    pub(crate) fn succeeded(&self) {
+        self.policy.failures.lock().unwrap().remove(&self.key);
+    }
+}
+
+const IMF_FIXDATE: &[FormatItem<'_>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+const ASCTIME: &[FormatItem<'_>] = format_description!(
+    "[weekday repr:short] [month repr:short] [day padding:space] [hour]:[minute]:[second] [year]"
+);
+
+/// Parses `Retry-After` as delay-seconds or an HTTP-date (IMF-fixdate or asctime). Dates in
+/// the past mean no wait.
+pub(crate) fn parse_retry_after(value: &HeaderValue) -> Option<Duration> {
+    let raw = value.to_str().ok()?.trim();
+    if let Ok(secs) = raw.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = PrimitiveDateTime::parse(raw, IMF_FIXDATE)
+        .or_else(|_| PrimitiveDateTime::parse(raw, ASCTIME))
+        .ok()?
+        .assume_utc();
+    let wait = date - OffsetDateTime::now_utc();
+    Some(wait.try_into().unwrap_or(Duration::ZERO))
+}
+
+/// Cheap jitter source; the low bits of the clock are random enough to spread retries.
+fn random_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    f64::from(nanos % 1000) / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_retry_after, RetryPolicy};
+    use reqwest::header::HeaderValue;
+    use std::time::Duration;
+    use time::format_description::well_known::Rfc2822;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn parses_retry_after_seconds_and_http_dates() {
+        let parse = |raw: &str| parse_retry_after(&HeaderValue::from_str(raw).unwrap());
+        assert_eq!(parse("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994"), Some(Duration::ZERO));
+        assert_eq!(parse("soon"), None);
+
+        let later = OffsetDateTime::now_utc() + time::Duration::seconds(30);
+        let raw = later.format(&Rfc2822).unwrap().replace("+0000", "GMT");
+        let wait = parse(&raw).expect("future date");
+        assert!(
+            wait > Duration::from_secs(25) && wait <= Duration::from_secs(30),
+            "{wait:?}"
+        );
+    }
+
+    #[test]
+    fn stops_at_attempt_limit_and_opens_circuit() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+            breaker_threshold: 2,
+            ..RetryPolicy::default()
+        };
+        for _ in 0..2 {
+            let mut retry = policy.start("model", "m");
+            assert!(retry.wait(None, "HTTP 503"));
+            assert!(retry.wait(Some(Duration::ZERO), "HTTP 503"));
+            assert_eq!(retry.attempt(), 3);
+            assert!(!retry.wait(None, "HTTP 503"));
+        }
+        let events = policy.take_events();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[1]["attempt"], 2);
+        assert_eq!(events[1]["delay_ms"], 0);
+
+        // Two exhausted requests in a row: the next one gets a single attempt.
+        let retry = policy.start("model", "m");
+        assert_eq!(retry.max_attempts(), 1);
+        retry.succeeded();
+        assert_eq!(policy.start("model", "m").max_attempts(), 3);
+        assert_eq!(policy.start("model", "other").max_attempts(), 3);
+
+        let budgeted = RetryPolicy {
+            budget: Some(Duration::from_secs(1)),
+            ..RetryPolicy::default()
+        };
+        let mut retry = budgeted.start("web_fetch", "example.com");
+        assert!(!retry.wait(Some(Duration::from_secs(5)), "HTTP 429"));
+        assert!(budgeted.take_events().is_empty());
+    }
+
+    #[test]
+    fn long_retry_after_is_capped_without_a_budget() {
+        let policy = RetryPolicy::default();
+        let mut retry = policy.start("model", "m");
+        assert!(retry.wait(Some(Duration::from_secs(3600)), "HTTP 429"));
+        let events = policy.take_events();
+        assert_eq!(events[0]["delay_ms"], 4000);
+
+        // With a budget the server's wait is honored, so one longer than the budget gives up.
+        let policy = RetryPolicy {
+            budget: Some(Duration::from_secs(60)),
+            ..RetryPolicy::default()
+        };
+        let mut retry = policy.start("model", "m");
+        assert!(!retry.wait(Some(Duration::from_secs(3600)), "HTTP 429"));
+        assert!(policy.take_events().is_empty());
+    }
+}
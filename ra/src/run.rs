@@ -1,9 +1,16 @@
 use crate::agent::Agent;
 use crate::cli::Args;
-use crate::constants::DEFAULT_MAX_TOOL_OUTPUT_CHARS;
+use crate::constants::{DEFAULT_MAX_TOOL_OUTPUT_CHARS, DEFAULT_PATCH_MAX_OFFSET};
+use crate::context::context_window_for_model;
+use crate::grade::Grader;
 use crate::logger::Logger;
+use crate::pricing::Pricing;
 use crate::prompt::load_task;
-use crate::tools::{build_tools, resolve_path};
+use crate::provider::SamplingParams;
+use crate::resume::load_resume_state;
+use crate::retry::RetryPolicy;
+use crate::sandbox::Sandbox;
+use crate::tools::{launch_mcp_servers, load_custom_tools, resolve_path, ToolRegistry};
 use anyhow::{bail, Result};
 use reqwest::blocking::Client;
 use std::env;
@@ -15,8 +22,14 @@ use time::OffsetDateTime;
 use uuid::Uuid;
 
 pub(crate) fn run_prompt(args: &Args, cwd: &Path, api_key: &str) -> Result<String> {
-    let session_id = Uuid::new_v4().to_string();
-    let logger = {
+    let resume_state = args.resume.as_deref().map(load_resume_state).transpose()?;
+    let session_id = resume_state
+        .as_ref()
+        .map(|state| state.thread_id.clone())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let logger = if let Some(path) = &args.resume {
+        Logger::new(Some(path.clone()), true, args.stream_json, args.json)?
+    } else {
         let log_path = if let Some(path) = &args.log_path {
             resolve_path(cwd, path)
         } else {
@@ -34,13 +47,14 @@ pub(crate) fn run_prompt(args: &Args, cwd: &Path, api_key: &str) -> Result<Strin
             let filename = format!("ra-{}-{}.jsonl", safe_ts, session_id);
             log_dir.join(filename)
         };
-        Logger::new(Some(log_path), args.stream_json, args.json)?
+        Logger::new(Some(log_path), false, args.stream_json, args.json)?
     };
     let logger_for_output = logger.clone();
 
     // UX default:
     // - `ra "PROMPT"` behaves like a normal CLI by default (no submit; exit on first assistant reply).
     // - `ra --prompt-file file.txt` runs in agent mode by default (submit-enabled; continues until submit).
+    // - `ra --resume log.jsonl` keeps the mode recorded in the log.
     // Explicit overrides: --exec / --no-submit.
     if args.exec && args.no_submit {
         bail!("--exec and --no-submit cannot both be set");
@@ -49,11 +63,33 @@ pub(crate) fn run_prompt(args: &Args, cwd: &Path, api_key: &str) -> Result<Strin
         true
     } else if args.no_submit {
         false
+    } else if let Some(state) = &resume_state {
+        state.submit_enabled
     } else {
         args.prompt_file.is_some()
     };
 
-    if args.web_search {
+    let sampling = SamplingParams::from_args(args)?;
+    let grader = args.grader.as_deref().map(Grader::parse).transpose()?;
+    let pricing = args
+        .pricing
+        .as_ref()
+        .map(|path| Pricing::load(&resolve_path(cwd, path)))
+        .transpose()?;
+    let mut external_tools = match &args.custom_tools {
+        Some(path) => load_custom_tools(&resolve_path(cwd, path))?,
+        None => Vec::new(),
+    };
+    external_tools.extend(launch_mcp_servers(&args.mcp_servers, cwd)?);
+    let registry = ToolRegistry::resolve(
+        args.tool_profile,
+        args.tools.as_deref(),
+        submit_enabled,
+        args.web_search,
+        args.shell_session,
+        external_tools,
+    )?;
+    if registry.contains("web_search") {
         let has_tavily_key = env::var("RA_TAVILY_API_KEY")
             .ok()
             .filter(|k| !k.trim().is_empty())
@@ -64,12 +100,17 @@ pub(crate) fn run_prompt(args: &Args, cwd: &Path, api_key: &str) -> Result<Strin
                 .is_some();
         if !has_tavily_key {
             bail!(
-                "--search is enabled but no Tavily API key was found. Set TAVILY_API_KEY (or RA_TAVILY_API_KEY)."
+                "web_search is enabled but no Tavily API key was found. Set TAVILY_API_KEY (or RA_TAVILY_API_KEY)."
             );
         }
     }
 
-    let tools = build_tools(submit_enabled, args.web_search);
+    let sandbox = Sandbox::new(
+        args.sandbox,
+        cwd.to_path_buf(),
+        !args.sandbox_no_network,
+        args.sandbox_container.clone(),
+    )?;
     // Sane defaults:
     // - explicit connect timeout so we fail fast on network issues
     // - generous overall request timeout so slow generations don't hang forever
@@ -79,25 +120,52 @@ pub(crate) fn run_prompt(args: &Args, cwd: &Path, api_key: &str) -> Result<Strin
         .build()?;
     let mut agent = Agent::new(
         client,
+        args.provider.build(args)?,
         args.base_url.clone(),
         args.model.clone(),
+        args.fallback_models.clone(),
+        args.fallback_sticky,
         api_key.to_string(),
         session_id,
-        tools,
-        args.temperature,
+        registry,
+        sampling,
+        args.stream,
         args.max_steps,
         args.time_limit_sec.map(Duration::from_secs),
+        args.max_cost,
+        args.max_total_tokens,
+        pricing,
         args.max_tool_output_chars
             .unwrap_or(DEFAULT_MAX_TOOL_OUTPUT_CHARS),
+        args.patch_max_offset.unwrap_or(DEFAULT_PATCH_MAX_OFFSET),
+        // The built-in window only turns on compaction ahead of requests once a strategy is
+        // chosen; otherwise history is pruned only when the provider reports an overflow.
+        args.context_window.or_else(|| {
+            args.context_strategy
+                .and_then(|_| context_window_for_model(&args.model))
+        }),
+        args.context_strategy.unwrap_or_default(),
+        args.parallel_tools,
+        sandbox,
         cwd.to_path_buf(),
         submit_enabled,
-        args.web_search,
         args.retry_429,
+        RetryPolicy::from_args(args)?,
+        args.redact_reasoning,
         logger,
     );
 
-    let prompt = load_task(args)?;
-    match agent.run(prompt) {
+    let result = match resume_state {
+        Some(state) => agent.resume(state),
+        None => agent.run(load_task(args)?),
+    }
+    .and_then(|answer| {
+        if let Some(grader) = &grader {
+            agent.grade(grader, &answer)?;
+        }
+        Ok(answer)
+    });
+    match result {
         Ok(answer) => {
             if args.json {
                 logger_for_output.emit_buffer_to_stdout()?;
@@ -122,10 +190,15 @@ pub(crate) fn resolve_and_validate_cwd(args: &Args) -> Result<std::path::PathBuf
 mod tests {
     use super::run_prompt;
     use crate::cli::Args;
+    use crate::context::{ContextStrategy, SUMMARY_MESSAGE_PREFIX, SUMMARY_SYSTEM_PROMPT};
+    use crate::provider::ProviderKind;
+    use crate::resume::load_resume_state;
+    use crate::sandbox::SandboxKind;
+    use crate::tools::ToolProfile;
     use serde_json::Value;
     use std::fs;
     use std::io::{Read, Write};
-    use std::net::TcpListener;
+    use std::net::{TcpListener, TcpStream};
     use std::path::PathBuf;
     use std::sync::Mutex;
     use std::thread;
@@ -134,6 +207,98 @@ mod tests {
 
     static ENV_LOCK: Mutex<()> = Mutex::new(());
 
+    fn test_args(base_url: String, log_path: PathBuf) -> Args {
+        Args {
+            model: "openai/gpt-4.1-mini".to_string(),
+            fallback_models: Vec::new(),
+            fallback_sticky: false,
+            profile: None,
+            prompt_file: None,
+            resume: None,
+            cwd: PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+            api_key: Some("test-key".to_string()),
+            base_url,
+            provider: ProviderKind::OpenaiChat,
+            mock_script: None,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            seed: None,
+            stop: Vec::new(),
+            reasoning_effort: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            extra_body: None,
+            stream: false,
+            max_steps: Some(1),
+            time_limit_sec: None,
+            max_cost: None,
+            max_total_tokens: None,
+            pricing: None,
+            log_dir: None,
+            log_path: Some(log_path),
+            json: false,
+            stream_json: false,
+            redact_reasoning: false,
+            grader: None,
+            max_tool_output_chars: None,
+            patch_max_offset: None,
+            context_window: None,
+            context_strategy: None,
+            parallel_tools: false,
+            shell_session: false,
+            custom_tools: None,
+            mcp_servers: Vec::new(),
+            tool_profile: ToolProfile::Default,
+            tools: None,
+            sandbox: SandboxKind::None,
+            sandbox_no_network: false,
+            sandbox_container: None,
+            exec: false,
+            no_submit: true,
+            retry_429: false,
+            retry_max_attempts: None,
+            retry_base_delay_ms: None,
+            retry_max_delay_ms: None,
+            retry_jitter: None,
+            retry_budget_sec: None,
+            retry_breaker: None,
+            web_search: false,
+            prompt: Some("hi".to_string()),
+            command: None,
+        }
+    }
+
+    /// Reads one HTTP request (head and Content-Length body) and returns the body.
+    fn read_request_body(stream: &mut TcpStream) -> String {
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        let mut buf = Vec::new();
+        let mut tmp = [0u8; 4096];
+        let mut body_start = None;
+        loop {
+            if let Some(start) = body_start {
+                let head = String::from_utf8_lossy(&buf[..start]).to_lowercase();
+                let len = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .and_then(|v| v.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if buf.len() >= start + len {
+                    return String::from_utf8_lossy(&buf[start..start + len]).to_string();
+                }
+            }
+            match stream.read(&mut tmp) {
+                Ok(0) | Err(_) => return String::new(),
+                Ok(n) => {
+                    buf.extend_from_slice(&tmp[..n]);
+                    if body_start.is_none() {
+                        body_start = buf.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4);
+                    }
+                }
+            }
+        }
+    }
+
     fn assert_obj_has<'a>(obj: &'a serde_json::Map<String, Value>, key: &str) -> &'a Value {
         obj.get(key)
             .unwrap_or_else(|| panic!("missing key `{}`", key))
@@ -192,26 +357,7 @@ mod tests {
         let _ = fs::remove_file(&log_path);
         fs::create_dir_all(log_path.parent().unwrap()).expect("create log dir");
 
-        let args = Args {
-            model: "openai/gpt-4.1-mini".to_string(),
-            prompt_file: None,
-            cwd: cwd.clone(),
-            api_key: Some("test-key".to_string()),
-            base_url,
-            temperature: None,
-            max_steps: Some(1),
-            time_limit_sec: None,
-            log_dir: None,
-            log_path: Some(log_path.clone()),
-            json: false,
-            stream_json: false,
-            max_tool_output_chars: None,
-            exec: false,
-            no_submit: true,
-            retry_429: false,
-            web_search: false,
-            prompt: Some("hi".to_string()),
-        };
+        let args = test_args(base_url, log_path.clone());
 
         let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
         assert_eq!(answer, "ok");
@@ -257,6 +403,7 @@ mod tests {
         );
         assert_obj_has(&obj, "prompt");
         assert_obj_has(&obj, "system_prompt");
+        assert_eq!(obj.get("sampling"), Some(&serde_json::json!({})));
 
         let turn_completed: Value = serde_json::from_str(lines[lines.len() - 1]).unwrap();
         let Value::Object(obj) = turn_completed else {
@@ -273,6 +420,7 @@ mod tests {
         assert_obj_has(u, "input_tokens");
         assert_obj_has(u, "cached_input_tokens");
         assert_obj_has(u, "output_tokens");
+        assert_obj_has(u, "reasoning_output_tokens");
 
         let _ = fs::remove_file(&log_path);
     }
@@ -340,26 +488,7 @@ mod tests {
         let _ = fs::remove_file(&log_path);
         fs::create_dir_all(log_path.parent().unwrap()).expect("create log dir");
 
-        let args = Args {
-            model: "openai/gpt-4.1-mini".to_string(),
-            prompt_file: None,
-            cwd: cwd.clone(),
-            api_key: Some("test-key".to_string()),
-            base_url,
-            temperature: None,
-            max_steps: Some(1),
-            time_limit_sec: None,
-            log_dir: None,
-            log_path: Some(log_path.clone()),
-            json: false,
-            stream_json: false,
-            max_tool_output_chars: None,
-            exec: false,
-            no_submit: true,
-            retry_429: false,
-            web_search: false,
-            prompt: Some("hi".to_string()),
-        };
+        let args = test_args(base_url, log_path.clone());
 
         let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
         assert_eq!(answer, "ok");
@@ -431,26 +560,8 @@ mod tests {
         let _ = fs::remove_file(&log_path);
         fs::create_dir_all(log_path.parent().unwrap()).expect("create log dir");
 
-        let args = Args {
-            model: "openai/gpt-4.1-mini".to_string(),
-            prompt_file: None,
-            cwd: cwd.clone(),
-            api_key: Some("test-key".to_string()),
-            base_url,
-            temperature: None,
-            max_steps: Some(1),
-            time_limit_sec: None,
-            log_dir: None,
-            log_path: Some(log_path.clone()),
-            json: false,
-            stream_json: false,
-            max_tool_output_chars: None,
-            exec: false,
-            no_submit: true,
-            retry_429: true,
-            web_search: false,
-            prompt: Some("hi".to_string()),
-        };
+        let mut args = test_args(base_url, log_path.clone());
+        args.retry_429 = true;
 
         let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
         assert_eq!(answer, "ok");
@@ -676,29 +787,15 @@ mod tests {
         let _ = fs::remove_file(&log_path);
         fs::create_dir_all(log_path.parent().unwrap()).expect("create log dir");
 
-        let args = Args {
-            model: "openai/gpt-4.1-mini".to_string(),
-            prompt_file: None,
-            cwd: cwd.clone(),
-            api_key: Some("test-key".to_string()),
-            base_url,
-            temperature: None,
-            max_steps: Some(10),
-            time_limit_sec: None,
-            log_dir: None,
-            log_path: Some(log_path.clone()),
-            json: false,
-            stream_json: false,
-            max_tool_output_chars: None,
-            exec: true,
-            no_submit: false,
-            retry_429: false,
-            web_search: true,
-            prompt: Some(
-                "Search for 'Rust 1.75 release notes', open the official blog link, then find 'stabilized' and cite the line ranges."
-                    .to_string(),
-            ),
-        };
+        let mut args = test_args(base_url, log_path.clone());
+        args.max_steps = Some(10);
+        args.exec = true;
+        args.no_submit = false;
+        args.web_search = true;
+        args.prompt = Some(
+            "Search for 'Rust 1.75 release notes', open the official blog link, then find 'stabilized' and cite the line ranges."
+                .to_string(),
+        );
 
         let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
         assert_eq!(answer, "ok");
@@ -708,4 +805,791 @@ mod tests {
         page_thread.join().expect("page join");
         let _ = fs::remove_file(&log_path);
     }
+
+    #[test]
+    fn streams_and_retries_interrupted_event_stream() {
+        // The first stream is cut off before [DONE]; the retry delivers a complete stream.
+        let listener = match TcpListener::bind("127.0.0.1:0") {
+            Ok(l) => l,
+            Err(err) => {
+                eprintln!(
+                    "skipping streams_and_retries_interrupted_event_stream: bind failed: {err}"
+                );
+                return;
+            }
+        };
+        let addr = listener.local_addr().expect("local_addr");
+        let base_url = format!("http://{}", addr);
+
+        let server_thread = thread::spawn(move || {
+            let mut request_bodies = Vec::new();
+            for i in 0..2 {
+                let (mut stream, _) = listener.accept().expect("accept");
+                request_bodies.push(read_request_body(&mut stream));
+                let events = if i == 0 {
+                    "data: {\"choices\":[{\"delta\":{\"content\":\"o\"}}]}\n\n".to_string()
+                } else {
+                    [
+                        "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"o\"}}]}\n\n",
+                        "data: {\"choices\":[{\"delta\":{\"content\":\"k\"},\"finish_reason\":\"stop\"}]}\n\n",
+                        "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":1,\"completion_tokens\":1,\"total_tokens\":2}}\n\n",
+                        "data: [DONE]\n\n",
+                    ]
+                    .concat()
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+                    events
+                );
+                stream.write_all(response.as_bytes()).expect("write stream");
+                let _ = stream.flush();
+            }
+            request_bodies
+        });
+
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let log_path = cwd
+            .join("target")
+            .join(format!("stream-test-{}.jsonl", Uuid::new_v4()));
+        let _ = fs::remove_file(&log_path);
+        fs::create_dir_all(log_path.parent().unwrap()).expect("create log dir");
+
+        let mut args = test_args(base_url, log_path.clone());
+        args.stream = true;
+
+        let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
+        assert_eq!(answer, "ok");
+
+        let request_bodies = server_thread.join().expect("server join");
+        let request: Value = serde_json::from_str(&request_bodies[1]).expect("request json");
+        assert_eq!(request.get("stream"), Some(&Value::Bool(true)));
+
+        let contents = fs::read_to_string(&log_path).expect("read log");
+        let events: Vec<Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("json line"))
+            .collect();
+        let last_update = events
+            .iter()
+            .rev()
+            .find(|e| e["type"] == "item.updated")
+            .expect("item.updated event");
+        assert_eq!(last_update["item"]["text"], "ok");
+        let messages: Vec<&Value> = events
+            .iter()
+            .filter(|e| e["type"] == "item.completed" && e["item"]["type"] == "agent_message")
+            .collect();
+        assert_eq!(messages.len(), 2);
+        // The interrupted attempt's item is closed before the retry starts a new one.
+        assert_eq!(messages[0]["item"]["status"], "failed");
+        assert_eq!(messages[0]["item"]["text"], "o");
+        assert_ne!(messages[0]["item"]["id"], last_update["item"]["id"]);
+        assert_eq!(messages[1]["item"]["id"], last_update["item"]["id"]);
+        assert_eq!(messages[1]["item"]["text"], "ok");
+
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn resumes_run_from_jsonl_log() {
+        let listener = match TcpListener::bind("127.0.0.1:0") {
+            Ok(l) => l,
+            Err(err) => {
+                eprintln!("skipping resumes_run_from_jsonl_log: bind failed: {err}");
+                return;
+            }
+        };
+        let addr = listener.local_addr().expect("local_addr");
+        let base_url = format!("http://{}", addr);
+
+        let server_thread = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let request = read_request_body(&mut stream);
+            let body = serde_json::json!({
+                "choices": [{
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call-2",
+                            "type": "function",
+                            "function": {"name": "submit", "arguments": "{\"answer\":\"done\"}"}
+                        }]
+                    }
+                }]
+            })
+            .to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).expect("write");
+            let _ = stream.flush();
+            request
+        });
+
+        // A log from a run that died while its first tool call was executing.
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let log_path = cwd
+            .join("target")
+            .join(format!("resume-test-{}.jsonl", Uuid::new_v4()));
+        fs::create_dir_all(log_path.parent().unwrap()).expect("create log dir");
+        let prior = [
+            serde_json::json!({"type": "thread.started", "thread_id": "thread-abc", "timestamp_ms": 1}),
+            serde_json::json!({"type": "turn.started", "prompt": "do it", "system_prompt": "sys", "submit_enabled": true, "timestamp_ms": 1}),
+            serde_json::json!({"type": "message.appended", "message": {"role": "assistant", "content": null, "tool_calls": [{"id": "call-1", "type": "function", "function": {"name": "shell_command", "arguments": "{\"command\":\"sleep 1000\"}"}}]}, "timestamp_ms": 2}),
+        ];
+        let prior: Vec<String> = prior.iter().map(|e| e.to_string() + "\n").collect();
+        fs::write(&log_path, prior.concat()).expect("write log");
+
+        let mut args = test_args(base_url, log_path.clone());
+        args.log_path = None;
+        args.prompt = None;
+        args.no_submit = false;
+        args.max_steps = Some(2);
+        args.resume = Some(log_path.clone());
+
+        let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
+        assert_eq!(answer, "done");
+
+        let request: Value =
+            serde_json::from_str(&server_thread.join().expect("server join")).expect("json");
+        let messages = request["messages"].as_array().expect("messages");
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["content"], "sys");
+        assert_eq!(messages[1]["content"], "do it");
+        assert_eq!(messages[2]["tool_calls"][0]["id"], "call-1");
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages[3]["tool_call_id"], "call-1");
+
+        let contents = fs::read_to_string(&log_path).expect("read log");
+        let events: Vec<Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("json line"))
+            .collect();
+        let resumed = events
+            .iter()
+            .find(|e| e["type"] == "thread.resumed")
+            .expect("thread.resumed");
+        assert_eq!(resumed["thread_id"], "thread-abc");
+        assert_eq!(resumed["steps_completed"], 1);
+        assert!(events
+            .iter()
+            .any(|e| e["type"] == "message.appended"
+                && e["message"]["tool_calls"][0]["id"] == "call-2"));
+
+        let _ = fs::remove_file(&log_path);
+    }
+
+    /// Serves `responses` in order on a local listener, returning the request bodies received.
+    fn serve_responses(
+        listener: TcpListener,
+        content_type: &'static str,
+        responses: Vec<String>,
+    ) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for body in responses {
+                let (mut stream, _) = listener.accept().expect("accept");
+                bodies.push(read_request_body(&mut stream));
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).expect("write");
+                let _ = stream.flush();
+            }
+            bodies
+        })
+    }
+
+    #[test]
+    fn anthropic_provider_tool_flow_with_stub() {
+        let listener = match TcpListener::bind("127.0.0.1:0") {
+            Ok(l) => l,
+            Err(err) => {
+                eprintln!("skipping anthropic_provider_tool_flow_with_stub: bind failed: {err}");
+                return;
+            }
+        };
+        let base_url = format!("http://{}", listener.local_addr().expect("local_addr"));
+        let responses = vec![
+            serde_json::json!({
+                "type": "message",
+                "role": "assistant",
+                "content": [
+                    {"type": "text", "text": "Reading."},
+                    {"type": "tool_use", "id": "toolu_1", "name": "list_dir", "input": {"dir_path": "src", "offset": null, "limit": null, "depth": null}}
+                ],
+                "usage": {"input_tokens": 10, "output_tokens": 5}
+            })
+            .to_string(),
+            serde_json::json!({
+                "type": "message",
+                "role": "assistant",
+                "content": [
+                    {"type": "tool_use", "id": "toolu_2", "name": "submit", "input": {"answer": "ok"}}
+                ],
+                "usage": {"input_tokens": 20, "output_tokens": 5}
+            })
+            .to_string(),
+        ];
+        let server_thread = serve_responses(listener, "application/json", responses);
+
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let log_path = cwd
+            .join("target")
+            .join(format!("anthropic-test-{}.jsonl", Uuid::new_v4()));
+        let mut args = test_args(base_url, log_path.clone());
+        args.provider = ProviderKind::Anthropic;
+        args.exec = true;
+        args.no_submit = false;
+        args.max_steps = Some(5);
+
+        let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
+        assert_eq!(answer, "ok");
+
+        let bodies = server_thread.join().expect("server join");
+        let second: Value = serde_json::from_str(&bodies[1]).expect("request json");
+        assert!(second["system"].as_str().is_some());
+        let messages = second["messages"].as_array().expect("messages");
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_1");
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn responses_provider_streaming_tool_flow_with_stub() {
+        let listener = match TcpListener::bind("127.0.0.1:0") {
+            Ok(l) => l,
+            Err(err) => {
+                eprintln!(
+                    "skipping responses_provider_streaming_tool_flow_with_stub: bind failed: {err}"
+                );
+                return;
+            }
+        };
+        let base_url = format!("http://{}", listener.local_addr().expect("local_addr"));
+        let completed = |output: Value| {
+            let event = serde_json::json!({
+                "type": "response.completed",
+                "response": {
+                    "output": output,
+                    "usage": {"input_tokens": 4, "output_tokens": 2, "total_tokens": 6}
+                }
+            });
+            format!("event: response.completed\ndata: {}\n\n", event)
+        };
+        let responses = vec![
+            completed(serde_json::json!([
+                {"type": "function_call", "call_id": "fc_1", "name": "list_dir", "arguments": "{\"dir_path\":\"src\",\"offset\":null,\"limit\":null,\"depth\":null}"}
+            ])),
+            format!(
+                "data: {}\n\n{}",
+                serde_json::json!({"type": "response.output_text.delta", "delta": "ok"}),
+                completed(serde_json::json!([
+                    {"type": "message", "role": "assistant", "content": [{"type": "output_text", "text": "ok"}]}
+                ]))
+            ),
+        ];
+        let server_thread = serve_responses(listener, "text/event-stream", responses);
+
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let log_path = cwd
+            .join("target")
+            .join(format!("responses-test-{}.jsonl", Uuid::new_v4()));
+        let mut args = test_args(base_url, log_path.clone());
+        args.provider = ProviderKind::Responses;
+        args.stream = true;
+        args.max_steps = Some(5);
+
+        let answer = run_prompt(&args, &cwd, "test-key").expect("run_prompt");
+        assert_eq!(answer, "ok");
+
+        let bodies = server_thread.join().expect("server join");
+        let second: Value = serde_json::from_str(&bodies[1]).expect("request json");
+        assert_eq!(second["parallel_tool_calls"], false);
+        let input = second["input"].as_array().expect("input");
+        assert_eq!(input[1]["type"], "function_call");
+        assert_eq!(input[2]["type"], "function_call_output");
+        assert_eq!(input[2]["call_id"], "fc_1");
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn mock_provider_runs_script_offline() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-script-{}.jsonl", Uuid::new_v4()));
+        let log_path = dir.join(format!("mock-test-{}.jsonl", Uuid::new_v4()));
+        let script = [
+            serde_json::json!({
+                "expect": {"model": "test-model", "messages": [{"role": "system"}, {"role": "user", "content": "hi"}]},
+                "message": {"content": "Looking.", "tool_calls": [
+                    {"function": {"name": "list_dir", "arguments": {"dir_path": "src", "offset": null, "limit": null, "depth": null}}}
+                ]},
+                "usage": {"prompt_tokens": 10, "completion_tokens": 3, "total_tokens": 13}
+            }),
+            serde_json::json!({
+                "expect_last_message": {"role": "tool", "tool_call_id": "mock_call_1"},
+                "message": {"tool_calls": [
+                    {"function": {"name": "list_dir", "arguments": {"dir_path": "tests", "offset": null, "limit": null, "depth": null}}}
+                ]}
+            }),
+            // Overflow: the agent prunes the oldest tool pair and asks again.
+            serde_json::json!({"error": "This model's maximum context length is 8192 tokens"}),
+            serde_json::json!({
+                "expect": {"messages": [{"role": "system"}, {"role": "user"}, {"role": "assistant", "tool_calls": [{"id": "mock_call_2"}]}]},
+                "expect_last_message": {"role": "tool", "tool_call_id": "mock_call_2"},
+                "message": {"tool_calls": [{"function": {"name": "submit", "arguments": {"answer": "done"}}}]}
+            }),
+        ];
+        let lines: Vec<String> = script.iter().map(|v| v.to_string()).collect();
+        fs::write(&script_path, lines.join("\n")).expect("write script");
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.model = "test-model".to_string();
+        args.exec = true;
+        args.no_submit = false;
+        args.max_steps = Some(5);
+
+        let answer = run_prompt(&args, &cwd, "").expect("run_prompt");
+        assert_eq!(answer, "done");
+
+        let contents = fs::read_to_string(&log_path).expect("read log");
+        let events: Vec<Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("json"))
+            .collect();
+        assert!(events.iter().any(|e| e["type"] == "item.completed"
+            && e["item"]["type"] == "command_execution"
+            && e["item"]["status"] == "completed"));
+        let last = events.last().expect("events");
+        assert_eq!(last["type"], "turn.completed");
+        assert_eq!(last["usage"]["input_tokens"], 10);
+
+        // A script that disagrees with the request fails the run.
+        let bad = serde_json::json!({"expect": {"model": "other"}, "message": {"content": "x"}});
+        fs::write(&script_path, bad.to_string()).expect("write script");
+        let _ = fs::remove_file(&log_path);
+        let err = run_prompt(&args, &cwd, "").expect_err("mismatch should fail");
+        assert!(err.to_string().contains("at /model"), "{err}");
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn captures_and_replays_reasoning() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-reasoning-{}.jsonl", Uuid::new_v4()));
+        let log_path = dir.join(format!("mock-reasoning-{}.jsonl", Uuid::new_v4()));
+        let details = serde_json::json!([{"type": "reasoning.text", "text": "List src first."}]);
+        let script = [
+            serde_json::json!({
+                "message": {"reasoning": "List src first.", "reasoning_details": details, "tool_calls": [
+                    {"function": {"name": "list_dir", "arguments": {"dir_path": "src", "offset": null, "limit": null, "depth": null}}}
+                ]},
+                "usage": {"prompt_tokens": 10, "completion_tokens": 20, "total_tokens": 30}
+            }),
+            serde_json::json!({
+                "expect": {"messages": [{}, {}, {"role": "assistant", "reasoning": "List src first.", "reasoning_details": details}]},
+                "message": {"content": "done"}
+            }),
+        ];
+        let lines: Vec<String> = script.iter().map(|v| v.to_string()).collect();
+        fs::write(&script_path, lines.join("\n")).expect("write script");
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.max_steps = Some(5);
+
+        for redact in [false, true] {
+            args.redact_reasoning = redact;
+            let _ = fs::remove_file(&log_path);
+            let answer = run_prompt(&args, &cwd, "").expect("run_prompt");
+            assert_eq!(answer, "done");
+
+            let contents = fs::read_to_string(&log_path).expect("read log");
+            let events: Vec<Value> = contents
+                .lines()
+                .map(|l| serde_json::from_str(l).expect("json"))
+                .collect();
+            let reasoning = events
+                .iter()
+                .find(|e| e["type"] == "item.completed" && e["item"]["type"] == "reasoning")
+                .expect("reasoning item");
+            let appended = events
+                .iter()
+                .find(|e| e["type"] == "message.appended")
+                .expect("message.appended");
+            if redact {
+                assert_eq!(reasoning["item"]["text"], "[redacted]");
+                assert!(appended["message"].get("reasoning").is_none());
+                assert!(!contents.contains("List src first."));
+            } else {
+                assert_eq!(reasoning["item"]["text"], "List src first.");
+                assert_eq!(appended["message"]["reasoning_details"], details);
+            }
+            // No reasoning count in the usage, so it is estimated from the captured text.
+            let last = events.last().expect("events");
+            assert_eq!(last["usage"]["reasoning_output_tokens"], 4);
+        }
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn stops_when_cost_or_token_budget_runs_out() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-budget-{}.jsonl", Uuid::new_v4()));
+        let pricing_path = dir.join(format!("pricing-{}.toml", Uuid::new_v4()));
+        let log_path = dir.join(format!("mock-budget-{}.jsonl", Uuid::new_v4()));
+        let step = |cost: Option<f64>| {
+            let mut usage = serde_json::json!({"prompt_tokens": 1000, "completion_tokens": 500, "total_tokens": 1500});
+            if let Some(cost) = cost {
+                usage["cost"] = serde_json::json!(cost);
+            }
+            serde_json::json!({
+                "message": {"tool_calls": [
+                    {"function": {"name": "list_dir", "arguments": {"dir_path": "src", "offset": null, "limit": null, "depth": null}}}
+                ]},
+                "usage": usage
+            })
+            .to_string()
+        };
+        fs::write(
+            &pricing_path,
+            "[models.test-model]\ninput = 10.0\noutput = 20.0\n",
+        )
+        .expect("write pricing");
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.model = "vendor/test-model".to_string();
+        args.exec = true;
+        args.no_submit = false;
+        args.max_steps = Some(10);
+        let run = |args: &Args, script: &[String]| -> Vec<Value> {
+            fs::write(&script_path, script.join("\n")).expect("write script");
+            let _ = fs::remove_file(&log_path);
+            run_prompt(args, &cwd, "").expect("run_prompt");
+            fs::read_to_string(&log_path)
+                .expect("read log")
+                .lines()
+                .map(|l| serde_json::from_str(l).expect("json"))
+                .collect()
+        };
+        let warning = |events: &[Value]| -> String {
+            events
+                .iter()
+                .rev()
+                .find(|e| e["type"] == "item.completed" && e["item"]["type"] == "error")
+                .map(|e| e["item"]["message"].as_str().unwrap_or("").to_string())
+                .unwrap_or_default()
+        };
+
+        // Reported costs: 0.02 + 0.02 passes $0.03, so the third step never starts.
+        args.max_cost = Some(0.03);
+        let events = run(&args, &[step(Some(0.02)), step(Some(0.02))]);
+        assert!(warning(&events).starts_with("Terminated: max_cost ($0.03) reached"));
+        let updates: Vec<&Value> = events
+            .iter()
+            .filter(|e| e["type"] == "usage.updated")
+            .collect();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[1]["total_cost_usd"], 0.04);
+        assert_eq!(events.last().expect("events")["usage"]["cost_usd"], 0.04);
+
+        // Priced locally: 1000 * $10/M + 500 * $20/M = $0.02 per step.
+        args.pricing = Some(pricing_path.clone());
+        let events = run(&args, &[step(None), step(None)]);
+        assert!(warning(&events).starts_with("Terminated: max_cost"));
+        assert_eq!(events.last().expect("events")["usage"]["cost_usd"], 0.04);
+
+        args.max_cost = None;
+        args.pricing = None;
+        args.max_total_tokens = Some(2000);
+        let events = run(&args, &[step(None), step(None)]);
+        assert_eq!(
+            warning(&events),
+            "Terminated: max_total_tokens (2000) reached."
+        );
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&pricing_path);
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn falls_back_to_next_model_when_unavailable() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-fallback-{}.jsonl", Uuid::new_v4()));
+        let log_path = dir.join(format!("mock-fallback-{}.jsonl", Uuid::new_v4()));
+        let script = |second_step_model: &str| {
+            [
+                serde_json::json!({"expect": {"model": "primary"}, "error": "overloaded", "status": 503}),
+                serde_json::json!({"expect": {"model": "fb-a"}, "error": "No endpoints found", "status": 404}),
+                serde_json::json!({"expect": {"model": "fb-b"}, "message": {"tool_calls": [
+                    {"function": {"name": "list_dir", "arguments": {"dir_path": "src", "offset": null, "limit": null, "depth": null}}}
+                ]}}),
+                serde_json::json!({"expect": {"model": second_step_model}, "message": {"content": "done"}}),
+            ]
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+        };
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.model = "primary".to_string();
+        args.fallback_models = vec!["fb-a".to_string(), "fb-b".to_string()];
+        args.max_steps = Some(5);
+
+        for (sticky, second_step_model) in [(false, "primary"), (true, "fb-b")] {
+            args.fallback_sticky = sticky;
+            fs::write(&script_path, script(second_step_model)).expect("write script");
+            let _ = fs::remove_file(&log_path);
+            let answer = run_prompt(&args, &cwd, "").expect("run_prompt");
+            assert_eq!(answer, "done");
+
+            let events: Vec<Value> = fs::read_to_string(&log_path)
+                .expect("read log")
+                .lines()
+                .map(|l| serde_json::from_str(l).expect("json"))
+                .collect();
+            let switches: Vec<(&str, &str)> = events
+                .iter()
+                .filter(|e| e["type"] == "model.switched")
+                .map(|e| (e["from"].as_str().unwrap(), e["to"].as_str().unwrap()))
+                .collect();
+            assert_eq!(switches, [("primary", "fb-a"), ("fb-a", "fb-b")]);
+            let models: Vec<&str> = events
+                .iter()
+                .filter(|e| e["type"] == "message.appended" && e["message"]["role"] == "assistant")
+                .map(|e| e["model"].as_str().unwrap())
+                .collect();
+            assert_eq!(models, ["fb-b", second_step_model]);
+        }
+
+        // Once the chain is used up, the last error ends the run.
+        args.fallback_models = vec!["fb-a".to_string()];
+        fs::write(&script_path, script("primary")).expect("write script");
+        let _ = fs::remove_file(&log_path);
+        let err = run_prompt(&args, &cwd, "").expect_err("chain exhausted");
+        assert!(err.to_string().contains("No endpoints found"), "{err}");
+
+        // A 404 that is not about the model (e.g. a wrong --base-url) does not fall back.
+        fs::write(
+            &script_path,
+            serde_json::json!({"error": "404 page not found", "status": 404}).to_string(),
+        )
+        .expect("write script");
+        let _ = fs::remove_file(&log_path);
+        let err = run_prompt(&args, &cwd, "").expect_err("404 ends the run");
+        assert!(err.to_string().contains("404 page not found"), "{err}");
+        let log = fs::read_to_string(&log_path).expect("read log");
+        assert!(!log.contains("model.switched"));
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn summarize_strategy_replaces_dropped_turns_with_summary() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-summary-{}.jsonl", Uuid::new_v4()));
+        let log_path = dir.join(format!("summary-test-{}.jsonl", Uuid::new_v4()));
+        let list_src = serde_json::json!({"function": {"name": "list_dir", "arguments": {"dir_path": "src", "offset": null, "limit": null, "depth": null}}});
+        let summary_message = format!("{}\n\nListed src.", SUMMARY_MESSAGE_PREFIX);
+        let script = [
+            serde_json::json!({"message": {"tool_calls": [list_src]}}),
+            serde_json::json!({"message": {"tool_calls": [list_src]}}),
+            serde_json::json!({"error": "maximum context length exceeded"}),
+            serde_json::json!({
+                "expect": {"messages": [{"role": "system", "content": SUMMARY_SYSTEM_PROMPT}, {"role": "user"}]},
+                "message": {"content": "Listed src."},
+                "usage": {"prompt_tokens": 50, "completion_tokens": 5, "total_tokens": 55}
+            }),
+            serde_json::json!({
+                "expect": {"messages": [{"role": "system"}, {"role": "user", "content": "hi"}, {"role": "user", "content": summary_message}, {"role": "assistant", "tool_calls": [{"id": "mock_call_2"}]}]},
+                "message": {"tool_calls": [{"function": {"name": "submit", "arguments": {"answer": "done"}}}]},
+                "usage": {"prompt_tokens": 100, "completion_tokens": 5, "total_tokens": 105}
+            }),
+        ];
+        let lines: Vec<String> = script.iter().map(|v| v.to_string()).collect();
+        fs::write(&script_path, lines.join("\n")).expect("write script");
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.context_strategy = Some(ContextStrategy::Summarize);
+        args.exec = true;
+        args.no_submit = false;
+        args.max_steps = Some(5);
+
+        let answer = run_prompt(&args, &cwd, "").expect("run_prompt");
+        assert_eq!(answer, "done");
+
+        let contents = fs::read_to_string(&log_path).expect("read log");
+        let events: Vec<Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("json"))
+            .collect();
+        let summarized = events
+            .iter()
+            .find(|e| e["type"] == "context.summarized")
+            .expect("context.summarized event");
+        assert_eq!(summarized["summary"], "Listed src.");
+        assert_eq!(summarized["dropped_messages"], 2);
+        assert_eq!(summarized["usage"]["input_tokens"], 50);
+        let compacted = events
+            .iter()
+            .find(|e| e["type"] == "context.compacted")
+            .expect("context.compacted event");
+        assert_eq!(compacted["reason"], "context_error");
+        assert_eq!(compacted["strategy"], "summarize");
+        assert_eq!(compacted["prunes"][0]["summary"], "Listed src.");
+        let last = events.last().expect("events");
+        assert_eq!(last["usage"]["input_tokens"], 150);
+
+        // Resuming rebuilds the compacted history rather than the dropped turns.
+        let state = load_resume_state(&log_path).expect("load resume state");
+        let history = serde_json::to_value(&state.messages).expect("messages");
+        assert_eq!(history[2]["content"], summary_message);
+        assert_eq!(history[3]["tool_calls"][0]["id"], "mock_call_2");
+        assert_eq!(state.steps, 3);
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn parallel_tools_runs_every_call_and_records_results_in_order() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-parallel-{}.jsonl", Uuid::new_v4()));
+        let log_path = dir.join(format!("parallel-test-{}.jsonl", Uuid::new_v4()));
+        let read = |path: &str| serde_json::json!({"function": {"name": "read_file", "arguments": {"file_path": path, "offset": null, "limit": 1}}});
+        let script = [
+            serde_json::json!({
+                "expect": {"parallel_tool_calls": true},
+                "message": {"tool_calls": [
+                    read("Cargo.toml"),
+                    read("src/main.rs"),
+                    {"function": {"name": "shell_command", "arguments": {"command": "echo mutating", "workdir": null, "timeout_ms": null}}},
+                    read("Cargo.toml"),
+                ]}
+            }),
+            serde_json::json!({
+                "expect": {"messages": [{}, {}, {},
+                    {"role": "tool", "tool_call_id": "mock_call_1"},
+                    {"role": "tool", "tool_call_id": "mock_call_2"},
+                    {"role": "tool", "tool_call_id": "mock_call_3"},
+                    {"role": "tool", "tool_call_id": "mock_call_4"}
+                ]},
+                "message": {"tool_calls": [{"function": {"name": "submit", "arguments": {"answer": "done"}}}]}
+            }),
+        ];
+        let lines: Vec<String> = script.iter().map(|v| v.to_string()).collect();
+        fs::write(&script_path, lines.join("\n")).expect("write script");
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.parallel_tools = true;
+        args.exec = true;
+        args.no_submit = false;
+        args.max_steps = Some(5);
+
+        let answer = run_prompt(&args, &cwd, "").expect("run_prompt");
+        assert_eq!(answer, "done");
+
+        let contents = fs::read_to_string(&log_path).expect("read log");
+        let tool_results: Vec<Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str::<Value>(l).expect("json"))
+            .filter(|e| e["type"] == "message.appended" && e["message"]["role"] == "tool")
+            .collect();
+        assert_eq!(tool_results.len(), 4);
+        assert!(tool_results[0]["message"]["content"]
+            .as_str()
+            .unwrap()
+            .contains("[package]"));
+        assert!(tool_results[2]["message"]["content"]
+            .as_str()
+            .unwrap()
+            .contains("mutating"));
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&log_path);
+    }
+
+    #[test]
+    fn grades_only_real_answers() {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = cwd.join("target");
+        fs::create_dir_all(&dir).expect("create dir");
+        let script_path = dir.join(format!("mock-grade-{}.jsonl", Uuid::new_v4()));
+        let log_path = dir.join(format!("mock-grade-{}.jsonl", Uuid::new_v4()));
+        fs::write(
+            &script_path,
+            r#"{"message": {"tool_calls": [{"function": {"name": "submit", "arguments": {"answer": "42"}}}]}}"#,
+        )
+        .expect("write script");
+
+        let mut args = test_args("http://unused.invalid".to_string(), log_path.clone());
+        args.provider = ProviderKind::Mock;
+        args.mock_script = Some(script_path.clone());
+        args.exec = true;
+        args.no_submit = false;
+        args.grader = Some("regex:.*".to_string());
+        let grade = |args: &Args| -> Value {
+            let _ = fs::remove_file(&log_path);
+            run_prompt(args, &cwd, "").expect("run_prompt");
+            fs::read_to_string(&log_path)
+                .expect("read log")
+                .lines()
+                .map(|l| serde_json::from_str::<Value>(l).expect("json"))
+                .find(|e| e["type"] == "grade.completed")
+                .expect("grade.completed")
+        };
+
+        // The termination message would match, but a budget stop is never graded.
+        args.max_steps = Some(0);
+        let event = grade(&args);
+        assert_eq!(event["passed"], false);
+        assert_eq!(event["score"], 0.0);
+        assert!(event["explanation"].as_str().unwrap().contains("max_steps"));
+
+        args.max_steps = Some(1);
+        let event = grade(&args);
+        assert_eq!(event["passed"], true);
+        assert_eq!(event["grader"], "regex");
+
+        let _ = fs::remove_file(&script_path);
+        let _ = fs::remove_file(&log_path);
+    }
 }
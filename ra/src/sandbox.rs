@@ -0,0 +1,286 @@
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SandboxKind {
+    /// Run commands directly on the host.
+    None,
+    /// Bubblewrap jail: workspace read-write, everything else read-only.
+    Bwrap,
+    /// `docker exec` into a running container (see --sandbox-container).
+    Docker,
+}
+
+/// Where shell tool processes run, and which paths in-process file writes may touch.
+#[derive(Clone, Debug)]
+pub(crate) struct Sandbox {
+    kind: SandboxKind,
+    workspace: PathBuf,
+    network: bool,
+    container: Option<String>,
+}
+
+impl Sandbox {
+    pub(crate) fn new(
+        kind: SandboxKind,
+        workspace: PathBuf,
+        network: bool,
+        container: Option<String>,
+    ) -> Result<Self> {
+        match kind {
+            SandboxKind::None => {
+                if !network {
+                    bail!("--sandbox-no-network requires --sandbox bwrap");
+                }
+            }
+            SandboxKind::Bwrap => {
+                if !Command::new("bwrap")
+                    .arg("--version")
+                    .output()
+                    .is_ok_and(|o| o.status.success())
+                {
+                    bail!("--sandbox bwrap requires bubblewrap (`bwrap`) on PATH");
+                }
+            }
+            SandboxKind::Docker => {
+                if container.is_none() {
+                    bail!("--sandbox docker requires --sandbox-container");
+                }
+                if !network {
+                    bail!(
+                        "--sandbox-no-network is not supported with --sandbox docker; configure the container's network instead"
+                    );
+                }
+            }
+        }
+        Ok(Self {
+            kind,
+            workspace,
+            network,
+            container,
+        })
+    }
+
+    /// Builds a command that runs `program args` with working directory `workdir`.
+    ///
+    /// For `docker`, the container is expected to mount the workspace at the same path as on
+    /// the host. A timed-out command is killed on our side only; with `docker exec` the process
+    /// inside the container may keep running.
+    pub(crate) fn command(&self, program: &str, args: &[&str], workdir: &Path) -> Command {
+        match self.kind {
+            SandboxKind::None => {
+                let mut cmd = Command::new(program);
+                cmd.args(args).current_dir(workdir);
+                cmd
+            }
+            SandboxKind::Bwrap => {
+                let mut cmd = Command::new("bwrap");
+                cmd.args(["--ro-bind", "/", "/"])
+                    .args(["--dev", "/dev"])
+                    .args(["--proc", "/proc"])
+                    .args(["--tmpfs", "/tmp"])
+                    // After /tmp so a workspace under /tmp stays visible.
+                    .arg("--bind")
+                    .arg(&self.workspace)
+                    .arg(&self.workspace)
+                    .args(["--unshare-pid", "--die-with-parent", "--new-session"]);
+                if !self.network {
+                    cmd.arg("--unshare-net");
+                }
+                cmd.arg("--chdir").arg(workdir).arg("--").arg(program);
+                cmd.args(args);
+                cmd
+            }
+            SandboxKind::Docker => {
+                let mut cmd = Command::new("docker");
+                cmd.args(["exec", "-i", "-w"])
+                    .arg(workdir)
+                    .arg(self.container.as_deref().unwrap_or_default())
+                    .arg(program)
+                    .args(args);
+                cmd
+            }
+        }
+    }
+
+    /// Rejects paths outside the workspace when a sandbox is active. Used by tools that write
+    /// files in-process rather than through [`Sandbox::command`]. Symlinks are followed, since a
+    /// sandboxed command can create one in the workspace that points anywhere.
+    pub(crate) fn check_writable(&self, path: &Path) -> Result<()> {
+        if self.kind == SandboxKind::None {
+            return Ok(());
+        }
+        let workspace = self
+            .workspace
+            .canonicalize()
+            .unwrap_or_else(|_| normalize(&self.workspace));
+        if resolve_real(path)?.starts_with(workspace) {
+            return Ok(());
+        }
+        bail!(
+            "{} is outside the sandbox workspace {}",
+            path.display(),
+            self.workspace.display()
+        )
+    }
+
+    /// Values for the `sandbox:` and `network_access:` lines of the system prompt.
+    pub(crate) fn describe(&self) -> (String, &'static str) {
+        let sandbox = match self.kind {
+            SandboxKind::None => "none".to_string(),
+            SandboxKind::Bwrap => format!(
+                "bwrap (read-write: {}; rest of the filesystem read-only)",
+                self.workspace.display()
+            ),
+            SandboxKind::Docker => format!(
+                "docker (container: {})",
+                self.container.as_deref().unwrap_or_default()
+            ),
+        };
+        let network = match self.kind {
+            SandboxKind::Docker => "container default",
+            _ if self.network => "enabled",
+            _ => "disabled",
+        };
+        (sandbox, network)
+    }
+}
+
+/// Resolves symlinks in the part of `path` that exists, and `.` and `..` lexically in the rest
+/// (which cannot contain links yet), so the prefix check sees where a write would land. A
+/// dangling symlink is an error rather than a path.
+fn resolve_real(path: &Path) -> Result<PathBuf> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while existing.symlink_metadata().is_err() {
+        let (Some(parent), Some(last)) = (existing.parent(), existing.components().next_back())
+        else {
+            break;
+        };
+        rest.push(last);
+        existing = parent;
+    }
+    let mut real = existing
+        .canonicalize()
+        .with_context(|| format!("cannot resolve {}", existing.display()))?;
+    real.extend(rest.into_iter().rev());
+    Ok(normalize(&real))
+}
+
+/// Resolves `.` and `..` lexically so `workspace/../x` cannot escape the prefix check.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sandbox, SandboxKind};
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::{Path, PathBuf};
+    use uuid::Uuid;
+
+    fn args_of(sandbox: &Sandbox) -> (String, Vec<String>) {
+        let cmd = sandbox.command("bash", &["-lc", "ls"], Path::new("/work/sub"));
+        (
+            cmd.get_program().to_string_lossy().to_string(),
+            cmd.get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn wraps_commands_per_backend() {
+        let bwrap = Sandbox {
+            kind: SandboxKind::Bwrap,
+            workspace: PathBuf::from("/work"),
+            network: false,
+            container: None,
+        };
+        let (program, args) = args_of(&bwrap);
+        assert_eq!(program, "bwrap");
+        let joined = args.join(" ");
+        assert!(joined.starts_with("--ro-bind / /"));
+        assert!(joined.contains("--bind /work /work"));
+        assert!(joined.contains("--unshare-net"));
+        assert!(joined.ends_with("--chdir /work/sub -- bash -lc ls"));
+        assert_eq!(bwrap.describe().1, "disabled");
+
+        let docker = Sandbox {
+            kind: SandboxKind::Docker,
+            workspace: PathBuf::from("/work"),
+            network: true,
+            container: Some("dev".to_string()),
+        };
+        let (program, args) = args_of(&docker);
+        assert_eq!(program, "docker");
+        assert_eq!(
+            args,
+            vec!["exec", "-i", "-w", "/work/sub", "dev", "bash", "-lc", "ls"]
+        );
+
+        let none = Sandbox {
+            kind: SandboxKind::None,
+            workspace: PathBuf::from("/work"),
+            network: true,
+            container: None,
+        };
+        let (program, args) = args_of(&none);
+        assert_eq!(program, "bash");
+        assert_eq!(args, vec!["-lc", "ls"]);
+    }
+
+    #[test]
+    fn rejects_writes_through_symlinks_out_of_the_workspace() {
+        let root = std::env::temp_dir().join(format!("ra-sandbox-{}", Uuid::new_v4()));
+        let workspace = root.join("ws");
+        let outside = root.join("outside");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(workspace.join("sub")).unwrap();
+        symlink(&outside, workspace.join("x")).unwrap();
+        symlink(outside.join("f.txt"), workspace.join("f.txt")).unwrap();
+        fs::write(outside.join("g.txt"), "").unwrap();
+        symlink(outside.join("g.txt"), workspace.join("g.txt")).unwrap();
+        symlink(workspace.join("sub"), workspace.join("inside")).unwrap();
+        let sandbox = Sandbox {
+            kind: SandboxKind::Bwrap,
+            workspace: workspace.clone(),
+            network: true,
+            container: None,
+        };
+
+        assert!(sandbox
+            .check_writable(&workspace.join("new/dir/a.txt"))
+            .is_ok());
+        assert!(sandbox
+            .check_writable(&workspace.join("inside/a.txt"))
+            .is_ok());
+        for escape in [
+            "x/passwd",
+            "x/new/a.txt",
+            "f.txt",
+            "g.txt",
+            "new/../../outside/a",
+        ] {
+            assert!(
+                sandbox.check_writable(&workspace.join(escape)).is_err(),
+                "{escape}"
+            );
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+use std::io::BufRead;
+
+/// Returned when an event stream ends (EOF or transport error) before its terminal event
+/// (`[DONE]`, `message_stop`, ...). The request is safe to retry from scratch.
+#[derive(Debug)]
+pub(crate) struct StreamInterrupted(pub(crate) String);
+
+impl fmt::Display for StreamInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stream interrupted: {}", self.0)
+    }
+}
+
+impl std::error::Error for StreamInterrupted {}
+
+pub(crate) struct SseEvent {
+    pub(crate) data: String,
+}
+
+/// Minimal `text/event-stream` reader: yields one event per blank-line-terminated block.
+pub(crate) struct SseReader<R> {
+    reader: R,
+}
+
+impl<R: BufRead> SseReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    pub(crate) fn next_event(&mut self) -> std::io::Result<Option<SseEvent>> {
+        let mut data: Vec<String> = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                // EOF: flush a trailing event that was not followed by a blank line.
+                if data.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(SseEvent {
+                    data: data.join("\n"),
+                }));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                if data.is_empty() {
+                    continue;
+                }
+                return Ok(Some(SseEvent {
+                    data: data.join("\n"),
+                }));
+            }
+            // Lines starting with ':' are comments (OpenRouter uses them as keep-alives).
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+                None => (line, ""),
+            };
+            if field == "data" {
+                data.push(value.to_string());
+            }
+        }
+    }
+}
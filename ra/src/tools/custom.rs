@@ -0,0 +1,238 @@
+use super::shell::run_command;
+use super::{signature_from_schema, tool_error, Tool, ToolExecContext};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// A `--custom-tools` file: `[[tool]]` tables in TOML, or `{"tool": [...]}` in JSON.
+#[derive(Deserialize)]
+struct CustomToolsFile {
+    #[serde(default, alias = "tools")]
+    tool: Vec<CustomToolSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomToolSpec {
+    name: String,
+    description: String,
+    /// Run with `bash -lc` in the workspace. Arguments are exported as `RA_ARG_<NAME>`, and
+    /// all of them as JSON in `RA_ARGS`.
+    command: String,
+    /// JSON schema for the arguments; a tool without one takes no arguments.
+    #[serde(default)]
+    parameters: Option<Value>,
+    /// Also write the arguments as JSON to the command's stdin.
+    #[serde(default)]
+    stdin: bool,
+    /// Lets `--parallel-tools` run the tool concurrently with other read-only tools.
+    #[serde(default)]
+    read_only: bool,
+    timeout_ms: Option<u64>,
+}
+
+/// Loads the tools declared in a `--custom-tools` file (`.json`, otherwise TOML).
+pub(crate) fn load_custom_tools(path: &Path) -> Result<Vec<Box<dyn Tool>>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read custom tools file {}", path.display()))?;
+    let file: CustomToolsFile = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse custom tools file {}", path.display()))?
+    } else {
+        toml::from_str(&text)
+            .with_context(|| format!("failed to parse custom tools file {}", path.display()))?
+    };
+
+    let mut tools: Vec<Box<dyn Tool>> = Vec::new();
+    for spec in file.tool {
+        if spec.name.is_empty()
+            || !spec
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            bail!(
+                "custom tool name must be non-empty and use only letters, digits, '_' and '-': {}",
+                spec.name
+            );
+        }
+        let parameters = spec
+            .parameters
+            .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+        if parameters.get("type").and_then(Value::as_str) != Some("object") {
+            bail!(
+                "parameters of custom tool {} must be a JSON schema of type object",
+                spec.name
+            );
+        }
+        tools.push(Box::new(CustomTool {
+            name: spec.name,
+            description: spec.description,
+            command: spec.command,
+            parameters,
+            stdin: spec.stdin,
+            read_only: spec.read_only,
+            timeout_ms: spec.timeout_ms,
+        }));
+    }
+    Ok(tools)
+}
+
+/// A fixed command exposed as a tool; the model chooses only its arguments.
+struct CustomTool {
+    name: String,
+    description: String,
+    command: String,
+    parameters: Value,
+    stdin: bool,
+    read_only: bool,
+    timeout_ms: Option<u64>,
+}
+
+impl Tool for CustomTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> String {
+        signature_from_schema(&self.name, &self.parameters)
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters
+            }
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let Some(fields) = args.as_object() else {
+            return Ok(tool_error(format!(
+                "{} arguments must be a JSON object",
+                self.name
+            )));
+        };
+        // Passed through `env` rather than `Command::env` so they also reach docker sandboxes.
+        let mut argv = vec![format!("RA_ARGS={args}")];
+        for (key, value) in fields {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            argv.push(format!("{}={value}", env_var_name(key)));
+        }
+        argv.extend(["bash".to_string(), "-lc".to_string(), self.command.clone()]);
+        let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+        let command = ctx.sandbox.command("env", &argv, ctx.cwd);
+        let stdin = self.stdin.then(|| args.to_string());
+        run_command(
+            command,
+            ctx.cwd,
+            self.timeout_ms,
+            ctx.max_output_chars,
+            stdin,
+        )
+    }
+}
+
+/// `RA_ARG_` followed by the argument name, upper-cased, with other characters as `_`.
+fn env_var_name(key: &str) -> String {
+    let suffix: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("RA_ARG_{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load_custom_tools;
+    use crate::protocol::{ToolCall, ToolFunction};
+    use crate::retry::RetryPolicy;
+    use crate::sandbox::{Sandbox, SandboxKind};
+    use crate::tools::{ToolExecContext, ToolProfile, ToolRegistry};
+    use serde_json::Value;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn runs_custom_command_tools() {
+        let dir = std::env::temp_dir().join(format!("ra-custom-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("tools.toml");
+        fs::write(
+            &file,
+            r#"
+[[tool]]
+name = "run_tests"
+description = "Run the tests matching a filter."
+command = 'echo "filter=$RA_ARG_FILTER quiet=$RA_ARG_QUIET"; exit 3'
+parameters = { type = "object", properties = { filter = { type = "string" }, quiet = { type = "boolean" } }, required = ["filter"] }
+
+[[tool]]
+name = "lint"
+description = "Lint the workspace."
+command = "cat"
+stdin = true
+read_only = true
+"#,
+        )
+        .unwrap();
+        let tools = load_custom_tools(&file).unwrap();
+        let registry =
+            ToolRegistry::resolve(ToolProfile::Default, None, false, false, false, tools).unwrap();
+        assert!(registry
+            .prompt_section()
+            .contains("- run_tests(filter, quiet?)\n- lint()\n"));
+        assert!(registry.is_read_only("lint") && !registry.is_read_only("run_tests"));
+
+        let sandbox = Sandbox::new(SandboxKind::None, dir.clone(), true, None).unwrap();
+        let ctx = ToolExecContext {
+            cwd: &dir,
+            max_output_chars: 8000,
+            patch_max_offset: 200,
+            sandbox: &sandbox,
+            retry: &RetryPolicy::default(),
+        };
+        let call = |name: &str, arguments: &str| -> Value {
+            let call = ToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: ToolFunction {
+                    name: name.to_string(),
+                    arguments: arguments.to_string(),
+                },
+            };
+            serde_json::from_str(&registry.execute(&call, &ctx).unwrap()).unwrap()
+        };
+
+        let out = call("run_tests", r#"{"filter": "a b; rm -rf x", "quiet": true}"#);
+        assert_eq!(out["exit_code"], 3);
+        assert_eq!(out["stdout"], "filter=a b; rm -rf x quiet=true\n");
+        let out = call("lint", r#"{"fix": false}"#);
+        assert_eq!(out["stdout"], r#"{"fix":false}"#);
+
+        let mut clash = load_custom_tools(&file).unwrap();
+        clash.extend(load_custom_tools(&file).unwrap());
+        assert!(
+            ToolRegistry::resolve(ToolProfile::Default, None, false, false, false, clash).is_err()
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
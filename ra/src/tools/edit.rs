@@ -0,0 +1,308 @@
+use super::{resolve_path, tool_error, truncate, Tool, ToolExecContext};
+use crate::sandbox::Sandbox;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+pub(crate) struct WriteFileTool;
+
+impl Tool for WriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn signature(&self) -> String {
+        "write_file(path, content)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "write_file",
+                "description": "Creates or overwrites a file with the given content (parent directories are created).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Path of the file to write." },
+                        "content": { "type": "string", "description": "Full new content of the file." }
+                    },
+                    "required": ["path", "content"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &["Use write_file for new files or full rewrites."]
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: WriteFileArgs = serde_json::from_value(args)?;
+        write_file(&args, ctx.cwd, ctx.sandbox)
+    }
+
+    fn file_changes(&self, arguments: &str, cwd: &Path) -> Option<Vec<Value>> {
+        let changes = serde_json::from_str::<WriteFileArgs>(arguments)
+            .map(|args| {
+                let exists = resolve_path(cwd, Path::new(&args.path)).exists();
+                let kind = if exists { "update" } else { "add" };
+                vec![json!({ "path": args.path, "kind": kind })]
+            })
+            .unwrap_or_default();
+        Some(changes)
+    }
+}
+
+pub(crate) struct EditFileTool;
+
+impl Tool for EditFileTool {
+    fn name(&self) -> &str {
+        "edit_file"
+    }
+
+    fn signature(&self) -> String {
+        "edit_file(path, old_string, new_string, replace_all?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "edit_file",
+                "description": "Replaces old_string with new_string in a file. old_string must match exactly once unless replace_all is true. Returns a diff of the change.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string", "description": "Path of the file to edit." },
+                        "old_string": { "type": "string", "description": "Exact text to replace (include enough surrounding lines to make it unique)." },
+                        "new_string": { "type": "string", "description": "Replacement text." },
+                        "replace_all": { "type": ["boolean", "null"], "description": "Replace every occurrence instead of requiring a unique match." }
+                    },
+                    "required": ["path", "old_string", "new_string", "replace_all"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &["edit_file replaces an exact old_string that must be unique in the file (include surrounding lines), or every occurrence with replace_all."]
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: EditFileArgs = serde_json::from_value(args)?;
+        edit_file(&args, ctx.cwd, ctx.max_output_chars, ctx.sandbox)
+    }
+
+    fn file_changes(&self, arguments: &str, _cwd: &Path) -> Option<Vec<Value>> {
+        let changes = serde_json::from_str::<EditFileArgs>(arguments)
+            .map(|args| vec![json!({ "path": args.path, "kind": "update" })])
+            .unwrap_or_default();
+        Some(changes)
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WriteFileArgs {
+    pub(crate) path: String,
+    pub(crate) content: String,
+}
+
+fn write_file(args: &WriteFileArgs, cwd: &Path, sandbox: &Sandbox) -> Result<String> {
+    let path = resolve_path(cwd, Path::new(&args.path));
+    if let Err(err) = sandbox.check_writable(&path) {
+        return Ok(tool_error(format!("{err:#}")));
+    }
+    let created = !path.exists();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    }
+    fs::write(&path, &args.content)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    let result = json!({
+        "path": path.display().to_string(),
+        "created": created,
+        "bytes_written": args.content.len(),
+        "total_lines": args.content.lines().count(),
+    });
+    Ok(result.to_string())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EditFileArgs {
+    pub(crate) path: String,
+    pub(crate) old_string: String,
+    pub(crate) new_string: String,
+    pub(crate) replace_all: Option<bool>,
+}
+
+fn edit_file(
+    args: &EditFileArgs,
+    cwd: &Path,
+    max_output_chars: usize,
+    sandbox: &Sandbox,
+) -> Result<String> {
+    /// Replacements shown in the returned diff; the rest are only counted.
+    const MAX_DIFF_HUNKS: usize = 3;
+
+    let path = resolve_path(cwd, Path::new(&args.path));
+    if let Err(err) = sandbox.check_writable(&path) {
+        return Ok(tool_error(format!("{err:#}")));
+    }
+    if args.old_string.is_empty() {
+        return Ok(tool_error(
+            "edit_file.old_string must not be empty (use write_file to create a file)".to_string(),
+        ));
+    }
+    if args.old_string == args.new_string {
+        return Ok(tool_error(
+            "edit_file.old_string and new_string are identical; nothing to change".to_string(),
+        ));
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read file {}", path.display()))?;
+
+    let matches: Vec<usize> = content
+        .match_indices(&args.old_string)
+        .map(|(idx, _)| idx)
+        .collect();
+    let replace_all = args.replace_all.unwrap_or(false);
+    if matches.is_empty() {
+        return Ok(tool_error(format!(
+            "old_string not found in {} (it must match exactly, including whitespace and indentation; re-read the file with read_file)",
+            path.display()
+        )));
+    }
+    if matches.len() > 1 && !replace_all {
+        let lines: Vec<String> = matches
+            .iter()
+            .map(|&idx| (content[..idx].matches('\n').count() + 1).to_string())
+            .collect();
+        return Ok(tool_error(format!(
+            "old_string matches {} times in {} (lines {}); include more surrounding context to make it unique, or set replace_all",
+            matches.len(),
+            path.display(),
+            lines.join(", ")
+        )));
+    }
+
+    let updated = content.replace(&args.old_string, &args.new_string);
+    fs::write(&path, &updated).with_context(|| format!("failed to write {}", path.display()))?;
+
+    // Each match shifts later text by the same amount, so positions in the new content are
+    // known without re-searching.
+    let growth = args.new_string.len() as isize - args.old_string.len() as isize;
+    let mut diff = Vec::new();
+    for (n, &idx) in matches.iter().take(MAX_DIFF_HUNKS).enumerate() {
+        let new_idx = (idx as isize + growth * n as isize) as usize;
+        diff.push(replacement_diff(
+            &content,
+            idx,
+            args.old_string.len(),
+            &updated,
+            new_idx,
+            args.new_string.len(),
+        ));
+    }
+    if matches.len() > MAX_DIFF_HUNKS {
+        diff.push(format!(
+            "... {} more replacements not shown",
+            matches.len() - MAX_DIFF_HUNKS
+        ));
+    }
+    let (diff, truncated) = truncate(&diff.join("\n"), max_output_chars);
+    let result = json!({
+        "path": path.display().to_string(),
+        "replacements": matches.len(),
+        "diff": diff,
+        "truncated": truncated,
+    });
+    Ok(result.to_string())
+}
+
+/// Unified-diff-style hunk for one replacement, widened to whole lines.
+fn replacement_diff(
+    old: &str,
+    old_idx: usize,
+    old_len: usize,
+    new: &str,
+    new_idx: usize,
+    new_len: usize,
+) -> String {
+    fn line_span(text: &str, start: usize, len: usize) -> (usize, &str) {
+        let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+        let end = start + len;
+        let line_end = if len > 0 && text[..end].ends_with('\n') {
+            end - 1
+        } else {
+            text[end..].find('\n').map_or(text.len(), |i| end + i)
+        };
+        let first_line = text[..line_start].matches('\n').count() + 1;
+        (first_line, &text[line_start..line_end])
+    }
+    let (old_line, old_text) = line_span(old, old_idx, old_len);
+    let (new_line, new_text) = line_span(new, new_idx, new_len);
+    let old_lines: Vec<&str> = old_text.split('\n').collect();
+    let new_lines: Vec<&str> = new_text.split('\n').collect();
+    let mut out = format!(
+        "@@ -{},{} +{},{} @@",
+        old_line,
+        old_lines.len(),
+        new_line,
+        new_lines.len()
+    );
+    for line in old_lines {
+        out.push_str(&format!("\n-{line}"));
+    }
+    for line in new_lines {
+        out.push_str(&format!("\n+{line}"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_file, EditFileArgs};
+    use crate::sandbox::{Sandbox, SandboxKind};
+    use serde_json::Value;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn edit_file_requires_a_unique_match() {
+        let dir = std::env::temp_dir().join(format!("ra-edit-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.rs"), "let x = 1;\nlet y = 1;\n").unwrap();
+        let sandbox = Sandbox::new(SandboxKind::None, dir.clone(), true, None).unwrap();
+        let edit = |old: &str, new: &str, replace_all: bool| -> Value {
+            let args = EditFileArgs {
+                path: "a.rs".to_string(),
+                old_string: old.to_string(),
+                new_string: new.to_string(),
+                replace_all: Some(replace_all),
+            };
+            serde_json::from_str(&edit_file(&args, &dir, 8000, &sandbox).unwrap()).unwrap()
+        };
+
+        let out = edit(" = 1", " = 2", false);
+        assert!(out["error"].as_str().unwrap().contains("matches 2 times"));
+        let out = edit("let y = 1;", "let y = 2;\nlet z = 3;", false);
+        assert_eq!(out["replacements"], 1);
+        assert_eq!(
+            out["diff"],
+            "@@ -2,1 +2,2 @@\n-let y = 1;\n+let y = 2;\n+let z = 3;"
+        );
+        let out = edit(";", "", true);
+        assert_eq!(out["replacements"], 3);
+        assert_eq!(
+            fs::read_to_string(dir.join("a.rs")).unwrap(),
+            "let x = 1\nlet y = 2\nlet z = 3\n"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,353 @@
+use super::{resolve_path, tool_error, Tool, ToolExecContext};
+use crate::constants::{DEFAULT_GREP_LIMIT, DEFAULT_LIST_LIMIT, DEFAULT_READ_LIMIT};
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSetBuilder};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+const PAGINATION_NOTE: &str = "Pagination is 1-indexed: read_file.offset and list_dir.offset start at 1 (not 0). limit/depth must be >= 1.";
+
+pub(crate) struct ReadFileTool;
+
+impl Tool for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn signature(&self) -> String {
+        "read_file(file_path, offset?, limit?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "read_file",
+                "description": "Reads a paginated range of lines from a file.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": { "type": "string", "description": "Path to the file to read." },
+                        "offset": { "type": ["integer", "null"], "minimum": 1, "default": 1, "description": "1-indexed start line (>= 1)." },
+                        "limit": { "type": ["integer", "null"], "minimum": 1, "default": 200, "description": "Maximum number of lines to return (>= 1)." }
+                    },
+                    "required": ["file_path", "offset", "limit"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &[PAGINATION_NOTE]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: ReadFileArgs = serde_json::from_value(args)?;
+        read_file(&args, ctx.cwd)
+    }
+}
+
+pub(crate) struct ListDirTool;
+
+impl Tool for ListDirTool {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+
+    fn signature(&self) -> String {
+        "list_dir(dir_path, offset?, limit?, depth?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "list_dir",
+                "description": "Lists directory entries with pagination and depth control.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "dir_path": { "type": "string", "description": "Path to the directory to list." },
+                        "offset": { "type": ["integer", "null"], "minimum": 1, "default": 1, "description": "1-indexed start entry (>= 1)." },
+                        "limit": { "type": ["integer", "null"], "minimum": 1, "default": 200, "description": "Maximum number of entries to return (>= 1)." },
+                        "depth": { "type": ["integer", "null"], "minimum": 1, "default": 1, "description": "Maximum directory depth to traverse (>= 1)." }
+                    },
+                    "required": ["dir_path", "offset", "limit", "depth"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &[PAGINATION_NOTE]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: ListDirArgs = serde_json::from_value(args)?;
+        list_dir(&args, ctx.cwd)
+    }
+}
+
+pub(crate) struct GrepFilesTool;
+
+impl Tool for GrepFilesTool {
+    fn name(&self) -> &str {
+        "grep_files"
+    }
+
+    fn signature(&self) -> String {
+        "grep_files(pattern, path?, include?, limit?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "grep_files",
+                "description": "Searches files for a pattern and returns matching lines.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "pattern": { "type": "string", "description": "Rust regex pattern to search for (escape metacharacters for literal matches)." },
+                        "path": { "type": ["string", "null"], "description": "Root path to search." },
+                        "include": { "type": ["string", "null"], "description": "Optional glob filter for files (matched against path relative to root)." },
+                        "limit": { "type": ["integer", "null"], "minimum": 1, "default": 100, "description": "Maximum number of matches to return (>= 1)." }
+                    },
+                    "required": ["pattern", "path", "include", "limit"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &["grep_files.pattern is a Rust regex. Escape metacharacters if you want a literal match (e.g. use \"main\\(\" to search for \"main(\")."]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: GrepFilesArgs = serde_json::from_value(args)?;
+        grep_files(&args, ctx.cwd)
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ReadFileArgs {
+    pub(crate) file_path: String,
+    pub(crate) offset: Option<usize>,
+    pub(crate) limit: Option<usize>,
+}
+
+fn read_file(args: &ReadFileArgs, cwd: &Path) -> Result<String> {
+    let offset = args.offset.unwrap_or(1);
+    let limit = args
+        .limit
+        .unwrap_or(DEFAULT_READ_LIMIT)
+        .min(DEFAULT_READ_LIMIT);
+    if offset < 1 || limit < 1 {
+        return Ok(tool_error(
+            "invalid pagination: read_file.offset and read_file.limit must be >= 1 (offset is 1-indexed)"
+                .to_string(),
+        ));
+    }
+
+    let path = resolve_path(cwd, Path::new(&args.file_path));
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read file {}", path.display()))?;
+    let lines: Vec<&str> = content.lines().collect();
+    let total = lines.len();
+    if offset > total {
+        return Ok(tool_error(format!(
+            "offset ({}) is beyond total lines ({})",
+            offset, total
+        )));
+    }
+    let end = (offset + limit - 1).min(total);
+    let mut numbered = Vec::new();
+    for (idx, line) in lines[offset - 1..end].iter().enumerate() {
+        numbered.push(format!("{}: {}", idx + offset, line));
+    }
+    let result = json!({
+        "file_path": path.display().to_string(),
+        "total_lines": total,
+        "start_line": offset,
+        "end_line": end,
+        "lines": numbered,
+    });
+    Ok(result.to_string())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ListDirArgs {
+    pub(crate) dir_path: String,
+    pub(crate) offset: Option<usize>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) depth: Option<usize>,
+}
+
+fn list_dir(args: &ListDirArgs, cwd: &Path) -> Result<String> {
+    let offset = args.offset.unwrap_or(1);
+    let limit = args
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .min(DEFAULT_LIST_LIMIT);
+    let depth = args.depth.unwrap_or(1);
+    if offset < 1 || limit < 1 || depth < 1 {
+        return Ok(tool_error(
+            "invalid pagination: list_dir.offset, list_dir.limit, and list_dir.depth must be >= 1 (offset is 1-indexed)"
+                .to_string(),
+        ));
+    }
+
+    let dir = resolve_path(cwd, Path::new(&args.dir_path));
+    let mut entries = Vec::new();
+    for entry in WalkDir::new(&dir).max_depth(depth) {
+        let entry = entry?;
+        if entry.depth() == 0 {
+            continue;
+        }
+        let entry_type = if entry.file_type().is_dir() {
+            "dir"
+        } else {
+            "file"
+        };
+        entries.push(json!({
+            "path": entry.path().display().to_string(),
+            "type": entry_type,
+        }));
+    }
+    entries.sort_by(|a, b| {
+        let a_path = a.get("path").and_then(Value::as_str).unwrap_or("");
+        let b_path = b.get("path").and_then(Value::as_str).unwrap_or("");
+        a_path.cmp(b_path)
+    });
+
+    let total = entries.len();
+    if offset > total && total > 0 {
+        return Ok(tool_error(format!(
+            "offset ({}) is beyond total entries ({})",
+            offset, total
+        )));
+    }
+    let end = (offset + limit - 1).min(total);
+    let slice = if total == 0 {
+        Vec::new()
+    } else {
+        entries[offset - 1..end].to_vec()
+    };
+
+    let result = json!({
+        "dir_path": dir.display().to_string(),
+        "total_entries": total,
+        "start_index": if total == 0 { 0 } else { offset },
+        "end_index": if total == 0 { 0 } else { end },
+        "entries": slice,
+    });
+    Ok(result.to_string())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GrepFilesArgs {
+    pub(crate) pattern: String,
+    pub(crate) path: Option<String>,
+    pub(crate) include: Option<String>,
+    pub(crate) limit: Option<usize>,
+}
+
+fn grep_files(args: &GrepFilesArgs, cwd: &Path) -> Result<String> {
+    let limit = args
+        .limit
+        .unwrap_or(DEFAULT_GREP_LIMIT)
+        .min(DEFAULT_GREP_LIMIT);
+    if limit < 1 {
+        return Ok(tool_error(
+            "invalid limit: grep_files.limit must be >= 1".to_string(),
+        ));
+    }
+
+    let pattern = match Regex::new(&args.pattern) {
+        Ok(p) => p,
+        Err(err) => {
+            return Ok(tool_error(format!(
+                "invalid regex pattern: {}: {} (tip: escape metacharacters for literal matches, e.g. \"main\\\\(\" to match \"main(\")",
+                args.pattern, err
+            )));
+        }
+    };
+
+    let globset = if let Some(include) = &args.include {
+        let glob = Glob::new(include).context("invalid include glob")?;
+        let mut builder = GlobSetBuilder::new();
+        builder.add(glob);
+        Some(builder.build()?)
+    } else {
+        None
+    };
+
+    let root = args
+        .path
+        .as_ref()
+        .map(|p| resolve_path(cwd, Path::new(p)))
+        .unwrap_or_else(|| cwd.to_path_buf());
+
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    for entry in WalkDir::new(&root).into_iter().filter_map(Result::ok) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if let Some(ref set) = globset {
+            // Match include globs against the path relative to the search root so callers can
+            // use patterns like "ra/src/main.rs" or "**/*.rs" without needing absolute paths.
+            let rel = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            if !set.is_match(rel) {
+                continue;
+            }
+        }
+        let content = match fs::read_to_string(entry.path()) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        for (idx, line) in content.lines().enumerate() {
+            if pattern.is_match(line) {
+                matches.push(json!({
+                    "path": entry.path().display().to_string(),
+                    "line": idx + 1,
+                    "text": line,
+                }));
+                if matches.len() >= limit {
+                    truncated = true;
+                    break;
+                }
+            }
+        }
+        if matches.len() >= limit {
+            break;
+        }
+    }
+
+    let result = json!({
+        "pattern": args.pattern.clone(),
+        "root": root.display().to_string(),
+        "matches": matches,
+        "truncated": truncated,
+    });
+    Ok(result.to_string())
+}
@@ -0,0 +1,427 @@
+use super::{signature_from_schema, tool_error, truncate, Tool, ToolExecContext};
+use crate::constants::{MCP_CALL_TIMEOUT, MCP_STARTUP_TIMEOUT};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Launches each `--mcp-server NAME=COMMAND` and returns its tools, named `NAME__tool`.
+/// Servers run on the host (outside any sandbox) with the workspace as their cwd.
+pub(crate) fn launch_mcp_servers(specs: &[String], cwd: &Path) -> Result<Vec<Box<dyn Tool>>> {
+    let mut tools: Vec<Box<dyn Tool>> = Vec::new();
+    let mut names = Vec::new();
+    for spec in specs {
+        let (name, command) = parse_server_spec(spec)?;
+        if names.contains(&name) {
+            bail!("duplicate --mcp-server name: {name}");
+        }
+        let connection = McpConnection::spawn(name, command, cwd)
+            .with_context(|| format!("failed to start MCP server {name}"))?;
+        let listed = connection
+            .list_tools()
+            .with_context(|| format!("failed to list tools of MCP server {name}"))?;
+        let connection = Arc::new(connection);
+        for tool in listed {
+            tools.push(Box::new(McpTool::new(connection.clone(), tool)?));
+        }
+        names.push(name);
+    }
+    Ok(tools)
+}
+
+fn parse_server_spec(spec: &str) -> Result<(&str, &str)> {
+    let Some((name, command)) = spec.split_once('=') else {
+        bail!("--mcp-server must look like NAME=COMMAND, got: {spec}");
+    };
+    let (name, command) = (name.trim(), command.trim());
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("MCP server name must be non-empty and use only letters, digits, and '-': {name}");
+    }
+    if command.is_empty() {
+        bail!("--mcp-server {name} has an empty command");
+    }
+    Ok((name, command))
+}
+
+/// A tool exposed by an MCP server. Calls are forwarded to the server's `tools/call`.
+struct McpTool {
+    connection: Arc<McpConnection>,
+    /// Name offered to the model: `<server>__<tool>`.
+    name: String,
+    /// Name the server knows the tool by.
+    remote_name: String,
+    description: String,
+    input_schema: Value,
+    read_only: bool,
+}
+
+impl McpTool {
+    fn new(connection: Arc<McpConnection>, listed: Value) -> Result<Self> {
+        let remote_name = listed
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                anyhow!(
+                    "MCP server {} listed a tool without a name",
+                    connection.name
+                )
+            })?
+            .to_string();
+        // Function names are limited to [A-Za-z0-9_-] by the model APIs.
+        let sanitized: String = remote_name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let input_schema = match listed.get("inputSchema") {
+            Some(schema @ Value::Object(_)) => schema.clone(),
+            _ => json!({ "type": "object", "properties": {} }),
+        };
+        Ok(Self {
+            name: format!("{}__{}", connection.name, sanitized),
+            remote_name,
+            description: listed
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            input_schema,
+            read_only: listed
+                .pointer("/annotations/readOnlyHint")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            connection,
+        })
+    }
+}
+
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> String {
+        signature_from_schema(&self.name, &self.input_schema)
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.input_schema
+            }
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let result = self.connection.request(
+            "tools/call",
+            json!({ "name": self.remote_name, "arguments": args }),
+            MCP_CALL_TIMEOUT,
+        )?;
+        let text = result_text(&result);
+        let (text, truncated) = truncate(&text, ctx.max_output_chars);
+        if result.get("isError") == Some(&Value::Bool(true)) {
+            return Ok(tool_error(text));
+        }
+        Ok(json!({
+            "content": text,
+            "truncated": truncated,
+        })
+        .to_string())
+    }
+}
+
+/// Flattens a `tools/call` result into text. Non-text content is noted but not inlined.
+fn result_text(result: &Value) -> String {
+    let mut parts = Vec::new();
+    for item in result
+        .get("content")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let kind = item.get("type").and_then(Value::as_str).unwrap_or_default();
+        let part = match kind {
+            "text" => item
+                .get("text")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            "resource" => match item.pointer("/resource/text").and_then(Value::as_str) {
+                Some(text) => text.to_string(),
+                None => format!(
+                    "[resource {}]",
+                    item.pointer("/resource/uri")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                ),
+            },
+            other => format!("[{other} content omitted]"),
+        };
+        parts.push(part);
+    }
+    if parts.is_empty() {
+        if let Some(structured) = result.get("structuredContent") {
+            return structured.to_string();
+        }
+    }
+    parts.join("\n")
+}
+
+/// A running MCP server speaking newline-delimited JSON-RPC over stdio. Requests are
+/// serialized, so concurrent calls to the same server wait for each other.
+struct McpConnection {
+    name: String,
+    child: Child,
+    io: Mutex<McpIo>,
+}
+
+struct McpIo {
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    next_id: u64,
+}
+
+impl McpConnection {
+    fn spawn(name: &str, command: &str, cwd: &Path) -> Result<Self> {
+        let mut child = Command::new("bash")
+            .arg("-c")
+            .arg(command)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("failed to spawn: {command}"))?;
+        let stdin = child.stdin.take().context("missing stdin")?;
+        let stdout = child.stdout.take().context("missing stdout")?;
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                // Servers sometimes log to stdout; anything that is not JSON is skipped.
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let connection = Self {
+            name: name.to_string(),
+            child,
+            io: Mutex::new(McpIo {
+                stdin,
+                messages,
+                next_id: 0,
+            }),
+        };
+        connection.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "ra", "version": env!("CARGO_PKG_VERSION") }
+            }),
+            MCP_STARTUP_TIMEOUT,
+        )?;
+        connection.notify("notifications/initialized")?;
+        Ok(connection)
+    }
+
+    fn list_tools(&self) -> Result<Vec<Value>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params, MCP_STARTUP_TIMEOUT)?;
+            if let Some(listed) = result.get("tools").and_then(Value::as_array) {
+                tools.extend(listed.iter().cloned());
+            }
+            match result.get("nextCursor").and_then(Value::as_str) {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    fn notify(&self, method: &str) -> Result<()> {
+        let mut io = self
+            .io
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        write_message(
+            &mut io.stdin,
+            &json!({ "jsonrpc": "2.0", "method": method }),
+        )
+    }
+
+    fn request(&self, method: &str, params: Value, timeout: Duration) -> Result<Value> {
+        let mut io = self
+            .io
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        io.next_id += 1;
+        let id = io.next_id;
+        write_message(
+            &mut io.stdin,
+            &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+        )
+        .with_context(|| format!("MCP server {} is not accepting requests", self.name))?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = match io.messages.recv_timeout(remaining) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) => bail!(
+                    "MCP server {} did not answer {method} within {}s",
+                    self.name,
+                    timeout.as_secs()
+                ),
+                Err(RecvTimeoutError::Disconnected) => {
+                    bail!("MCP server {} exited", self.name)
+                }
+            };
+            if let Some(server_method) = message.get("method").and_then(Value::as_str) {
+                // Server-to-client requests: answer pings, decline everything else.
+                if let Some(request_id) = message.get("id") {
+                    let reply = if server_method == "ping" {
+                        json!({ "jsonrpc": "2.0", "id": request_id, "result": {} })
+                    } else {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": request_id,
+                            "error": { "code": -32601, "message": "Method not found" }
+                        })
+                    };
+                    write_message(&mut io.stdin, &reply)?;
+                }
+                continue;
+            }
+            // Replies to requests that already timed out are dropped here.
+            if message.get("id").and_then(Value::as_u64) != Some(id) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                bail!(
+                    "MCP server {} returned an error for {method}: {}",
+                    self.name,
+                    error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown error")
+                );
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+}
+
+impl Drop for McpConnection {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn write_message(stdin: &mut ChildStdin, message: &Value) -> Result<()> {
+    writeln!(stdin, "{message}")?;
+    stdin.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::launch_mcp_servers;
+    use crate::protocol::{ToolCall, ToolFunction};
+    use crate::retry::RetryPolicy;
+    use crate::sandbox::{Sandbox, SandboxKind};
+    use crate::tools::{ToolExecContext, ToolProfile, ToolRegistry};
+    use serde_json::Value;
+    use std::path::PathBuf;
+
+    /// `examples/mcp_stub.rs`, which `cargo test` builds alongside the test binary.
+    fn stub_server() -> PathBuf {
+        let exe = std::env::current_exe().unwrap();
+        let profile_dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+        profile_dir.join("examples").join("mcp_stub")
+    }
+
+    #[test]
+    fn routes_calls_to_mcp_server_tools() {
+        let stub = stub_server();
+        assert!(
+            stub.exists(),
+            "missing {}; run cargo build --examples",
+            stub.display()
+        );
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let tools = launch_mcp_servers(&[format!("stub={}", stub.display())], &cwd).unwrap();
+        let registry =
+            ToolRegistry::resolve(ToolProfile::ReadOnly, None, false, false, false, tools).unwrap();
+
+        assert!(registry.contains("stub__echo") && registry.contains("stub__fail"));
+        assert!(registry.is_read_only("stub__echo") && !registry.is_read_only("stub__fail"));
+        let section = registry.prompt_section();
+        assert!(
+            section.contains("- stub__echo(repeat?, text)\n"),
+            "{section}"
+        );
+
+        let sandbox = Sandbox::new(SandboxKind::None, cwd.clone(), true, None).unwrap();
+        let ctx = ToolExecContext {
+            cwd: &cwd,
+            max_output_chars: 12,
+            patch_max_offset: 200,
+            sandbox: &sandbox,
+            retry: &RetryPolicy::default(),
+        };
+        let call = |name: &str, arguments: &str| -> Value {
+            let call = ToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: ToolFunction {
+                    name: name.to_string(),
+                    arguments: arguments.to_string(),
+                },
+            };
+            serde_json::from_str(&registry.execute(&call, &ctx).unwrap()).unwrap()
+        };
+
+        let out = call("stub__echo", r#"{"text": "hi"}"#);
+        assert_eq!(out["content"], "hi");
+        assert_eq!(out["truncated"], false);
+        let out = call("stub__echo", r#"{"text": "abc", "repeat": 10}"#);
+        assert!(out["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("abcabcabcabc\n"));
+        assert_eq!(out["truncated"], true);
+        let out = call("stub__fail", "{}");
+        assert_eq!(out["error"], "boom");
+    }
+}
@@ -0,0 +1,490 @@
+mod custom;
+mod edit;
+mod fs;
+mod mcp;
+mod patch;
+mod session;
+mod shell;
+mod web;
+
+use crate::protocol::ToolCall;
+use crate::retry::RetryPolicy;
+use crate::sandbox::Sandbox;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+pub(crate) use custom::load_custom_tools;
+use edit::{EditFileTool, WriteFileTool};
+use fs::{GrepFilesTool, ListDirTool, ReadFileTool};
+pub(crate) use mcp::launch_mcp_servers;
+use patch::ApplyPatchTool;
+use serde_json::{json, Value};
+use session::ShellSessionTool;
+pub(crate) use shell::run_command;
+use shell::ShellCommandTool;
+use std::path::{Path, PathBuf};
+use web::{WebFindTool, WebOpenTool, WebSearchTool};
+
+pub(crate) struct ToolExecContext<'a> {
+    pub(crate) cwd: &'a Path,
+    pub(crate) max_output_chars: usize,
+    pub(crate) patch_max_offset: usize,
+    pub(crate) sandbox: &'a Sandbox,
+    /// Retry policy for tools that make HTTP requests.
+    pub(crate) retry: &'a RetryPolicy,
+}
+
+/// A tool the model can call. Each tool's module holds its arguments, schema, prompt text,
+/// and implementation; [`ToolRegistry`] decides which tools a run offers.
+pub(crate) trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// How the tool is listed in the system prompt, e.g. `read_file(file_path, offset?, limit?)`.
+    fn signature(&self) -> String;
+
+    /// Function schema sent to the model.
+    fn schema(&self) -> Value;
+
+    /// Entries for the prompt's `Tool usage notes:`. A note shared by several tools is listed once.
+    fn prompt_notes(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Tools without side effects, which `--parallel-tools` may run concurrently.
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String>;
+
+    /// `file_change` entries for tools that modify files, computed before the tool runs.
+    /// `None` for tools that are logged as `command_execution` instead.
+    fn file_changes(&self, _arguments: &str, _cwd: &Path) -> Option<Vec<Value>> {
+        None
+    }
+
+    /// The `command` of this call's `command_execution` item.
+    fn command_string(&self, arguments: &str) -> String {
+        tool_command_string(self.name(), arguments)
+    }
+
+    /// Tools whose exit code only comes from the command they run. A result without one (e.g. an
+    /// error before the command started) is logged with no exit code instead of a made-up 0/1.
+    fn reports_exit_code(&self) -> bool {
+        false
+    }
+
+    /// Tools that may return while their command is still running (`"running": true`). Such a
+    /// result is logged without an exit code and is not a failure.
+    fn may_keep_running(&self) -> bool {
+        false
+    }
+}
+
+/// Ends the run with a final answer. The agent loop handles `submit` calls itself, so this
+/// only contributes the schema and prompt entry.
+struct SubmitTool;
+
+impl Tool for SubmitTool {
+    fn name(&self) -> &str {
+        "submit"
+    }
+
+    fn signature(&self) -> String {
+        "submit(answer)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "submit",
+                "description": "Signals completion and returns the final answer.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "answer": { "type": "string", "description": "Final answer." }
+                    },
+                    "required": ["answer"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn execute(&self, _args: Value, _ctx: &ToolExecContext<'_>) -> Result<String> {
+        Ok(tool_error(
+            "submit is handled by the agent loop".to_string(),
+        ))
+    }
+}
+
+/// Built-in tools in the order they are offered to the model and listed in the prompt.
+fn builtin_tools() -> Vec<Box<dyn Tool>> {
+    vec![
+        Box::new(ShellCommandTool),
+        Box::new(ReadFileTool),
+        Box::new(ListDirTool),
+        Box::new(GrepFilesTool),
+        Box::new(ApplyPatchTool),
+        Box::new(WriteFileTool),
+        Box::new(EditFileTool),
+        Box::new(ShellSessionTool::default()),
+        Box::new(WebSearchTool),
+        Box::new(WebOpenTool),
+        Box::new(WebFindTool),
+        Box::new(SubmitTool),
+    ]
+}
+
+const WEB_TOOLS: &[&str] = &["web_search", "web_open", "web_find"];
+
+/// Named base tool sets, refined further with `--tools`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ToolProfile {
+    /// shell_command, read_file, list_dir, grep_files, apply_patch (baseline).
+    Default,
+    /// write_file and edit_file instead of apply_patch.
+    Edit,
+    /// apply_patch, write_file, and edit_file.
+    All,
+    /// read_file, list_dir, and grep_files only.
+    ReadOnly,
+}
+
+impl ToolProfile {
+    fn tool_names(self) -> &'static [&'static str] {
+        match self {
+            ToolProfile::Default => &[
+                "shell_command",
+                "read_file",
+                "list_dir",
+                "grep_files",
+                "apply_patch",
+            ],
+            ToolProfile::Edit => &[
+                "shell_command",
+                "read_file",
+                "list_dir",
+                "grep_files",
+                "write_file",
+                "edit_file",
+            ],
+            ToolProfile::All => &[
+                "shell_command",
+                "read_file",
+                "list_dir",
+                "grep_files",
+                "apply_patch",
+                "write_file",
+                "edit_file",
+            ],
+            ToolProfile::ReadOnly => &["read_file", "list_dir", "grep_files"],
+        }
+    }
+}
+
+/// The tools offered in this run, resolved from `--tool-profile`, `--tools`, and the flags
+/// that enable optional tools. The agent runs, schedules, and logs tool calls through it.
+pub(crate) struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+    /// Known tools left out of this run, for the error returned when one is called anyway.
+    disabled: Vec<String>,
+}
+
+impl ToolRegistry {
+    /// `tools_spec` is the `--tools` value: comma-separated names that replace the profile's
+    /// tools, or `+name` / `-name` entries that add to or remove from them. `submit` is
+    /// controlled by exec mode and ignored here. `external` tools (custom and MCP tools) are
+    /// offered after the built-ins unless `--tools` leaves them out.
+    pub(crate) fn resolve(
+        profile: ToolProfile,
+        tools_spec: Option<&str>,
+        submit_enabled: bool,
+        web_search_enabled: bool,
+        shell_session_enabled: bool,
+        external: Vec<Box<dyn Tool>>,
+    ) -> Result<Self> {
+        let external_names: Vec<String> = external.iter().map(|t| t.name().to_string()).collect();
+        let mut available = builtin_tools();
+        for tool in external {
+            if available.iter().any(|t| t.name() == tool.name()) {
+                return Err(anyhow!("tool {} is defined more than once", tool.name()));
+            }
+            available.push(tool);
+        }
+        let mut selected: Vec<&str> = profile.tool_names().to_vec();
+        selected.extend(external_names.iter().map(String::as_str));
+        if web_search_enabled {
+            selected.extend(WEB_TOOLS);
+        }
+        if shell_session_enabled {
+            selected.push("shell_session");
+        }
+
+        if let Some(spec) = tools_spec {
+            let entries: Vec<&str> = spec
+                .split(',')
+                .map(str::trim)
+                .filter(|e| !e.is_empty())
+                .collect();
+            let mut allowlist = Vec::new();
+            let mut added = Vec::new();
+            let mut removed = Vec::new();
+            for entry in entries {
+                let (target, name) = match entry.as_bytes()[0] {
+                    b'+' => (&mut added, &entry[1..]),
+                    b'-' => (&mut removed, &entry[1..]),
+                    _ => (&mut allowlist, entry),
+                };
+                if !available.iter().any(|tool| tool.name() == name) {
+                    let known: Vec<&str> = available.iter().map(|tool| tool.name()).collect();
+                    return Err(anyhow!(
+                        "unknown tool in --tools: {name} (known tools: {})",
+                        known.join(", ")
+                    ));
+                }
+                target.push(name);
+            }
+            // submit follows the run mode; say so rather than ignoring the entry.
+            if !submit_enabled && (allowlist.contains(&"submit") || added.contains(&"submit")) {
+                return Err(anyhow!(
+                    "--tools lists submit, but submit is off for this run; pass --exec to enable it"
+                ));
+            }
+            if submit_enabled && removed.contains(&"submit") {
+                return Err(anyhow!(
+                    "--tools removes submit, but this run needs it to finish; pass --no-submit instead"
+                ));
+            }
+            if !allowlist.is_empty() {
+                selected = allowlist;
+            }
+            selected.extend(added);
+            selected.retain(|name| !removed.contains(name));
+        }
+
+        selected.retain(|name| *name != "submit");
+        if submit_enabled {
+            selected.push("submit");
+        }
+        let (tools, disabled): (Vec<_>, Vec<_>) = available
+            .into_iter()
+            .partition(|tool| selected.contains(&tool.name()));
+        Ok(Self {
+            tools,
+            disabled: disabled
+                .iter()
+                .map(|tool| tool.name().to_string())
+                .collect(),
+        })
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|tool| tool.name() == name)
+            .map(|tool| tool.as_ref())
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub(crate) fn is_read_only(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|tool| tool.read_only())
+    }
+
+    pub(crate) fn schemas(&self) -> Vec<Value> {
+        self.tools.iter().map(|tool| tool.schema()).collect()
+    }
+
+    /// The `Tools:` and `Tool usage notes:` sections of the system prompt.
+    pub(crate) fn prompt_section(&self) -> String {
+        let mut section = String::from("Tools:\n");
+        for tool in &self.tools {
+            section.push_str(&format!("- {}\n", tool.signature()));
+        }
+        let mut notes: Vec<&str> = Vec::new();
+        for note in self.tools.iter().flat_map(|tool| tool.prompt_notes()) {
+            if !notes.contains(note) {
+                notes.push(note);
+            }
+        }
+        if !notes.is_empty() {
+            section.push_str("\nTool usage notes:\n");
+            for note in notes {
+                section.push_str(&format!("- {note}\n"));
+            }
+        }
+        section
+    }
+
+    pub(crate) fn execute(
+        &self,
+        tool_call: &ToolCall,
+        ctx: &ToolExecContext<'_>,
+    ) -> Result<String> {
+        let Some(tool) = self.get(&tool_call.function.name) else {
+            return Ok(self.disabled_error(&tool_call.function.name));
+        };
+        let args: Value = serde_json::from_str(&tool_call.function.arguments)?;
+        tool.execute(args, ctx)
+    }
+
+    /// Tool error for a call to a tool that is not offered in this run.
+    fn disabled_error(&self, name: &str) -> String {
+        let reason = if self.disabled.iter().any(|disabled| disabled == name) {
+            format!("Tool {name} is disabled in this run")
+        } else {
+            format!("Unknown tool: {name}")
+        };
+        let available: Vec<&str> = self.tools.iter().map(|tool| tool.name()).collect();
+        tool_error(format!(
+            "{reason}. Available tools: {}.",
+            available.join(", ")
+        ))
+    }
+}
+
+/// Prompt signature for a tool described only by its JSON schema: `name(required, optional?)`.
+fn signature_from_schema(name: &str, parameters: &Value) -> String {
+    let required: Vec<&str> = parameters
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let params: Vec<String> = parameters
+        .get("properties")
+        .and_then(Value::as_object)
+        .map(|props| {
+            props
+                .keys()
+                .map(|key| {
+                    if required.contains(&key.as_str()) {
+                        key.clone()
+                    } else {
+                        format!("{key}?")
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    format!("{}({})", name, params.join(", "))
+}
+
+/// Generic `command_execution` command for a tool call: `tool:<name> <arguments>`.
+pub(crate) fn tool_command_string(tool_name: &str, arguments: &str) -> String {
+    if arguments.trim().is_empty() {
+        format!("tool:{}", tool_name)
+    } else {
+        format!("tool:{} {}", tool_name, arguments)
+    }
+}
+
+pub(crate) fn tool_error(message: String) -> String {
+    json!({
+        "error": message,
+    })
+    .to_string()
+}
+
+pub(crate) fn resolve_path(cwd: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        cwd.join(path)
+    }
+}
+
+pub(crate) fn truncate(value: &str, limit: usize) -> (String, bool) {
+    let mut out = String::new();
+    for (count, ch) in value.chars().enumerate() {
+        if count >= limit {
+            break;
+        }
+        out.push(ch);
+    }
+    if value.chars().count() > limit {
+        out.push_str("\n...[truncated]...");
+        (out, true)
+    } else {
+        (out, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ToolProfile, ToolRegistry};
+    use serde_json::Value;
+
+    #[test]
+    fn resolves_tool_sets_and_prompt_from_the_registry() {
+        let tools = ToolRegistry::resolve(
+            ToolProfile::Default,
+            Some("-shell_command,+web_find"),
+            true,
+            false,
+            false,
+            Vec::new(),
+        )
+        .unwrap();
+        let names: Vec<Value> = tools
+            .schemas()
+            .iter()
+            .map(|t| t["function"]["name"].clone())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "read_file",
+                "list_dir",
+                "grep_files",
+                "apply_patch",
+                "web_find",
+                "submit"
+            ]
+        );
+        let section = tools.prompt_section();
+        assert!(section.starts_with("Tools:\n- read_file(file_path, offset?, limit?)\n"));
+        assert_eq!(section.matches("Pagination is 1-indexed").count(), 1);
+        assert!(section.contains("- Tip: Prefer web_find"));
+
+        let resolve = |spec: &str, submit_enabled: bool| {
+            ToolRegistry::resolve(
+                ToolProfile::Default,
+                Some(spec),
+                submit_enabled,
+                true,
+                false,
+                Vec::new(),
+            )
+        };
+        let err = resolve("read_file,grep_files,submit", false)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("pass --exec"), "{err}");
+        assert!(resolve("-submit", true).is_err());
+        let with_submit = resolve("read_file,grep_files,submit", true).unwrap();
+        assert!(with_submit.contains("submit") && !with_submit.contains("shell_command"));
+
+        let read_only = resolve("read_file,grep_files", false).unwrap();
+        assert!(read_only.contains("grep_files") && !read_only.contains("web_search"));
+        assert!(!read_only.contains("submit"));
+        let error = read_only.disabled_error("shell_command");
+        assert!(error.contains("shell_command is disabled"), "{error}");
+        assert!(
+            error.contains("Available tools: read_file, grep_files."),
+            "{error}"
+        );
+        assert!(ToolRegistry::resolve(
+            ToolProfile::Default,
+            Some("bash"),
+            false,
+            false,
+            false,
+            Vec::new()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,1096 @@
+use super::{resolve_path, tool_error, truncate, Tool, ToolExecContext};
+use crate::sandbox::Sandbox;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+pub(crate) struct ApplyPatchTool;
+
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "apply_patch"
+    }
+
+    fn signature(&self) -> String {
+        "apply_patch(patch)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "apply_patch",
+                "description": "Applies a unified diff, or a Codex-style *** Begin Patch envelope (*** Add File / *** Update File / *** Delete File / *** Move to).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "patch": { "type": "string", "description": "Unified diff or *** Begin Patch envelope to apply." }
+                    },
+                    "required": ["patch"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &["If you need to edit files, prefer apply_patch."]
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: ApplyPatchArgs = serde_json::from_value(args)?;
+        apply_patch(
+            &args,
+            ctx.cwd,
+            ctx.max_output_chars,
+            ctx.patch_max_offset,
+            ctx.sandbox,
+        )
+    }
+
+    fn file_changes(&self, arguments: &str, _cwd: &Path) -> Option<Vec<Value>> {
+        let patch = serde_json::from_str::<ApplyPatchArgs>(arguments)
+            .map(|args| args.patch)
+            .unwrap_or_default();
+        Some(parse_patch_changes(&patch))
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ApplyPatchArgs {
+    pub(crate) patch: String,
+}
+
+/// One file section of a unified diff. A `None` path is `/dev/null` (file added or deleted).
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    hunks: Vec<Hunk>,
+}
+
+struct Hunk {
+    header: String,
+    /// 1-indexed start line on the old side; `None` when the `@@` line has no numbers.
+    old_start: Option<usize>,
+    lines: Vec<HunkLine>,
+    old_no_newline: bool,
+    new_no_newline: bool,
+    /// Codex-style `@@ <line>` anchors, located in order before the hunk's context.
+    anchors: Vec<String>,
+    /// Codex `*** End of File`: the hunk applies at the end of the file.
+    at_eof: bool,
+}
+
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Add(text) => Some(text.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+}
+
+enum FileWrite {
+    Write(PathBuf, String),
+    Remove(PathBuf),
+}
+
+fn apply_patch(
+    args: &ApplyPatchArgs,
+    cwd: &Path,
+    max_output_chars: usize,
+    max_offset: usize,
+    sandbox: &Sandbox,
+) -> Result<String> {
+    let (strip_level, files) = parse_patch(&args.patch);
+    let files = match files {
+        Ok(files) if files.is_empty() => {
+            return Ok(tool_error(
+                "patch contains no file changes (expected a unified diff with ---/+++ headers or a *** Begin Patch envelope)"
+                    .to_string(),
+            ));
+        }
+        Ok(files) => files,
+        Err(err) => return Ok(tool_error(format!("invalid patch: {err:#}"))),
+    };
+
+    // Each section is planned from the file on disk, so a second section for the same path
+    // would silently discard the first one's changes.
+    let mut seen = HashSet::new();
+    for file in &files {
+        let paths: HashSet<PathBuf> = file
+            .old_path
+            .iter()
+            .chain(file.new_path.iter())
+            .map(|p| resolve_path(cwd, Path::new(p)))
+            .collect();
+        for path in paths {
+            if !seen.insert(path.clone()) {
+                return Ok(tool_error(format!(
+                    "invalid patch: {} appears in more than one file section; put all of its hunks in one section",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    // Every hunk of every file is checked before anything is written, so a patch that does not
+    // apply changes nothing.
+    let mut report = Vec::new();
+    let mut failures = Vec::new();
+    let mut writes = Vec::new();
+    for file in &files {
+        match plan_file_patch(file, cwd, max_offset, sandbox) {
+            Ok((planned, notes)) => {
+                writes.extend(planned);
+                report.extend(notes);
+            }
+            Err(errors) => failures.extend(errors),
+        }
+    }
+
+    let exit_code = if failures.is_empty() {
+        commit_writes(writes)?;
+        0
+    } else {
+        failures.push("Patch not applied; no files were changed.".to_string());
+        1
+    };
+
+    let (stdout, stdout_truncated) = truncate(&report.join("\n"), max_output_chars);
+    let (stderr, stderr_truncated) = truncate(&failures.join("\n"), max_output_chars);
+    let result = json!({
+        "strip_level": strip_level,
+        "exit_code": exit_code,
+        "stdout": stdout,
+        "stderr": stderr,
+        "truncated": stdout_truncated || stderr_truncated,
+    });
+    Ok(result.to_string())
+}
+
+/// Performs the planned writes. New contents go to temporary files next to their targets
+/// first, so failing to write any of them leaves every file as it was; only then are they
+/// renamed into place and deleted files removed.
+fn commit_writes(writes: Vec<FileWrite>) -> Result<()> {
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let mut removals = Vec::new();
+    for write in writes {
+        match write {
+            FileWrite::Write(path, content) => match stage_write(&path, &content) {
+                Ok(temp) => staged.push((temp, path)),
+                Err(err) => {
+                    for (temp, _) in &staged {
+                        let _ = fs::remove_file(temp);
+                    }
+                    return Err(err);
+                }
+            },
+            FileWrite::Remove(path) => removals.push(path),
+        }
+    }
+    for (temp, path) in staged {
+        fs::rename(&temp, &path).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    for path in removals {
+        fs::remove_file(&path).with_context(|| format!("failed to delete {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes `content` to a temporary sibling of `path`, keeping the permissions of the file it
+/// will replace, and returns the temporary path.
+fn stage_write(path: &Path, content: &str) -> Result<PathBuf> {
+    let parent = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create directory {}", parent.display()))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = parent.join(format!(".{name}.ra-patch-{}", Uuid::new_v4()));
+    let result = fs::write(&temp, content).and_then(|()| match fs::metadata(path) {
+        Ok(meta) => fs::set_permissions(&temp, meta.permissions()),
+        Err(_) => Ok(()),
+    });
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(err).with_context(|| format!("failed to write {}", path.display()));
+    }
+    Ok(temp)
+}
+
+/// Applies one file's hunks in memory. Returns the writes to perform and report lines, or
+/// every failure found in the file.
+fn plan_file_patch(
+    file: &FilePatch,
+    cwd: &Path,
+    max_offset: usize,
+    sandbox: &Sandbox,
+) -> std::result::Result<(Vec<FileWrite>, Vec<String>), Vec<String>> {
+    let display = file
+        .new_path
+        .as_deref()
+        .or(file.old_path.as_deref())
+        .unwrap_or_default()
+        .to_string();
+    let old_path = file
+        .old_path
+        .as_ref()
+        .map(|p| resolve_path(cwd, Path::new(p)));
+    let new_path = file
+        .new_path
+        .as_ref()
+        .map(|p| resolve_path(cwd, Path::new(p)));
+    for path in old_path.iter().chain(new_path.iter()) {
+        if let Err(err) = sandbox.check_writable(path) {
+            return Err(vec![format!("{display}: {err}")]);
+        }
+    }
+
+    let original = match &old_path {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|err| vec![format!("{display}: cannot read {}: {err}", path.display())])?,
+        None => {
+            if new_path.as_ref().is_some_and(|p| p.exists()) {
+                return Err(vec![format!(
+                    "{display}: patch adds this file but it already exists"
+                )]);
+            }
+            String::new()
+        }
+    };
+    if let (Some(old), Some(new)) = (&old_path, &new_path) {
+        if old != new && new.exists() {
+            return Err(vec![format!(
+                "{display}: patch moves a file here but it already exists"
+            )]);
+        }
+    }
+
+    let crlf = original.contains("\r\n");
+    let mut lines: Vec<String> = original.split('\n').map(str::to_string).collect();
+    if original.is_empty() || original.ends_with('\n') {
+        lines.pop();
+    }
+    let mut trailing_newline = original.is_empty() || original.ends_with('\n');
+
+    let mut notes = vec![format!("patching file {display}")];
+    let mut errors = Vec::new();
+    let mut delta: isize = 0;
+    let mut min_pos = 0;
+    for (idx, hunk) in file.hunks.iter().enumerate() {
+        let number = idx + 1;
+        let old = hunk.old_lines();
+        let new: Vec<String> = hunk
+            .new_lines()
+            .into_iter()
+            .map(|line| {
+                if crlf && !line.ends_with('\r') {
+                    format!("{line}\r")
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        let mut search_from = min_pos;
+        let mut missing_anchor = None;
+        for anchor in &hunk.anchors {
+            match find_line(&lines, anchor, search_from) {
+                Some(pos) => search_from = pos + 1,
+                None => {
+                    missing_anchor = Some(anchor);
+                    break;
+                }
+            }
+        }
+        if let Some(anchor) = missing_anchor {
+            errors.push(format!(
+                "{display}: Hunk #{number} FAILED ({}): anchor line {anchor:?} not found.",
+                hunk.header
+            ));
+            continue;
+        }
+
+        // For pure insertions the old start names the line *after which* to insert.
+        let expected = if hunk.at_eof {
+            Some(lines.len().saturating_sub(old.len()))
+        } else {
+            hunk.old_start
+                .map(|start| {
+                    if old.is_empty() {
+                        start
+                    } else {
+                        start.saturating_sub(1)
+                    }
+                })
+                .map(|start| (start as isize + delta).max(0) as usize)
+        };
+
+        let found = if old.is_empty() {
+            let at = match expected {
+                Some(expected) => expected,
+                None if hunk.anchors.is_empty() => lines.len(),
+                None => search_from,
+            };
+            Some((at.clamp(search_from, lines.len()), false))
+        } else {
+            find_hunk_position(&lines, &old, expected, search_from, max_offset)
+        };
+        let Some((pos, fuzzy)) = found else {
+            errors.push(describe_hunk_failure(
+                &display, number, hunk, &lines, &old, expected, max_offset,
+            ));
+            continue;
+        };
+
+        if let (Some(expected), Some(_)) = (expected, hunk.old_start) {
+            let offset = pos as isize - expected as isize;
+            match (offset, fuzzy) {
+                (0, false) => {}
+                (0, true) => notes.push(format!(
+                    "Hunk #{number} succeeded at {} (whitespace-insensitive match).",
+                    pos + 1
+                )),
+                (offset, fuzzy) => notes.push(format!(
+                    "Hunk #{number} succeeded at {} (offset {offset} lines{}).",
+                    pos + 1,
+                    if fuzzy {
+                        ", whitespace-insensitive match"
+                    } else {
+                        ""
+                    }
+                )),
+            }
+        }
+        let at_end = pos + old.len() == lines.len();
+        let added = new.len();
+        lines.splice(pos..pos + old.len(), new);
+        delta += added as isize - old.len() as isize;
+        min_pos = pos + added;
+        if at_end {
+            if hunk.new_no_newline {
+                trailing_newline = false;
+            } else if hunk.old_no_newline {
+                trailing_newline = true;
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut writes = Vec::new();
+    match (old_path, new_path) {
+        (Some(old), None) => {
+            // A deletion without hunks (Codex `*** Delete File`, binary git diffs) removes
+            // the file whatever its content.
+            if !lines.is_empty() && !file.hunks.is_empty() {
+                return Err(vec![format!(
+                    "{display}: patch deletes this file but its hunks do not remove all of its content"
+                )]);
+            }
+            notes[0] = format!("deleting file {display}");
+            writes.push(FileWrite::Remove(old));
+        }
+        (old, Some(new)) => {
+            let mut content = lines.join("\n");
+            if trailing_newline && !lines.is_empty() {
+                content.push('\n');
+            }
+            if let Some(old) = old.filter(|old| *old != new) {
+                notes[0] = format!("renaming file {} to {display}", old.display());
+                writes.push(FileWrite::Write(new, content));
+                writes.push(FileWrite::Remove(old));
+            } else {
+                writes.push(FileWrite::Write(new, content));
+            }
+        }
+        (None, None) => return Err(vec!["patch section has no file path".to_string()]),
+    }
+    Ok((writes, notes))
+}
+
+/// First line at or after `from` that matches `text`, exactly or ignoring whitespace.
+fn find_line(lines: &[String], text: &str, from: usize) -> Option<usize> {
+    [false, true]
+        .into_iter()
+        .find_map(|fuzzy| (from..lines.len()).find(|&pos| lines_match(&lines[pos], text, fuzzy)))
+}
+
+fn lines_match(line: &str, expected: &str, fuzzy: bool) -> bool {
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    let expected = expected.strip_suffix('\r').unwrap_or(expected);
+    if fuzzy {
+        line.split_whitespace().eq(expected.split_whitespace())
+    } else {
+        line == expected
+    }
+}
+
+/// Finds where `old` occurs, trying exact matches nearest to `expected` first and then
+/// whitespace-insensitive ones. Without an expected line the whole file is searched.
+fn find_hunk_position(
+    lines: &[String],
+    old: &[&str],
+    expected: Option<usize>,
+    min_pos: usize,
+    max_offset: usize,
+) -> Option<(usize, bool)> {
+    if old.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - old.len();
+    let (center, max_offset) = match expected {
+        Some(expected) => (expected, max_offset),
+        None => (min_pos, lines.len()),
+    };
+    let matches_at = |pos: usize, fuzzy: bool| {
+        old.iter()
+            .enumerate()
+            .all(|(i, expected)| lines_match(&lines[pos + i], expected, fuzzy))
+    };
+    for fuzzy in [false, true] {
+        for offset in 0..=max_offset {
+            let mut candidates = vec![center.checked_add(offset)];
+            if offset > 0 {
+                candidates.push(center.checked_sub(offset));
+            }
+            for pos in candidates.into_iter().flatten() {
+                if pos >= min_pos && pos <= last && matches_at(pos, fuzzy) {
+                    return Some((pos, fuzzy));
+                }
+            }
+            if center.saturating_sub(offset) <= min_pos && center + offset >= last {
+                break;
+            }
+        }
+    }
+    None
+}
+
+/// Explains a failed hunk and shows the file lines that resemble its context the most, so the
+/// model can correct the diff.
+fn describe_hunk_failure(
+    display: &str,
+    number: usize,
+    hunk: &Hunk,
+    lines: &[String],
+    old: &[&str],
+    expected: Option<usize>,
+    max_offset: usize,
+) -> String {
+    const MAX_SHOWN: usize = 12;
+    let mut message = match expected {
+        Some(expected) => format!(
+            "{display}: Hunk #{number} FAILED ({}): context/removed lines not found within {max_offset} lines of line {}.",
+            hunk.header,
+            expected + 1
+        ),
+        None => format!(
+            "{display}: Hunk #{number} FAILED ({}): context/removed lines not found.",
+            hunk.header
+        ),
+    };
+
+    let mut best: Option<(usize, usize)> = None;
+    let window = old.len().min(lines.len());
+    for pos in 0..=lines.len() - window {
+        let score = old
+            .iter()
+            .zip(&lines[pos..])
+            .filter(|(expected, line)| lines_match(line, expected, true))
+            .count();
+        let closer = |current: usize| {
+            let target = expected.unwrap_or(0);
+            pos.abs_diff(target) < current.abs_diff(target)
+        };
+        if score > 0
+            && best.is_none_or(|(best_pos, best_score)| {
+                score > best_score || (score == best_score && closer(best_pos))
+            })
+        {
+            best = Some((pos, score));
+        }
+    }
+    match best {
+        Some((pos, score)) => {
+            message.push_str(&format!(
+                "\nNearest match at line {} ({score}/{} lines match):",
+                pos + 1,
+                old.len()
+            ));
+            for (i, line) in lines[pos..].iter().take(window.min(MAX_SHOWN)).enumerate() {
+                message.push_str(&format!(
+                    "\n{}: {}",
+                    pos + i + 1,
+                    line.strip_suffix('\r').unwrap_or(line)
+                ));
+            }
+        }
+        None => {
+            message.push_str("\nNo similar lines found in the file; re-read it before retrying.")
+        }
+    }
+    message
+}
+
+/// Parses either patch format: a Codex `*** Begin Patch` envelope or a unified diff. Returns
+/// the path strip level used for unified diffs (`None` for the envelope, whose paths are bare).
+fn parse_patch(patch: &str) -> (Option<usize>, Result<Vec<FilePatch>>) {
+    if patch.lines().any(|line| line.trim() == "*** Begin Patch") {
+        return (None, parse_codex_patch(patch));
+    }
+    let strip_level = detect_patch_strip_level(patch);
+    (Some(strip_level), parse_unified_diff(patch, strip_level))
+}
+
+/// Parses the Codex envelope (`*** Add File` / `*** Update File` / `*** Delete File`, with
+/// optional `*** Move to`). Update hunks carry no line numbers; `@@ <line>` anchors and
+/// `*** End of File` locate them instead.
+fn parse_codex_patch(patch: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut i = lines
+        .iter()
+        .position(|line| line.trim() == "*** Begin Patch")
+        .map_or(0, |pos| pos + 1);
+    let mut files = Vec::new();
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        if line.trim() == "*** End Patch" {
+            break;
+        }
+        if let Some(path) = line.strip_prefix("*** Add File: ") {
+            i += 1;
+            let mut added = Vec::new();
+            while i < lines.len() && !lines[i].starts_with("*** ") {
+                let Some(text) = lines[i].strip_prefix('+') else {
+                    return Err(anyhow!(
+                        "line {}: lines of an added file must start with '+'",
+                        i + 1
+                    ));
+                };
+                added.push(HunkLine::Add(text.to_string()));
+                i += 1;
+            }
+            files.push(FilePatch {
+                old_path: None,
+                new_path: Some(path.trim().to_string()),
+                hunks: vec![codex_hunk(Vec::new(), added, Some(0))],
+            });
+        } else if let Some(path) = line.strip_prefix("*** Delete File: ") {
+            files.push(FilePatch {
+                old_path: Some(path.trim().to_string()),
+                new_path: None,
+                hunks: Vec::new(),
+            });
+            i += 1;
+        } else if let Some(path) = line.strip_prefix("*** Update File: ") {
+            let path = path.trim().to_string();
+            i += 1;
+            let mut new_path = path.clone();
+            if let Some(dest) = lines.get(i).and_then(|l| l.strip_prefix("*** Move to: ")) {
+                new_path = dest.trim().to_string();
+                i += 1;
+            }
+            let mut hunks = Vec::new();
+            let mut anchors = Vec::new();
+            let mut body: Vec<HunkLine> = Vec::new();
+            let mut at_eof = false;
+            while i < lines.len() {
+                let line = lines[i];
+                if line.trim_end() == "*** End of File" {
+                    at_eof = true;
+                    i += 1;
+                    continue;
+                }
+                if line.starts_with("*** ") {
+                    break;
+                }
+                if let Some(anchor) = line.strip_prefix("@@") {
+                    if !body.is_empty() || at_eof {
+                        let mut hunk = codex_hunk(std::mem::take(&mut anchors), body, None);
+                        hunk.at_eof = at_eof;
+                        hunks.push(hunk);
+                        body = Vec::new();
+                        at_eof = false;
+                    }
+                    let anchor = anchor.trim();
+                    if !anchor.is_empty() {
+                        anchors.push(anchor.to_string());
+                    }
+                    i += 1;
+                    continue;
+                }
+                body.push(match line.chars().next() {
+                    Some('+') => HunkLine::Add(line[1..].to_string()),
+                    Some('-') => HunkLine::Remove(line[1..].to_string()),
+                    Some(' ') => HunkLine::Context(line[1..].to_string()),
+                    None => HunkLine::Context(String::new()),
+                    Some(_) => {
+                        return Err(anyhow!(
+                            "line {}: unexpected line in *** Update File: {path} (hunk lines must start with ' ', '+', '-', or @@)",
+                            i + 1
+                        ));
+                    }
+                });
+                i += 1;
+            }
+            if !body.is_empty() {
+                let mut hunk = codex_hunk(anchors, body, None);
+                hunk.at_eof = at_eof;
+                hunks.push(hunk);
+            }
+            if hunks.is_empty() && new_path == path {
+                return Err(anyhow!("*** Update File: {path} has no changes"));
+            }
+            files.push(FilePatch {
+                old_path: Some(path),
+                new_path: Some(new_path),
+                hunks,
+            });
+        } else if line.trim().is_empty() {
+            i += 1;
+        } else {
+            return Err(anyhow!(
+                "line {}: expected *** Add File, *** Update File, *** Delete File, or *** End Patch, got {line:?}",
+                i + 1
+            ));
+        }
+    }
+    Ok(files)
+}
+
+fn codex_hunk(anchors: Vec<String>, lines: Vec<HunkLine>, old_start: Option<usize>) -> Hunk {
+    let header = match anchors.last() {
+        Some(anchor) => format!("@@ {anchor}"),
+        None => "@@".to_string(),
+    };
+    Hunk {
+        header,
+        old_start,
+        lines,
+        old_no_newline: false,
+        new_no_newline: false,
+        anchors,
+        at_eof: false,
+    }
+}
+
+fn parse_unified_diff(patch: &str, strip_level: usize) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    // Set while a `diff --git` section has not seen its ---/+++ headers yet.
+    let mut git_section_open = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let mut paths = rest.split_whitespace();
+            let old = paths.next().map(|p| strip_components(p, strip_level));
+            let new = paths.next().map(|p| strip_components(p, strip_level));
+            files.push(FilePatch {
+                old_path: old.or(new.clone()),
+                new_path: new,
+                hunks: Vec::new(),
+            });
+            git_section_open = true;
+            i += 1;
+            continue;
+        }
+        if git_section_open {
+            let file = files.last_mut().expect("git section has a file");
+            if line.starts_with("new file mode") {
+                file.old_path = None;
+            } else if line.starts_with("deleted file mode") {
+                file.new_path = None;
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                file.old_path = Some(path.trim().to_string());
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.new_path = Some(path.trim().to_string());
+            }
+        }
+        if let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.get(i + 1).and_then(|next| next.strip_prefix("+++ ")),
+        ) {
+            let old_path = patch_header_path(old, strip_level);
+            let new_path = patch_header_path(new, strip_level);
+            if git_section_open {
+                let file = files.last_mut().expect("git section has a file");
+                file.old_path = old_path;
+                file.new_path = new_path;
+            } else {
+                files.push(FilePatch {
+                    old_path,
+                    new_path,
+                    hunks: Vec::new(),
+                });
+            }
+            git_section_open = false;
+            i += 2;
+            continue;
+        }
+        if line.starts_with("@@") {
+            let Some(file) = files.last_mut() else {
+                return Err(anyhow!(
+                    "line {}: hunk before any ---/+++ file header",
+                    i + 1
+                ));
+            };
+            git_section_open = false;
+            let (hunk, next) = parse_hunk(&lines, i)?;
+            file.hunks.push(hunk);
+            i = next;
+            continue;
+        }
+        if !git_section_open
+            && !files.is_empty()
+            && (line.starts_with('+') || line.starts_with('-'))
+        {
+            return Err(anyhow!(
+                "line {}: diff line outside of a hunk (check the line counts in the preceding @@ header)",
+                i + 1
+            ));
+        }
+        i += 1;
+    }
+    Ok(files)
+}
+
+/// Parses the hunk starting at `lines[start]` (its `@@` line). Returns the hunk and the index
+/// of the first line after it.
+fn parse_hunk(lines: &[&str], start: usize) -> Result<(Hunk, usize)> {
+    let header = lines[start].to_string();
+    static HUNK_HEADER: OnceLock<Regex> = OnceLock::new();
+    let ranges = HUNK_HEADER
+        .get_or_init(|| {
+            Regex::new(r"^@@ -(\d+)(?:,(\d+))? \+(\d+)(?:,(\d+))? @@")
+                .expect("valid hunk header regex")
+        })
+        .captures(&header)
+        .map(|caps| {
+            let num = |idx: usize, default: usize| {
+                caps.get(idx)
+                    .and_then(|m| m.as_str().parse().ok())
+                    .unwrap_or(default)
+            };
+            (num(1, 0), num(2, 1), num(4, 1))
+        });
+    // Models often get the counts wrong; they only bound the hunk, and a hunk also ends at
+    // the next header.
+    let (old_start, mut old_remaining, mut new_remaining) = match ranges {
+        Some((old_start, old_len, new_len)) => (Some(old_start), Some(old_len), Some(new_len)),
+        None => (None, None, None),
+    };
+
+    let mut hunk = Hunk {
+        header: header.trim().to_string(),
+        old_start,
+        lines: Vec::new(),
+        old_no_newline: false,
+        new_no_newline: false,
+        anchors: Vec::new(),
+        at_eof: false,
+    };
+    let mut i = start + 1;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with('\\') {
+            match hunk.lines.last() {
+                Some(HunkLine::Remove(_)) => hunk.old_no_newline = true,
+                Some(HunkLine::Add(_)) => hunk.new_no_newline = true,
+                Some(HunkLine::Context(_)) => {
+                    hunk.old_no_newline = true;
+                    hunk.new_no_newline = true;
+                }
+                None => {}
+            }
+            i += 1;
+            continue;
+        }
+        let done = old_remaining == Some(0) && new_remaining == Some(0);
+        let next_header = line.starts_with("@@")
+            || line.starts_with("diff --git ")
+            || (line.starts_with("--- ")
+                && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")));
+        if done || next_header {
+            break;
+        }
+        let (kind, text) = match line.chars().next() {
+            Some(' ') => ('c', &line[1..]),
+            Some('-') => ('-', &line[1..]),
+            Some('+') => ('+', &line[1..]),
+            // Editors and models often strip the single space from blank context lines.
+            None => ('c', ""),
+            Some(_) if ranges.is_none() => break,
+            Some(_) => {
+                return Err(anyhow!(
+                    "line {}: unexpected line in hunk {} (hunk lines must start with ' ', '+', or '-')",
+                    i + 1,
+                    hunk.header
+                ));
+            }
+        };
+        let dec = |remaining: &mut Option<usize>| {
+            if let Some(n) = remaining {
+                *n = n.saturating_sub(1);
+            }
+        };
+        match kind {
+            'c' => {
+                dec(&mut old_remaining);
+                dec(&mut new_remaining);
+                hunk.lines.push(HunkLine::Context(text.to_string()));
+            }
+            '-' => {
+                dec(&mut old_remaining);
+                hunk.lines.push(HunkLine::Remove(text.to_string()));
+            }
+            _ => {
+                dec(&mut new_remaining);
+                hunk.lines.push(HunkLine::Add(text.to_string()));
+            }
+        }
+        i += 1;
+    }
+    Ok((hunk, i))
+}
+
+/// Path from a `---`/`+++` header, without any timestamp and with `strip_level` leading
+/// components removed. `None` means `/dev/null`.
+fn patch_header_path(raw: &str, strip_level: usize) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or(raw).trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    Some(strip_components(path, strip_level))
+}
+
+fn strip_components(path: &str, strip_level: usize) -> String {
+    let mut rest = path;
+    for _ in 0..strip_level {
+        match rest.split_once('/') {
+            Some((_, tail)) if !tail.is_empty() => rest = tail,
+            _ => break,
+        }
+    }
+    rest.to_string()
+}
+
+fn detect_patch_strip_level(patch: &str) -> usize {
+    // Most patches generated by git include a/ and b/ prefixes in file paths and require -p1.
+    // Plain unified diffs without those prefixes typically require -p0.
+    for line in patch.lines() {
+        if line.starts_with("diff --git a/") {
+            return 1;
+        }
+        if line.starts_with("--- a/") || line.starts_with("+++ a/") {
+            return 1;
+        }
+        if line.starts_with("--- b/") || line.starts_with("+++ b/") {
+            return 1;
+        }
+    }
+    0
+}
+
+pub(crate) fn parse_patch_changes(patch: &str) -> Vec<Value> {
+    let files = parse_patch(patch).1.unwrap_or_default();
+    let mut changes = Vec::new();
+    let mut seen = HashSet::new();
+    for file in files {
+        let change = match (file.old_path, file.new_path) {
+            (None, Some(new)) => json!({ "path": new, "kind": "add" }),
+            (Some(old), None) => json!({ "path": old, "kind": "delete" }),
+            (Some(old), Some(new)) if old != new => {
+                json!({ "path": new, "kind": "move", "move_from": old })
+            }
+            (_, Some(new)) => json!({ "path": new, "kind": "update" }),
+            (None, None) => continue,
+        };
+        let path = change["path"].as_str().unwrap_or_default().to_string();
+        if seen.insert(path) {
+            changes.push(change);
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_patch, parse_patch_changes, ApplyPatchArgs};
+    use crate::sandbox::{Sandbox, SandboxKind};
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::path::Path;
+    use uuid::Uuid;
+
+    fn run_patch(dir: &Path, patch: &str) -> Value {
+        let sandbox = Sandbox::new(SandboxKind::None, dir.to_path_buf(), true, None).unwrap();
+        let args = ApplyPatchArgs {
+            patch: patch.to_string(),
+        };
+        let out = apply_patch(&args, dir, 8000, 200, &sandbox).unwrap();
+        serde_json::from_str(&out).unwrap()
+    }
+
+    #[test]
+    fn applies_with_offset_and_whitespace_fuzz() {
+        let dir = std::env::temp_dir().join(format!("ra-patch-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "x\ny\none\ntwo  \nthree\n").unwrap();
+        let patch = "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+TWO\n three\n--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+hi\n";
+        let out = run_patch(&dir, patch);
+        assert_eq!(out["exit_code"], 0, "{out}");
+        assert!(out["stdout"]
+            .as_str()
+            .unwrap()
+            .contains("offset 2 lines, whitespace-insensitive"));
+        assert_eq!(
+            fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "x\ny\none\nTWO\nthree\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("new.txt")).unwrap(), "hi\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failing_hunk_leaves_every_file_untouched() {
+        let dir = std::env::temp_dir().join(format!("ra-patch-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(dir.join("b.txt"), "alpha\nbeta\ngamma\n").unwrap();
+        let patch = "--- a.txt\n+++ a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n--- b.txt\n+++ b.txt\n@@ -1,3 +1,3 @@\n alpha\n-delta\n+DELTA\n gamma\n";
+        let out = run_patch(&dir, patch);
+        assert_eq!(out["exit_code"], 1);
+        let stderr = out["stderr"].as_str().unwrap();
+        assert!(stderr.contains("b.txt: Hunk #1 FAILED"), "{stderr}");
+        assert!(
+            stderr.contains("Nearest match at line 1 (2/3 lines match)"),
+            "{stderr}"
+        );
+        assert!(stderr.contains("2: beta"), "{stderr}");
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\ntwo\n");
+        assert!(!dir.join("a.txt.orig").exists() && !dir.join("b.txt.rej").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_two_sections_for_one_file() {
+        let dir = std::env::temp_dir().join(format!("ra-patch-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        let patch = "--- a.txt\n+++ a.txt\n@@ -1 +1 @@\n-one\n+1\n--- a/a.txt\n+++ b/a.txt\n@@ -3 +3 @@\n-three\n+3\n";
+        let out = run_patch(&dir, patch);
+        let err = out["error"].as_str().unwrap();
+        assert!(
+            err.contains("appears in more than one file section"),
+            "{err}"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "one\ntwo\nthree\n"
+        );
+
+        let patch = "--- a.txt\n+++ a.txt\n@@ -1,3 +1,3 @@\n-one\n+1\n two\n-three\n+3\n";
+        assert_eq!(run_patch(&dir, patch)["exit_code"], 0);
+        assert_eq!(
+            fs::read_to_string(dir.join("a.txt")).unwrap(),
+            "1\ntwo\n3\n"
+        );
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.txt"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn applies_codex_envelope() {
+        let dir = std::env::temp_dir().join(format!("ra-patch-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("lib.rs"),
+            "fn a() {\n    1\n}\nfn b() {\n    1\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("old.txt"), "bye\n").unwrap();
+        let patch = "*** Begin Patch\n*** Update File: lib.rs\n*** Move to: src/lib.rs\n@@ fn b() {\n-    1\n+    2\n }\n*** End of File\n*** Add File: notes.md\n+# Notes\n*** Delete File: old.txt\n*** End Patch\n";
+        let out = run_patch(&dir, patch);
+        assert_eq!(out["exit_code"], 0, "{out}");
+        assert_eq!(
+            fs::read_to_string(dir.join("src/lib.rs")).unwrap(),
+            "fn a() {\n    1\n}\nfn b() {\n    2\n}\n"
+        );
+        assert!(!dir.join("lib.rs").exists() && !dir.join("old.txt").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("notes.md")).unwrap(),
+            "# Notes\n"
+        );
+        assert_eq!(
+            parse_patch_changes(patch),
+            vec![
+                json!({"path": "src/lib.rs", "kind": "move", "move_from": "lib.rs"}),
+                json!({"path": "notes.md", "kind": "add"}),
+                json!({"path": "old.txt", "kind": "delete"}),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn refuses_to_move_onto_an_existing_file() {
+        let dir = std::env::temp_dir().join(format!("ra-patch-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "one\n").unwrap();
+        fs::write(dir.join("b.txt"), "keep\n").unwrap();
+        let patch = "*** Begin Patch\n*** Update File: a.txt\n*** Move to: b.txt\n@@\n-one\n+1\n*** End Patch\n";
+        let out = run_patch(&dir, patch);
+        assert_eq!(out["exit_code"], 1);
+        let stderr = out["stderr"].as_str().unwrap();
+        assert!(
+            stderr.contains("b.txt: patch moves a file here but it already exists"),
+            "{stderr}"
+        );
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "keep\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reports_changes_from_git_headers() {
+        let patch = "diff --git a/old.rs b/new.rs\nsimilarity index 100%\nrename from old.rs\nrename to new.rs\ndiff --git a/gone.rs b/gone.rs\ndeleted file mode 100644\n--- a/gone.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-x\n";
+        assert_eq!(
+            parse_patch_changes(patch),
+            vec![
+                json!({"path": "new.rs", "kind": "move", "move_from": "old.rs"}),
+                json!({"path": "gone.rs", "kind": "delete"}),
+            ]
+        );
+    }
+}
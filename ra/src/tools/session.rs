@@ -0,0 +1,457 @@
+use super::{tool_error, truncate, Tool, ToolExecContext};
+use crate::sandbox::Sandbox;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// How long an interrupted command gets to exit before the whole session is restarted.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize)]
+pub(crate) struct ShellSessionArgs {
+    pub(crate) command: Option<String>,
+    pub(crate) input: Option<String>,
+    pub(crate) interrupt: Option<bool>,
+    pub(crate) timeout_ms: Option<u64>,
+    pub(crate) max_output_chars: Option<usize>,
+}
+
+/// The `shell_session` tool. It owns the session, so the shell lives as long as the registry.
+#[derive(Default)]
+pub(crate) struct ShellSessionTool {
+    session: ShellSession,
+}
+
+impl Tool for ShellSessionTool {
+    fn name(&self) -> &str {
+        "shell_session"
+    }
+
+    fn signature(&self) -> String {
+        "shell_session(command?, input?, interrupt?, timeout_ms?, max_output_chars?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "shell_session",
+                "description": "Runs a command in a persistent bash session (cd, env vars, and background jobs carry over between calls). If a command is still running after timeout_ms, call again with input to write to its stdin, interrupt to send Ctrl-C, or no command to keep waiting.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": ["string", "null"], "description": "Command to run in the session (must be null while a command is running)." },
+                        "input": { "type": ["string", "null"], "description": "Text to write to the running command's stdin (include a trailing newline to submit a line)." },
+                        "interrupt": { "type": ["boolean", "null"], "description": "Send SIGINT to the running command." },
+                        "timeout_ms": { "type": ["number", "null"], "description": "How long to wait for the command to finish before returning with running=true." },
+                        "max_output_chars": { "type": ["number", "null"], "description": "Maximum output characters to return." }
+                    },
+                    "required": ["command", "input", "interrupt", "timeout_ms", "max_output_chars"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &["shell_session keeps state between calls. Start long-running servers in the background (e.g. `cmd > server.log 2>&1 &`) and use timeout_ms for commands that may not exit."]
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: ShellSessionArgs = serde_json::from_value(args)?;
+        self.session
+            .run(&args, ctx.cwd, ctx.max_output_chars, ctx.sandbox)
+    }
+
+    fn command_string(&self, arguments: &str) -> String {
+        let Ok(args) = serde_json::from_str::<ShellSessionArgs>(arguments) else {
+            return format!("tool:shell_session {}", arguments);
+        };
+        if let Some(command) = args.command {
+            format!("session: {}", command)
+        } else if args.interrupt == Some(true) {
+            "session: <interrupt>".to_string()
+        } else if let Some(input) = args.input {
+            format!("session: <input> {}", input)
+        } else {
+            "session: <wait>".to_string()
+        }
+    }
+
+    fn reports_exit_code(&self) -> bool {
+        true
+    }
+
+    fn may_keep_running(&self) -> bool {
+        true
+    }
+}
+
+/// Long-lived bash process behind the `shell_session` tool, so `cd`, exported variables and
+/// background jobs survive between steps. The process is spawned on first use.
+#[derive(Default)]
+pub(crate) struct ShellSession {
+    process: Mutex<Option<SessionProcess>>,
+}
+
+impl ShellSession {
+    pub(crate) fn run(
+        &self,
+        args: &ShellSessionArgs,
+        cwd: &Path,
+        max_output_chars: usize,
+        sandbox: &Sandbox,
+    ) -> Result<String> {
+        let mut guard = self
+            .process
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if guard.as_mut().is_some_and(|p| p.exited()) {
+            *guard = None;
+        }
+        let process = match guard.as_mut() {
+            Some(process) => process,
+            None => guard.insert(SessionProcess::spawn(cwd, sandbox)?),
+        };
+
+        let running = process.running;
+        if let Some(command) = &args.command {
+            if running {
+                return Ok(tool_error(
+                    "a command is still running in the session; call shell_session with input, interrupt, or no command to wait for it"
+                        .to_string(),
+                ));
+            }
+            process.start(command)?;
+        } else if !running {
+            if args.input.is_some() || args.interrupt == Some(true) {
+                return Ok(tool_error(
+                    "no command is running in the session; pass command to start one".to_string(),
+                ));
+            }
+            return Ok(tool_error(
+                "shell_session requires command (or input/interrupt while a command is running)"
+                    .to_string(),
+            ));
+        }
+        if let Some(input) = &args.input {
+            process.send_input(input)?;
+        }
+
+        let limit = args.max_output_chars.unwrap_or(max_output_chars);
+        let mut restarted = false;
+        let outcome = if args.interrupt == Some(true) {
+            process.interrupt();
+            let outcome = process.wait(Some(INTERRUPT_GRACE));
+            if outcome.exit_code.is_none() {
+                // The signal did not reach the command (or it ignored it); start over.
+                restarted = true;
+            }
+            outcome
+        } else {
+            process.wait(args.timeout_ms.map(Duration::from_millis))
+        };
+        let exited = process.exited();
+        if restarted || exited {
+            *guard = None;
+        }
+
+        let (stdout, stdout_truncated) = truncate(&outcome.stdout, limit);
+        let (stderr, stderr_truncated) = truncate(&outcome.stderr, limit);
+        let running = outcome.exit_code.is_none() && !restarted && !exited;
+        let mut result = json!({
+            "exit_code": outcome.exit_code.unwrap_or(-1),
+            "stdout": stdout,
+            "stderr": stderr,
+            "timed_out": running,
+            "running": running,
+            "truncated": stdout_truncated || stderr_truncated,
+        });
+        if restarted || exited {
+            result["session_restarted"] = json!(true);
+        }
+        Ok(result.to_string())
+    }
+}
+
+#[derive(Default)]
+struct Captured {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    stdout_closed: bool,
+    stderr_closed: bool,
+}
+
+struct WaitOutcome {
+    exit_code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+struct SessionProcess {
+    child: Child,
+    stdin: ChildStdin,
+    captured: Arc<(Mutex<Captured>, Condvar)>,
+    marker: String,
+    running: bool,
+}
+
+impl SessionProcess {
+    fn spawn(cwd: &Path, sandbox: &Sandbox) -> Result<Self> {
+        let mut cmd = sandbox.command("bash", &["--noprofile", "--norc"], cwd);
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Own process group, so interrupts and teardown reach the command and its children.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("failed to spawn shell session in {}", cwd.display()))?;
+
+        let captured = Arc::new((Mutex::new(Captured::default()), Condvar::new()));
+        if let Some(stdout) = child.stdout.take() {
+            spawn_reader(stdout, Arc::clone(&captured), false);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            spawn_reader(stderr, Arc::clone(&captured), true);
+        }
+        let stdin = child.stdin.take().context("shell session has no stdin")?;
+
+        let mut process = Self {
+            child,
+            stdin,
+            captured,
+            marker: format!("__RA_DONE_{}", Uuid::new_v4().simple()),
+            running: false,
+        };
+        // A trap (rather than ignoring SIGINT) keeps the shell alive on interrupt while
+        // commands still get the default disposition.
+        process.write("trap ':' INT\n")?;
+        Ok(process)
+    }
+
+    fn start(&mut self, command: &str) -> Result<()> {
+        // The whole block is parsed before it runs, so the sentinels are never left on stdin
+        // for the command to read.
+        let script = format!(
+            "{{\n{command}\n}}; __ra_rc=$?; printf '{m}_%s__\\n' \"$__ra_rc\"; printf '{m}__\\n' >&2\n",
+            m = self.marker,
+        );
+        self.write(&script)?;
+        self.running = true;
+        Ok(())
+    }
+
+    fn send_input(&mut self, input: &str) -> Result<()> {
+        self.write(input)
+    }
+
+    fn write(&mut self, data: &str) -> Result<()> {
+        self.stdin
+            .write_all(data.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .context("failed to write to shell session")
+    }
+
+    fn interrupt(&self) {
+        signal_group(self.child.id(), "INT");
+    }
+
+    fn exited(&mut self) -> bool {
+        !matches!(self.child.try_wait(), Ok(None))
+    }
+
+    /// Waits for the running command's sentinels (or the timeout) and drains the output
+    /// captured so far.
+    fn wait(&mut self, timeout: Option<Duration>) -> WaitOutcome {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let stdout_marker = format!("{}_", self.marker);
+        let stderr_marker = format!("{}__\n", self.marker);
+        let (lock, cvar) = &*self.captured;
+        let mut captured = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        loop {
+            let stdout_done = find(&captured.stdout, stdout_marker.as_bytes());
+            let stderr_done = find(&captured.stderr, stderr_marker.as_bytes());
+            if let (Some(out_at), Some(err_at)) = (stdout_done, stderr_done) {
+                let tail = &captured.stdout[out_at + stdout_marker.len()..];
+                if let Some(end) = find(tail, b"__\n") {
+                    let exit_code = String::from_utf8_lossy(&tail[..end])
+                        .parse::<i32>()
+                        .unwrap_or(-1);
+                    let consumed = out_at + stdout_marker.len() + end + 3;
+                    let stdout = drain_text(&mut captured.stdout, Some((out_at, consumed)));
+                    let stderr = drain_text(
+                        &mut captured.stderr,
+                        Some((err_at, err_at + stderr_marker.len())),
+                    );
+                    self.running = false;
+                    return WaitOutcome {
+                        exit_code: Some(exit_code),
+                        stdout,
+                        stderr,
+                    };
+                }
+            }
+            if captured.stdout_closed && captured.stderr_closed {
+                break;
+            }
+            let wait_for = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    deadline - now
+                }
+                None => Duration::from_millis(200),
+            };
+            captured = cvar
+                .wait_timeout(captured, wait_for)
+                .map(|(guard, _)| guard)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+        let stdout = drain_text(&mut captured.stdout, None);
+        let stderr = drain_text(&mut captured.stderr, None);
+        WaitOutcome {
+            exit_code: None,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+impl Drop for SessionProcess {
+    fn drop(&mut self) {
+        // Take background jobs (e.g. dev servers) down with the shell.
+        signal_group(self.child.id(), "KILL");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn spawn_reader<R: Read + Send + 'static>(
+    mut pipe: R,
+    captured: Arc<(Mutex<Captured>, Condvar)>,
+    is_stderr: bool,
+) {
+    thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = pipe.read(&mut buf).unwrap_or(0);
+            let (lock, cvar) = &*captured;
+            let mut guard = lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let captured = &mut *guard;
+            let (data, closed) = if is_stderr {
+                (&mut captured.stderr, &mut captured.stderr_closed)
+            } else {
+                (&mut captured.stdout, &mut captured.stdout_closed)
+            };
+            if n == 0 {
+                *closed = true;
+            } else {
+                data.extend_from_slice(&buf[..n]);
+            }
+            cvar.notify_all();
+            if n == 0 {
+                return;
+            }
+        }
+    });
+}
+
+/// Removes everything up to the end of the sentinel at `sentinel` (or the whole buffer) and
+/// returns the text before the sentinel.
+fn drain_text(buf: &mut Vec<u8>, sentinel: Option<(usize, usize)>) -> String {
+    let (keep, end) = sentinel.unwrap_or((buf.len(), buf.len()));
+    let drained: Vec<u8> = buf.drain(..end).collect();
+    String::from_utf8_lossy(&drained[..keep]).to_string()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn signal_group(pid: u32, signal: &str) {
+    let _ = Command::new("kill")
+        .arg(format!("-{signal}"))
+        .arg("--")
+        .arg(format!("-{pid}"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShellSession, ShellSessionArgs};
+    use crate::sandbox::{Sandbox, SandboxKind};
+    use serde_json::Value;
+    use std::path::PathBuf;
+
+    fn call(
+        session: &ShellSession,
+        command: Option<&str>,
+        input: Option<&str>,
+        interrupt: bool,
+        timeout_ms: Option<u64>,
+    ) -> Value {
+        let cwd = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let sandbox = Sandbox::new(SandboxKind::None, cwd.clone(), true, None).unwrap();
+        let args = ShellSessionArgs {
+            command: command.map(str::to_string),
+            input: input.map(str::to_string),
+            interrupt: Some(interrupt),
+            timeout_ms,
+            max_output_chars: None,
+        };
+        let out = session.run(&args, &cwd, 8000, &sandbox).unwrap();
+        serde_json::from_str(&out).unwrap()
+    }
+
+    #[test]
+    fn keeps_state_and_drives_running_commands() {
+        let session = ShellSession::default();
+        let out = call(&session, Some("cd src && export RA_X=1"), None, false, None);
+        assert_eq!(out["exit_code"], 0);
+        let out = call(
+            &session,
+            Some("echo $RA_X; basename $PWD; false"),
+            None,
+            false,
+            None,
+        );
+        assert_eq!(out["stdout"], "1\nsrc\n");
+        assert_eq!(out["exit_code"], 1);
+
+        let out = call(
+            &session,
+            Some("read line; echo got $line"),
+            None,
+            false,
+            Some(200),
+        );
+        assert_eq!(out["running"], true);
+        assert_eq!(out["exit_code"], -1);
+        let out = call(&session, Some("echo nope"), None, false, None);
+        assert!(out["error"].is_string());
+        let out = call(&session, None, Some("hello\n"), false, Some(5000));
+        assert_eq!(out["stdout"], "got hello\n");
+        assert_eq!(out["exit_code"], 0);
+
+        call(&session, Some("sleep 30"), None, false, Some(100));
+        let out = call(&session, None, None, true, None);
+        assert_eq!(out["running"], false);
+        let out = call(&session, Some("basename $PWD"), None, false, None);
+        assert_eq!(out["stdout"], "src\n");
+    }
+}
@@ -0,0 +1,138 @@
+use super::{resolve_path, truncate, Tool, ToolExecContext};
+use crate::sandbox::Sandbox;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+pub(crate) struct ShellCommandTool;
+
+impl Tool for ShellCommandTool {
+    fn name(&self) -> &str {
+        "shell_command"
+    }
+
+    fn signature(&self) -> String {
+        "shell_command(command, workdir?, timeout_ms?, max_output_chars?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "shell_command",
+                "description": "Runs a shell command and returns its output.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "string", "description": "The shell command to execute." },
+                        "workdir": { "type": ["string", "null"], "description": "Working directory for the command." },
+                        "timeout_ms": { "type": ["number", "null"], "description": "Timeout in milliseconds." },
+                        "max_output_chars": { "type": ["number", "null"], "description": "Maximum output characters to return." }
+                    },
+                    // Some providers enforce `required` includes all keys in `properties`.
+                    // We keep optional semantics by allowing `null` and handling null/missing in the tool.
+                    "required": ["command", "workdir", "timeout_ms", "max_output_chars"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: ShellArgs = serde_json::from_value(args)?;
+        run_shell_command(&args, ctx.cwd, ctx.max_output_chars, ctx.sandbox)
+    }
+
+    fn command_string(&self, arguments: &str) -> String {
+        match serde_json::from_str::<ShellArgs>(arguments) {
+            Ok(args) => format!("bash -lc {}", args.command),
+            Err(_) => format!("bash -lc {}", arguments),
+        }
+    }
+
+    fn reports_exit_code(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ShellArgs {
+    pub(crate) command: String,
+    pub(crate) workdir: Option<String>,
+    pub(crate) timeout_ms: Option<u64>,
+    pub(crate) max_output_chars: Option<usize>,
+}
+
+fn run_shell_command(
+    args: &ShellArgs,
+    cwd: &Path,
+    max_output_chars: usize,
+    sandbox: &Sandbox,
+) -> Result<String> {
+    let workdir = args
+        .workdir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| cwd.to_path_buf());
+    let workdir = resolve_path(cwd, &workdir);
+
+    let command = sandbox.command("bash", &["-lc", &args.command], &workdir);
+    let limit = args.max_output_chars.unwrap_or(max_output_chars);
+    run_command(command, &workdir, args.timeout_ms, limit, None)
+}
+
+/// Runs a prepared command and reports its exit code and (truncated) output the way
+/// `shell_command` does. `stdin`, when given, is written to the command's standard input.
+pub(crate) fn run_command(
+    mut command: Command,
+    workdir: &Path,
+    timeout_ms: Option<u64>,
+    limit: usize,
+    stdin: Option<String>,
+) -> Result<String> {
+    if stdin.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to spawn shell command in {}", workdir.display()))?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // Written from a thread so a command that never reads its input cannot block us.
+        thread::spawn(move || {
+            let _ = pipe.write_all(input.as_bytes());
+        });
+    }
+
+    let mut timed_out = false;
+    if let Some(timeout_ms) = timeout_ms {
+        let timeout = Duration::from_millis(timeout_ms);
+        if child.wait_timeout(timeout)?.is_none() {
+            timed_out = true;
+            let _ = child.kill();
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (stdout, stdout_truncated) = truncate(&stdout, limit);
+    let (stderr, stderr_truncated) = truncate(&stderr, limit);
+    let truncated = stdout_truncated || stderr_truncated;
+
+    let result = json!({
+        "exit_code": output.status.code().unwrap_or(-1),
+        "stdout": stdout,
+        "stderr": stderr,
+        "timed_out": timed_out,
+        "truncated": truncated,
+    });
+    Ok(result.to_string())
+}
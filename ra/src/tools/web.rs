@@ -0,0 +1,623 @@
+use super::{tool_error, truncate, Tool, ToolExecContext};
+use crate::constants::DEFAULT_READ_LIMIT;
+use crate::retry::{parse_retry_after, RetryPolicy};
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::time::Duration;
+
+const WEB_FETCH_NOTE: &str =
+    "web_open/web_find fetch live URLs and return extracted text with line numbers for citations.";
+
+pub(crate) struct WebSearchTool;
+
+impl Tool for WebSearchTool {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+
+    fn signature(&self) -> String {
+        "web_search(query, max_results?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "web_search",
+                "description": "Searches the web for up-to-date information (Tavily). Requires RA_TAVILY_API_KEY (or TAVILY_API_KEY).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "Search query." },
+                        "max_results": { "type": ["integer", "null"], "minimum": 1, "maximum": 10, "default": 5, "description": "Max results to return (1-10)." }
+                    },
+                    "required": ["query", "max_results"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &["web_search requires RA_TAVILY_API_KEY (or TAVILY_API_KEY)."]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: WebSearchArgs = serde_json::from_value(args)?;
+        web_search(&args, ctx.max_output_chars, ctx.retry)
+    }
+}
+
+pub(crate) struct WebOpenTool;
+
+impl Tool for WebOpenTool {
+    fn name(&self) -> &str {
+        "web_open"
+    }
+
+    fn signature(&self) -> String {
+        "web_open(url, offset?, limit?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "web_open",
+                "description": "Fetches a URL and returns extracted text with line numbers (for quoting/citations).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string", "description": "URL to fetch (http/https)." },
+                        "offset": { "type": ["integer", "null"], "minimum": 1, "default": 1, "description": "1-indexed start line (>= 1)." },
+                        "limit": { "type": ["integer", "null"], "minimum": 1, "default": 200, "description": "Maximum number of lines to return (>= 1)." }
+                    },
+                    "required": ["url", "offset", "limit"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &[WEB_FETCH_NOTE]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: WebOpenArgs = serde_json::from_value(args)?;
+        web_open(&args, ctx.max_output_chars, ctx.retry)
+    }
+}
+
+pub(crate) struct WebFindTool;
+
+impl Tool for WebFindTool {
+    fn name(&self) -> &str {
+        "web_find"
+    }
+
+    fn signature(&self) -> String {
+        "web_find(url, pattern, max_results?, context_lines?)".to_string()
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": "web_find",
+                "description": "Finds occurrences of a pattern in the extracted text of a URL and returns matching line ranges (for citations).",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "url": { "type": "string", "description": "URL to fetch (http/https)." },
+                        "pattern": { "type": "string", "description": "Case-insensitive substring to find." },
+                        "max_results": { "type": ["integer", "null"], "minimum": 1, "maximum": 50, "default": 10, "description": "Max matches to return (1-50)." },
+                        "context_lines": { "type": ["integer", "null"], "minimum": 0, "maximum": 10, "default": 2, "description": "Lines of context before/after each match (0-10)." }
+                    },
+                    "required": ["url", "pattern", "max_results", "context_lines"],
+                    "additionalProperties": false
+                }
+            }
+        })
+    }
+
+    fn prompt_notes(&self) -> &[&str] {
+        &[
+            WEB_FETCH_NOTE,
+            "Tip: Prefer web_find(url, pattern, ...) over repeatedly calling web_open with different offsets.",
+            "Tip: Prefer official sources when available (e.g. blog.rust-lang.org for Rust release posts).",
+        ]
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, args: Value, ctx: &ToolExecContext<'_>) -> Result<String> {
+        let args: WebFindArgs = serde_json::from_value(args)?;
+        web_find(&args, ctx.max_output_chars, ctx.retry)
+    }
+}
+
+fn web_max_bytes() -> u64 {
+    const DEFAULT: u64 = 2 * 1024 * 1024; // 2 MiB
+    const MIN: u64 = 64 * 1024; // 64 KiB
+    const MAX: u64 = 20 * 1024 * 1024; // 20 MiB
+
+    let raw = std::env::var("RA_WEB_MAX_BYTES").ok();
+    let Some(raw) = raw.filter(|s| !s.trim().is_empty()) else {
+        return DEFAULT;
+    };
+    match raw.trim().parse::<u64>() {
+        Ok(v) => v.clamp(MIN, MAX),
+        Err(_) => DEFAULT,
+    }
+}
+
+fn should_retry_reqwest_error(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect()
+}
+
+fn should_retry_http_status(status: reqwest::StatusCode) -> bool {
+    status.as_u16() == 408 || status.as_u16() == 429 || status.is_server_error()
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WebSearchArgs {
+    pub(crate) query: String,
+    pub(crate) max_results: Option<usize>,
+}
+
+fn web_search(
+    args: &WebSearchArgs,
+    max_output_chars: usize,
+    retry: &RetryPolicy,
+) -> Result<String> {
+    let api_key = std::env::var("RA_TAVILY_API_KEY")
+        .or_else(|_| std::env::var("TAVILY_API_KEY"))
+        .ok();
+    let Some(api_key) = api_key.filter(|k| !k.is_empty()) else {
+        return Ok(tool_error(
+            "web_search requires RA_TAVILY_API_KEY (or TAVILY_API_KEY)".to_string(),
+        ));
+    };
+
+    let max_results = args.max_results.unwrap_or(5).clamp(1, 10);
+    tavily_web_search(args, &api_key, max_results, max_output_chars, retry)
+}
+
+fn tavily_web_search(
+    args: &WebSearchArgs,
+    api_key: &str,
+    max_results: usize,
+    max_output_chars: usize,
+    retry: &RetryPolicy,
+) -> Result<String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("failed to build HTTP client for web_search")?;
+
+    let tavily_base_url = std::env::var("RA_TAVILY_BASE_URL")
+        .unwrap_or_else(|_| "https://api.tavily.com".to_string());
+    let tavily_base_url = tavily_base_url.trim_end_matches('/').to_string();
+    let endpoint = format!("{}/search", tavily_base_url);
+
+    let host = reqwest::Url::parse(&endpoint)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut retry = retry.start("web_search", &host);
+
+    loop {
+        let resp = client
+            .post(&endpoint)
+            .header(
+                reqwest::header::USER_AGENT,
+                format!("ra-cli/{}", env!("CARGO_PKG_VERSION")),
+            )
+            .json(&json!({
+                "api_key": api_key,
+                "query": args.query,
+                "max_results": max_results,
+                "search_depth": "basic",
+                "include_answer": false,
+                "include_raw_content": false,
+                "include_images": false
+            }))
+            .send();
+
+        let resp = match resp {
+            Ok(r) => r,
+            Err(err) => {
+                if should_retry_reqwest_error(&err) && retry.wait(None, &err.to_string()) {
+                    continue;
+                }
+                return Ok(tool_error(format!(
+                    "web_search HTTP request failed: POST {}: {}",
+                    endpoint, err
+                )));
+            }
+        };
+
+        let status = resp.status();
+        let retry_after = resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(parse_retry_after);
+        let text = resp.text().unwrap_or_else(|_| "".to_string());
+
+        if status.is_success() {
+            // Continue parsing below using `text`.
+            retry.succeeded();
+        } else {
+            if should_retry_http_status(status)
+                && retry.wait(retry_after, &format!("HTTP {}", status.as_u16()))
+            {
+                continue;
+            }
+            let (snippet, _) = truncate(&text, 2000);
+            return Ok(tool_error(format!(
+                "web_search provider error (HTTP {}): {}",
+                status.as_u16(),
+                snippet
+            )));
+        }
+
+        // Parse the successful body.
+        #[derive(Deserialize)]
+        struct TavilyResponse {
+            #[serde(default)]
+            results: Vec<TavilyResult>,
+        }
+
+        #[derive(Deserialize)]
+        struct TavilyResult {
+            #[serde(default)]
+            title: Option<String>,
+            url: String,
+            #[serde(default)]
+            content: Option<String>,
+            #[serde(default)]
+            score: Option<f64>,
+        }
+
+        let parsed: TavilyResponse = serde_json::from_str(&text).with_context(|| {
+            let (snippet, _) = truncate(&text, 2000);
+            format!("web_search provider returned invalid JSON: {}", snippet)
+        })?;
+
+        let results: Vec<Value> = parsed
+            .results
+            .into_iter()
+            .take(max_results)
+            .map(|r| {
+                json!({
+                    "title": r.title.unwrap_or_default(),
+                    "url": r.url,
+                    "content": r.content.unwrap_or_default(),
+                    "score": r.score
+                })
+            })
+            .collect();
+
+        let out = json!({
+            "provider": "tavily",
+            "query": args.query,
+            "max_results": max_results,
+            "results": results
+        })
+        .to_string();
+        let (out, truncated) = truncate(&out, max_output_chars);
+        if truncated {
+            return Ok(json!({
+                "provider": "tavily",
+                "query": args.query,
+                "max_results": max_results,
+                "results_truncated": true,
+                "output": out
+            })
+            .to_string());
+        }
+        return Ok(out);
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WebOpenArgs {
+    pub(crate) url: String,
+    pub(crate) offset: Option<usize>,
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WebFindArgs {
+    pub(crate) url: String,
+    pub(crate) pattern: String,
+    pub(crate) max_results: Option<usize>,
+    pub(crate) context_lines: Option<usize>,
+}
+
+fn web_open(args: &WebOpenArgs, max_output_chars: usize, retry: &RetryPolicy) -> Result<String> {
+    let offset = args.offset.unwrap_or(1);
+    let limit = args
+        .limit
+        .unwrap_or(DEFAULT_READ_LIMIT)
+        .min(DEFAULT_READ_LIMIT);
+    if offset < 1 || limit < 1 {
+        return Ok(tool_error(
+            "invalid pagination: web_open.offset and web_open.limit must be >= 1 (offset is 1-indexed)"
+                .to_string(),
+        ));
+    }
+
+    let (text, meta) = match fetch_url_as_text(&args.url, retry) {
+        Ok(v) => v,
+        Err(err) => return Ok(tool_error(err.to_string())),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let total = lines.len();
+    if total == 0 {
+        return Ok(json!({
+            "url": args.url,
+            "meta": meta,
+            "total_lines": 0,
+            "start_line": 0,
+            "end_line": 0,
+            "lines": []
+        })
+        .to_string());
+    }
+    if offset > total {
+        return Ok(tool_error(format!(
+            "offset ({offset}) is beyond total lines ({total})"
+        )));
+    }
+    let end = (offset + limit - 1).min(total);
+    let mut numbered = Vec::new();
+    for (idx, line) in lines[offset - 1..end].iter().enumerate() {
+        numbered.push(format!("{}: {}", idx + offset, line));
+    }
+    let out = json!({
+        "url": args.url,
+        "meta": meta,
+        "total_lines": total,
+        "start_line": offset,
+        "end_line": end,
+        "lines": numbered
+    })
+    .to_string();
+    let (out, truncated) = truncate(&out, max_output_chars);
+    if truncated {
+        Ok(json!({"truncated": true, "output": out}).to_string())
+    } else {
+        Ok(out)
+    }
+}
+
+fn web_find(args: &WebFindArgs, max_output_chars: usize, retry: &RetryPolicy) -> Result<String> {
+    let pattern = args.pattern.trim().to_string();
+    if pattern.is_empty() {
+        return Ok(tool_error("web_find.pattern must be non-empty".to_string()));
+    }
+    let max_results = args.max_results.unwrap_or(10).clamp(1, 50);
+    let context_lines = args.context_lines.unwrap_or(2).clamp(0, 10);
+
+    let (text, meta) = match fetch_url_as_text(&args.url, retry) {
+        Ok(v) => v,
+        Err(err) => return Ok(tool_error(err.to_string())),
+    };
+    let lines: Vec<&str> = text.lines().collect();
+    let needle = pattern.to_lowercase();
+
+    let mut matches = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if matches.len() >= max_results {
+            break;
+        }
+        if line.to_lowercase().contains(&needle) {
+            let line_no = i + 1;
+            let start = line_no.saturating_sub(context_lines);
+            let start = start.max(1);
+            let end = (line_no + context_lines).min(lines.len());
+            let mut snippet = Vec::new();
+            for j in start..=end {
+                snippet.push(format!("{}: {}", j, lines[j - 1]));
+            }
+            matches.push(json!({
+                "start_line": start,
+                "end_line": end,
+                "match_line": line_no,
+                "snippet": snippet,
+            }));
+        }
+    }
+
+    let out = json!({
+        "url": args.url,
+        "meta": meta,
+        "pattern": pattern,
+        "matches": matches,
+        "truncated_matches": matches.len() >= max_results
+    })
+    .to_string();
+    let (out, truncated) = truncate(&out, max_output_chars);
+    if truncated {
+        Ok(json!({"truncated": true, "output": out}).to_string())
+    } else {
+        Ok(out)
+    }
+}
+
+fn fetch_url_as_text(url: &str, retry: &RetryPolicy) -> Result<(String, Value)> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(anyhow!(
+            "web_* only supports http:// or https:// URLs (got: {})",
+            url
+        ));
+    }
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("failed to build HTTP client for web_open/web_find")?;
+
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let mut retry = retry.start("web_fetch", &host);
+
+    loop {
+        let resp = client
+            .get(url)
+            .header(
+                reqwest::header::USER_AGENT,
+                format!("ra-cli/{}", env!("CARGO_PKG_VERSION")),
+            )
+            .send();
+
+        let resp = match resp {
+            Ok(r) => r,
+            Err(err) => {
+                if should_retry_reqwest_error(&err) && retry.wait(None, &err.to_string()) {
+                    continue;
+                }
+                return Err(anyhow!("GET {} failed: {}", url, err));
+            }
+        };
+
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let content_type = headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let content_length = headers
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+
+        let max_bytes = web_max_bytes();
+        let truncated_by_limit = content_length.is_some_and(|len| len > max_bytes);
+        let mut buf = Vec::new();
+        let _ = resp.take(max_bytes).read_to_end(&mut buf);
+        let body = String::from_utf8_lossy(&buf).to_string();
+
+        if status.is_success() {
+            retry.succeeded();
+            // Continue parsing below.
+            let is_html = content_type.contains("text/html")
+                || content_type.contains("application/xhtml")
+                || body.contains("<html");
+            let is_text = content_type.starts_with("text/") || content_type.is_empty();
+            let is_json = content_type.starts_with("application/json");
+
+            if !(is_html || is_text || is_json) {
+                return Err(anyhow!(
+                    "unsupported content type for web_open/web_find: {} (url: {})",
+                    if content_type.is_empty() {
+                        "<missing content-type>"
+                    } else {
+                        content_type.as_str()
+                    },
+                    url
+                ));
+            }
+
+            let text = if is_html { html_to_text(&body) } else { body };
+            return Ok((
+                normalize_text(&text),
+                json!({
+                    "status": status.as_u16(),
+                    "content_type": content_type,
+                    "content_length": content_length,
+                    "max_bytes": max_bytes,
+                    "truncated": truncated_by_limit
+                }),
+            ));
+        }
+
+        let retry_after = headers
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(parse_retry_after);
+        if should_retry_http_status(status)
+            && retry.wait(retry_after, &format!("HTTP {}", status.as_u16()))
+        {
+            continue;
+        }
+
+        // Non-success final: return structured meta+error like before.
+        return Ok((
+            String::new(),
+            json!({
+                "status": status.as_u16(),
+                "content_type": content_type,
+                "content_length": content_length,
+                "max_bytes": max_bytes,
+                "truncated": truncated_by_limit,
+                "error": truncate(&body, 2000).0
+            }),
+        ));
+    }
+}
+
+fn html_to_text(html: &str) -> String {
+    // Minimal HTML-to-text for a baseline agent (no external deps).
+    // This is intentionally simple: good enough for citations/line numbers, not perfect rendering.
+    let re_script = Regex::new(r"(?is)<script[^>]*>.*?</script>").unwrap();
+    let re_style = Regex::new(r"(?is)<style[^>]*>.*?</style>").unwrap();
+    let re_nav = Regex::new(r"(?is)<nav[^>]*>.*?</nav>").unwrap();
+    let re_header = Regex::new(r"(?is)<header[^>]*>.*?</header>").unwrap();
+    let re_footer = Regex::new(r"(?is)<footer[^>]*>.*?</footer>").unwrap();
+    let re_aside = Regex::new(r"(?is)<aside[^>]*>.*?</aside>").unwrap();
+
+    let mut s = re_script.replace_all(html, "\n").to_string();
+    s = re_style.replace_all(&s, "\n").to_string();
+    // Remove common boilerplate containers so the model doesn't waste steps paging through nav.
+    s = re_nav.replace_all(&s, "\n").to_string();
+    s = re_header.replace_all(&s, "\n").to_string();
+    s = re_footer.replace_all(&s, "\n").to_string();
+    s = re_aside.replace_all(&s, "\n").to_string();
+
+    // Add newlines for some block-ish tags to preserve structure a bit.
+    let re_breaks = Regex::new(r"(?i)</(p|div|h1|h2|h3|h4|h5|h6|li|tr|br)\s*>").unwrap();
+    s = re_breaks.replace_all(&s, "\n").to_string();
+
+    let re_tags = Regex::new(r"(?is)<[^>]+>").unwrap();
+    s = re_tags.replace_all(&s, "").to_string();
+    s
+}
+
+fn normalize_text(text: &str) -> String {
+    // Collapse excessive blank lines and trim trailing spaces.
+    let mut out_lines = Vec::new();
+    let mut last_blank = false;
+    for line in text.lines() {
+        let line = line.trim_end().to_string();
+        let blank = line.trim().is_empty();
+        if blank {
+            if last_blank {
+                continue;
+            }
+            last_blank = true;
+            out_lines.push(String::new());
+        } else {
+            last_blank = false;
+            out_lines.push(line);
+        }
+    }
+    out_lines.join("\n")
+}